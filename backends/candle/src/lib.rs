@@ -17,10 +17,13 @@ use candle::{DType, Device};
 use candle_nn::VarBuilder;
 use models::Config;
 use std::path::PathBuf;
-use text_embeddings_backend_core::{Backend, BackendError, Batch, Embedding, ModelType};
+use text_embeddings_backend_core::{
+    Backend, BackendError, Batch, DeviceStats, Embedding, ModelType,
+};
 
 pub struct CandleBackend {
     model: Box<dyn Model + Send>,
+    device: Device,
 }
 
 impl CandleBackend {
@@ -78,7 +81,7 @@ impl CandleBackend {
         }
         .s()?;
 
-        let model: Box<dyn Model + Send> = match device {
+        let model: Box<dyn Model + Send> = match &device {
             Device::Cpu => {
                 if config.position_embedding_type == PositionEmbeddingType::Alibi {
                     tracing::info!("Starting JinaBert model on CPU");
@@ -119,7 +122,7 @@ impl CandleBackend {
             }
         };
 
-        Ok(Self { model })
+        Ok(Self { model, device })
     }
 }
 
@@ -132,6 +135,32 @@ impl Backend for CandleBackend {
         self.model.is_padded()
     }
 
+    fn device_stats(&self) -> Option<DeviceStats> {
+        #[cfg(feature = "cuda")]
+        {
+            use candle::cuda_backend::cudarc::driver::result::mem_get_info;
+
+            // Fails when the model does not run on a GPU
+            if let Ok((free, total)) = mem_get_info() {
+                return Some(DeviceStats {
+                    memory_used: Some((total - free) as u64),
+                    memory_total: Some(total as u64),
+                    // Utilization is only available through NVML
+                    utilization: None,
+                });
+            }
+        }
+        None
+    }
+
+    fn device(&self) -> Option<String> {
+        match self.device {
+            Device::Cpu => Some("cpu".to_string()),
+            // The model is loaded on the first GPU
+            Device::Cuda(_) => Some("cuda:0".to_string()),
+        }
+    }
+
     fn embed(&self, batch: Batch) -> Result<Vec<Embedding>, BackendError> {
         let results = self.model.embed(batch).e()?;
         let results = results.to_dtype(DType::F32).e()?.to_vec2().e()?;
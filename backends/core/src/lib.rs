@@ -14,6 +14,19 @@ pub struct Batch {
 
 pub type Embedding = Vec<f32>;
 
+/// Resource usage of the device running the model
+///
+/// Stats that a backend cannot sample are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceStats {
+    /// Device memory in use, in bytes
+    pub memory_used: Option<u64>,
+    /// Total device memory, in bytes
+    pub memory_total: Option<u64>,
+    /// Fraction of time the device was busy, between 0 and 1
+    pub utilization: Option<f32>,
+}
+
 pub trait Backend {
     fn health(&self) -> Result<(), BackendError>;
     fn max_batch_size(&self) -> Option<usize> {
@@ -22,6 +35,15 @@ pub trait Backend {
 
     fn is_padded(&self) -> bool;
 
+    fn device_stats(&self) -> Option<DeviceStats> {
+        None
+    }
+
+    /// Device the model runs on, e.g. `cpu` or `cuda:0`
+    fn device(&self) -> Option<String> {
+        None
+    }
+
     fn embed(&self, batch: Batch) -> Result<Vec<Embedding>, BackendError>;
 
     fn predict(&self, batch: Batch) -> Result<Vec<Vec<f32>>, BackendError>;
@@ -59,4 +81,6 @@ pub enum BackendError {
     Inference(String),
     #[error("Backend is unhealthy")]
     Unhealthy,
+    #[error("Backend timed out: {0}")]
+    Timeout(String),
 }
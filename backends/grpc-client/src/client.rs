@@ -3,6 +3,7 @@ use crate::pb::embedding::v1::embedding_service_client::EmbeddingServiceClient;
 use crate::pb::embedding::v1::*;
 use crate::Result;
 use grpc_metadata::InjectTelemetryContext;
+use std::time::Duration;
 use tonic::transport::{Channel, Uri};
 use tracing::instrument;
 
@@ -23,9 +24,21 @@ impl Client {
     }
 
     /// Returns a client connected to the given unix socket
-    pub async fn connect_uds(path: String) -> Result<Self> {
-        let channel = Channel::from_shared("http://[::]:50051".to_string())
-            .unwrap()
+    ///
+    /// `connect_timeout` bounds each connection attempt and `read_timeout` each request.
+    pub async fn connect_uds(
+        path: String,
+        connect_timeout: Option<Duration>,
+        read_timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let mut endpoint = Channel::from_shared("http://[::]:50051".to_string()).unwrap();
+        if let Some(timeout) = connect_timeout {
+            endpoint = endpoint.connect_timeout(timeout);
+        }
+        if let Some(timeout) = read_timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        let channel = endpoint
             .connect_with_connector(tower::service_fn(move |_: Uri| {
                 tokio::net::UnixStream::connect(path.clone())
             }))
@@ -65,3 +78,63 @@ impl Client {
         Ok(response.embeddings)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientError;
+    use std::time::Instant;
+
+    #[test]
+    fn read_timeout_bounds_requests() {
+        let path = std::env::temp_dir().join(format!("te-grpc-client-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            // Backend accepting connections but never responding
+            let listener = tokio::net::UnixListener::bind(&path).unwrap();
+            let server = tokio::spawn(async move {
+                let mut streams = Vec::new();
+                while let Ok((stream, _)) = listener.accept().await {
+                    streams.push(stream);
+                }
+            });
+
+            let mut client = Client::connect_uds(
+                path.to_string_lossy().to_string(),
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_millis(200)),
+            )
+            .await
+            .unwrap();
+            let start = Instant::now();
+            let err = client.health().await.unwrap_err();
+            assert!(matches!(err, ClientError::Timeout(_)), "{err}");
+            assert!(start.elapsed() < Duration::from_secs(5));
+            server.abort();
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn connect_fails_without_backend() {
+        let path = std::env::temp_dir().join("te-grpc-client-missing");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let err = runtime
+            .block_on(Client::connect_uds(
+                path.to_string_lossy().to_string(),
+                Some(Duration::from_millis(200)),
+                None,
+            ))
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Connection(_)), "{err}");
+    }
+}
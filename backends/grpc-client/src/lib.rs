@@ -9,7 +9,7 @@ pub use pb::embedding::v1::Embedding;
 pub use pb::embedding::v1::HealthResponse;
 use thiserror::Error;
 use tonic::transport;
-use tonic::Status;
+use tonic::{Code, Status};
 
 #[derive(Error, Debug, Clone)]
 pub enum ClientError {
@@ -17,11 +17,17 @@ pub enum ClientError {
     Connection(String),
     #[error("Server error: {0}")]
     Inference(String),
+    #[error("Request timed out: {0}")]
+    Timeout(String),
 }
 
 impl From<Status> for ClientError {
     fn from(err: Status) -> Self {
-        let err = Self::Inference(err.message().to_string());
+        // Requests exceeding the channel timeout are cancelled
+        let err = match err.code() {
+            Code::DeadlineExceeded | Code::Cancelled => Self::Timeout(err.message().to_string()),
+            _ => Self::Inference(err.message().to_string()),
+        };
         tracing::error!("{err}");
         err
     }
@@ -36,3 +42,19 @@ impl From<transport::Error> for ClientError {
 }
 
 pub type Result<T> = std::result::Result<T, ClientError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_requests_are_timeouts() {
+        let err = ClientError::from(Status::deadline_exceeded("deadline"));
+        assert!(matches!(err, ClientError::Timeout(_)));
+        // Requests cancelled by the channel timeout
+        let err = ClientError::from(Status::cancelled("Timeout expired"));
+        assert!(matches!(err, ClientError::Timeout(_)));
+        let err = ClientError::from(Status::internal("error"));
+        assert!(matches!(err, ClientError::Inference(_)));
+    }
+}
@@ -1,7 +1,8 @@
 mod logging;
 mod management;
 
-use backend_grpc_client::Client;
+use backend_grpc_client::{Client, ClientError};
+use std::time::Duration;
 use text_embeddings_backend_core::{Backend, BackendError, Batch, Embedding, ModelType, Pool};
 use tokio::runtime::Runtime;
 
@@ -18,6 +19,8 @@ impl PythonBackend {
         model_type: ModelType,
         uds_path: String,
         otlp_endpoint: Option<String>,
+        connect_timeout: Option<Duration>,
+        read_timeout: Option<Duration>,
     ) -> Result<Self, BackendError> {
         let pool = match model_type {
             ModelType::Classifier => {
@@ -40,7 +43,7 @@ impl PythonBackend {
             .map_err(|err| BackendError::Start(format!("Could not start Tokio runtime: {err}")))?;
 
         let backend_client = tokio_runtime
-            .block_on(Client::connect_uds(uds_path))
+            .block_on(Client::connect_uds(uds_path, connect_timeout, read_timeout))
             .map_err(|err| {
                 BackendError::Start(format!("Could not connect to backend process: {err}"))
             })?;
@@ -79,7 +82,10 @@ impl Backend for PythonBackend {
                 batch.cumulative_seq_lengths,
                 batch.max_length,
             ))
-            .map_err(|err| BackendError::Inference(err.to_string()))?;
+            .map_err(|err| match err {
+                ClientError::Timeout(_) => BackendError::Timeout(err.to_string()),
+                _ => BackendError::Inference(err.to_string()),
+            })?;
         Ok(results.into_iter().map(|r| r.values).collect())
     }
 
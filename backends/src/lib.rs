@@ -9,7 +9,9 @@ use tokio::sync::{mpsc, oneshot, watch};
 use tracing::{instrument, Span};
 
 pub use crate::dtype::DType;
-pub use text_embeddings_backend_core::{BackendError, Batch, Embedding, ModelType, Pool};
+pub use text_embeddings_backend_core::{
+    BackendError, Batch, DeviceStats, Embedding, ModelType, Pool,
+};
 
 #[cfg(feature = "candle")]
 use text_embeddings_backend_candle::CandleBackend;
@@ -26,6 +28,10 @@ pub struct Backend {
     _backend_thread: Arc<BackendThread>,
     pub padded_model: bool,
     pub max_batch_size: Option<usize>,
+    /// Device the model runs on, if the backend reports it
+    pub device: Option<String>,
+    /// Data type the model runs in, e.g. `float16`
+    pub dtype: String,
     pub model_type: ModelType,
 }
 
@@ -36,8 +42,11 @@ impl Backend {
         model_type: ModelType,
         uds_path: String,
         otlp_endpoint: Option<String>,
+        connect_timeout: Option<Duration>,
+        read_timeout: Option<Duration>,
     ) -> Result<Self, BackendError> {
         let (backend_sender, backend_receiver) = mpsc::unbounded_channel();
+        let dtype_name = dtype.to_string();
 
         let backend = init_backend(
             model_path,
@@ -45,9 +54,12 @@ impl Backend {
             model_type.clone(),
             uds_path,
             otlp_endpoint,
+            connect_timeout,
+            read_timeout,
         )?;
         let padded_model = backend.is_padded();
         let max_batch_size = backend.max_batch_size();
+        let device = backend.device();
 
         let (health_sender, health_receiver) = watch::channel(false);
         let _backend_thread =
@@ -59,6 +71,8 @@ impl Backend {
             _backend_thread,
             padded_model,
             max_batch_size,
+            device,
+            dtype: dtype_name,
             model_type,
         })
     }
@@ -99,6 +113,19 @@ impl Backend {
         self.health_receiver.clone()
     }
 
+    /// Sample the resource usage of the device, between two batches
+    #[instrument(skip(self))]
+    pub async fn device_stats(&self) -> Option<DeviceStats> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.backend_sender
+            .send(BackendCommand::DeviceStats(Span::current(), sender))
+            .expect("No backend receiver. This is a bug.");
+        receiver.await.expect(
+            "Backend blocking task dropped the sender without send a response. This is a bug.",
+        )
+    }
+
     #[instrument(skip_all)]
     pub async fn embed(&self, batch: Batch) -> Result<(Vec<Embedding>, Duration), BackendError> {
         let (sender, receiver) = oneshot::channel();
@@ -131,6 +158,8 @@ fn init_backend(
     model_type: ModelType,
     uds_path: String,
     otlp_endpoint: Option<String>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
 ) -> Result<Box<dyn CoreBackend + Send>, BackendError> {
     if cfg!(feature = "candle") {
         #[cfg(feature = "candle")]
@@ -150,6 +179,8 @@ fn init_backend(
                         model_type,
                         uds_path,
                         otlp_endpoint,
+                        connect_timeout,
+                        read_timeout,
                     )
                 })
                 .join()
@@ -178,6 +209,12 @@ impl BackendThread {
                         let _span = span.entered();
                         let _ = sender.send(backend.health().map(|_| healthy = true));
                     }
+                    BackendCommand::DeviceStats(span, sender) => {
+                        let _span = span.entered();
+                        // Sampling stats does not tell if the backend is healthy
+                        healthy = *health_sender.borrow();
+                        let _ = sender.send(backend.device_stats());
+                    }
                     BackendCommand::Embed(batch, span, sender) => {
                         let _span = span.entered();
                         let _ = sender.send(backend.embed(batch).map(|e| {
@@ -208,6 +245,7 @@ impl Drop for BackendThread {
 
 enum BackendCommand {
     Health(Span, oneshot::Sender<Result<(), BackendError>>),
+    DeviceStats(Span, oneshot::Sender<Option<DeviceStats>>),
     Embed(
         Batch,
         Span,
@@ -0,0 +1,111 @@
+/// Dense layer of sentence-transformers models applied to pooled embeddings:
+/// `activation(W · v + b)`
+#[derive(Debug, Clone)]
+pub struct Dense {
+    /// Row-major `output_dim x input_dim` matrix
+    weight: Vec<f32>,
+    bias: Option<Vec<f32>>,
+    input_dim: usize,
+    activation: DenseActivation,
+}
+
+/// Activation applied to the output of a [`Dense`] layer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DenseActivation {
+    Identity,
+    Tanh,
+}
+
+impl Dense {
+    /// Create a layer from its row-major `output_dim x input_dim` weight and its bias
+    pub fn new(
+        weight: Vec<f32>,
+        output_dim: usize,
+        input_dim: usize,
+        bias: Option<Vec<f32>>,
+        activation: DenseActivation,
+    ) -> Result<Self, String> {
+        if output_dim == 0 || input_dim == 0 {
+            return Err("the weight is empty".to_string());
+        }
+        if weight.len() != output_dim * input_dim {
+            return Err(format!(
+                "the weight has {} values, expected {output_dim}x{input_dim}",
+                weight.len()
+            ));
+        }
+        if let Some(bias) = &bias {
+            if bias.len() != output_dim {
+                return Err(format!(
+                    "the bias has {} values, expected {output_dim}",
+                    bias.len()
+                ));
+            }
+        }
+
+        Ok(Self {
+            weight,
+            bias,
+            input_dim,
+            activation,
+        })
+    }
+
+    /// Dimension of the embeddings the layer applies to
+    pub fn input_dim(&self) -> usize {
+        self.input_dim
+    }
+
+    /// Dimension of the projected embeddings
+    pub fn output_dim(&self) -> usize {
+        self.weight.len() / self.input_dim
+    }
+
+    /// Project an embedding of `input_dim` values
+    pub fn apply(&self, embedding: &[f32]) -> Vec<f32> {
+        self.weight
+            .chunks_exact(self.input_dim)
+            .enumerate()
+            .map(|(i, row)| {
+                let v: f32 = row.iter().zip(embedding).map(|(w, v)| w * v).sum();
+                let v = v + self.bias.as_ref().map_or(0.0, |bias| bias[i]);
+                match self.activation {
+                    DenseActivation::Identity => v,
+                    DenseActivation::Tanh => v.tanh(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_projects_and_activates() {
+        let dense = Dense::new(
+            vec![1.0, 0.0, 0.0, 0.0, 2.0, -1.0],
+            2,
+            3,
+            Some(vec![0.5, 0.0]),
+            DenseActivation::Identity,
+        )
+        .unwrap();
+        assert_eq!(dense.input_dim(), 3);
+        assert_eq!(dense.output_dim(), 2);
+        assert_eq!(dense.apply(&[2.0, 3.0, 4.0]), vec![2.5, 2.0]);
+
+        let dense = Dense::new(vec![1.0, -1.0], 1, 2, None, DenseActivation::Tanh).unwrap();
+        assert_eq!(dense.apply(&[1.0, 1.0]), vec![0.0]);
+        assert_eq!(dense.apply(&[3.0, 1.0]), vec![2.0f32.tanh()]);
+    }
+
+    #[test]
+    fn new_rejects_mismatched_shapes() {
+        let identity = DenseActivation::Identity;
+        assert!(Dense::new(vec![1.0, 0.0, 1.0], 2, 2, None, identity).is_err());
+        assert!(Dense::new(vec![1.0, 0.0], 1, 2, Some(vec![0.0, 0.0]), identity).is_err());
+        assert!(Dense::new(Vec::new(), 0, 2, None, identity).is_err());
+    }
+}
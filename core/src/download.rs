@@ -27,6 +27,13 @@ pub async fn download_artifacts(api: &ApiRepo) -> Result<PathBuf, ApiError> {
     Ok(model_root)
 }
 
+#[instrument(skip_all)]
+pub async fn download_dense(api: &ApiRepo) -> Result<PathBuf, ApiError> {
+    api.get("2_Dense/config.json").await?;
+    let dense_path = api.get("2_Dense/model.safetensors").await?;
+    Ok(dense_path)
+}
+
 #[instrument(skip_all)]
 pub async fn download_pool_config(api: &ApiRepo) -> Result<PathBuf, ApiError> {
     let pool_config_path = api.get("1_Pooling/config.json").await?;
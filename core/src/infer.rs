@@ -1,11 +1,19 @@
+use crate::dense::Dense;
 use crate::queue::{Entry, Metadata, NextBatch, Queue};
 use crate::tokenization::{EncodingInput, Tokenization};
+use crate::whitening::Whitening;
 use crate::TextEmbeddingsError;
-use std::sync::Arc;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
-use text_embeddings_backend::{Backend, BackendError, ModelType};
-use tokio::sync::{mpsc, oneshot, watch, Notify, OwnedSemaphorePermit, Semaphore};
-use tracing::{instrument, Span};
+use text_embeddings_backend::{Backend, BackendError, DeviceStats, ModelType};
+use tokio::sync::{
+    mpsc, oneshot, watch, Notify, OnceCell, OwnedSemaphorePermit, Semaphore, TryAcquireError,
+};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{field, instrument, Instrument, Span};
 
 /// Inference struct
 #[derive(Debug, Clone)]
@@ -16,7 +24,22 @@ pub struct Infer {
     notify_batching_task: Arc<Notify>,
     /// Inference limit
     limit_concurrent_requests: Arc<Semaphore>,
+    max_concurrent_requests: usize,
     backend: Backend,
+    /// Lower bound of the norm used for normalization
+    normalize_epsilon: f32,
+    /// Embeddings of identical inputs shared between requests
+    coalescer: Arc<Coalescer>,
+    /// Dense projection head of the model, applied to pooled embeddings before whitening
+    dense: Option<Arc<Dense>>,
+    /// Transform applied to embeddings before normalization
+    whitening: Option<Arc<Whitening>>,
+    /// Flip the sign of embeddings so that their largest-magnitude component is positive
+    canonicalize_sign: bool,
+    /// Number of mantissa bits embeddings are rounded to
+    round_bits: Option<u32>,
+    /// Batching and backend tasks, aborted by [`Infer::stop`]
+    tasks: Arc<Vec<JoinHandle<()>>>,
 }
 
 impl Infer {
@@ -26,25 +49,32 @@ impl Infer {
         queue: Queue,
         max_concurrent_requests: usize,
         backend: Backend,
+        normalize_epsilon: f32,
+        coalesce_ttl: Duration,
+        dense: Option<Dense>,
+        whitening: Option<Whitening>,
+        canonicalize_sign: bool,
+        round_bits: Option<u32>,
     ) -> Self {
         let notify_batching_task = Arc::new(Notify::new());
 
         let (embed_sender, embed_receiver) = mpsc::unbounded_channel();
 
         // Create two batching tasks to prefetch batches
-        tokio::spawn(batching_task(
-            queue.clone(),
-            notify_batching_task.clone(),
-            embed_sender.clone(),
-        ));
-        tokio::spawn(batching_task(
-            queue.clone(),
-            notify_batching_task.clone(),
-            embed_sender,
-        ));
-
-        // Create embed task to communicate with backend
-        tokio::spawn(backend_task(backend.clone(), embed_receiver));
+        let tasks = vec![
+            tokio::spawn(batching_task(
+                queue.clone(),
+                notify_batching_task.clone(),
+                embed_sender.clone(),
+            )),
+            tokio::spawn(batching_task(
+                queue.clone(),
+                notify_batching_task.clone(),
+                embed_sender,
+            )),
+            // Create embed task to communicate with backend
+            tokio::spawn(backend_task(backend.clone(), embed_receiver)),
+        ];
 
         // Inference limit with a semaphore
         let semaphore = Arc::new(Semaphore::new(max_concurrent_requests));
@@ -54,7 +84,15 @@ impl Infer {
             queue,
             notify_batching_task,
             limit_concurrent_requests: semaphore,
+            max_concurrent_requests,
             backend,
+            normalize_epsilon,
+            coalescer: Arc::new(Coalescer::new(coalesce_ttl)),
+            dense: dense.map(Arc::new),
+            whitening: whitening.map(Arc::new),
+            canonicalize_sign,
+            round_bits,
+            tasks: Arc::new(tasks),
         }
     }
 
@@ -80,13 +118,126 @@ impl Infer {
             .expect("Semaphore has been closed. This is a bug.")
     }
 
-    #[instrument(skip(self, _permit))]
+    /// Wait at most `timeout` for a permit, or indefinitely if `timeout` is `None`
+    ///
+    /// Returns an overloaded error if no permit is released in time.
+    #[instrument(skip(self))]
+    pub async fn acquire_permit_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<OwnedSemaphorePermit, TextEmbeddingsError> {
+        self.acquire_permits_timeout(1, timeout).await
+    }
+
+    /// Wait at most `timeout` for one permit per input of a batch, or indefinitely if `timeout`
+    /// is `None`
+    ///
+    /// At most `max_concurrent_requests` permits are taken so that any batch can run.
+    #[instrument(skip(self))]
+    pub async fn acquire_permits_timeout(
+        &self,
+        count: usize,
+        timeout: Option<Duration>,
+    ) -> Result<OwnedSemaphorePermit, TextEmbeddingsError> {
+        let count = count.clamp(1, self.max_concurrent_requests) as u32;
+        let permits = self
+            .limit_concurrent_requests
+            .clone()
+            .acquire_many_owned(count);
+        let permits = match timeout {
+            None => permits.await,
+            Some(timeout) => tokio::time::timeout(timeout, permits).await.map_err(|_| {
+                metrics::increment_counter!("te_request_failure", "err" => "overloaded");
+                tracing::error!("No permit was released within {timeout:?}");
+                TextEmbeddingsError::from(TryAcquireError::NoPermits)
+            })?,
+        };
+        // The semaphore is closed once the model is stopped
+        permits.map_err(|_| TextEmbeddingsError::from(TryAcquireError::Closed))
+    }
+
+    /// Wait until all the requests holding a permit are done
+    #[instrument(skip(self))]
+    pub async fn drain(&self) {
+        let _permits = self
+            .limit_concurrent_requests
+            .acquire_many(self.max_concurrent_requests as u32)
+            .await
+            .expect("Semaphore has been closed. This is a bug.");
+    }
+
+    /// Wait until all the requests holding a permit are done, then stop the batching tasks
+    ///
+    /// The backend is stopped once the last clone of the model is dropped. Requests waiting for
+    /// a permit get an overloaded error.
+    #[instrument(skip(self))]
+    pub async fn stop(&self) {
+        self.drain().await;
+        self.limit_concurrent_requests.close();
+        for task in self.tasks.iter() {
+            task.abort();
+        }
+    }
+
+    /// L2 normalize an embedding in place, as `embed` does when `normalize` is set
+    pub fn normalize(&self, embedding: &mut [f32]) {
+        normalize_embedding(embedding, self.normalize_epsilon);
+    }
+
+    #[instrument(skip(self, inputs, _permit), fields(inputs = field::Empty))]
     pub async fn embed<I: Into<EncodingInput> + std::fmt::Debug>(
         &self,
         inputs: I,
         truncate: bool,
+        add_special_tokens: bool,
         normalize: bool,
         _permit: OwnedSemaphorePermit,
+    ) -> Result<InferResponse, TextEmbeddingsError> {
+        record_inputs(&inputs);
+        let inputs = inputs.into();
+
+        // Requests embedding the same text at the same time share a single backend call
+        let key = match &inputs {
+            EncodingInput::Single(text) => (
+                String::new(),
+                text.clone(),
+                truncate,
+                add_special_tokens,
+                normalize,
+            ),
+            EncodingInput::Prefixed(prefix, text) => (
+                prefix.clone(),
+                text.clone(),
+                truncate,
+                add_special_tokens,
+                normalize,
+            ),
+            EncodingInput::Dual(..) => {
+                return self
+                    .embed_once(inputs, truncate, add_special_tokens, normalize)
+                    .await
+            }
+        };
+        let response = self
+            .coalescer
+            .run(key, || {
+                self.embed_once(inputs, truncate, add_special_tokens, normalize)
+            })
+            .await;
+        if let Ok(response) = &response {
+            record_batch_size(response.batch_size);
+            record_truncated_tokens(response.truncated_tokens);
+        }
+        response
+    }
+
+    /// Embed an input without sharing the backend call with other requests
+    async fn embed_once(
+        &self,
+        inputs: EncodingInput,
+        truncate: bool,
+        add_special_tokens: bool,
+        normalize: bool,
     ) -> Result<InferResponse, TextEmbeddingsError> {
         if self.is_classifier() {
             metrics::increment_counter!("te_request_failure", "err" => "model_type");
@@ -103,7 +254,7 @@ impl Infer {
         // Tokenization
         let encoding = self
             .tokenization
-            .encode(inputs.into(), truncate)
+            .encode(inputs, truncate, add_special_tokens)
             .await
             .map_err(|err| {
                 metrics::increment_counter!("te_request_failure", "err" => "tokenization");
@@ -122,6 +273,7 @@ impl Infer {
                 tokenization: start_time.elapsed(),
                 queue_time: Instant::now(),
                 prompt_tokens: encoding.input_ids.len(),
+                truncated_tokens: encoding.truncated_tokens,
             },
             encoding,
         });
@@ -139,21 +291,20 @@ impl Infer {
                 err
             })?;
 
+        if let Some(dense) = &self.dense {
+            response.results = dense.apply(&response.results);
+        }
+        if let Some(whitening) = &self.whitening {
+            response.results = whitening.apply(&response.results);
+        }
+        if self.canonicalize_sign {
+            canonicalize_sign(&mut response.results);
+        }
         if normalize {
-            // Normalize embedding
-            let scale = (1.0
-                / response
-                    .results
-                    .iter()
-                    .map(|v| {
-                        let v = *v as f64;
-                        v * v
-                    })
-                    .sum::<f64>()
-                    .sqrt()) as f32;
-            for v in response.results.iter_mut() {
-                *v *= scale;
-            }
+            normalize_embedding(&mut response.results, self.normalize_epsilon);
+        }
+        if let Some(bits) = self.round_bits {
+            round_mantissa(&mut response.results, bits);
         }
 
         // Timings
@@ -175,14 +326,16 @@ impl Infer {
         Ok(response)
     }
 
-    #[instrument(skip(self, _permit))]
+    #[instrument(skip(self, inputs, _permit), fields(inputs = field::Empty))]
     pub async fn predict<I: Into<EncodingInput> + std::fmt::Debug>(
         &self,
         inputs: I,
         truncate: bool,
+        add_special_tokens: bool,
         raw_scores: bool,
         _permit: OwnedSemaphorePermit,
     ) -> Result<InferResponse, TextEmbeddingsError> {
+        record_inputs(&inputs);
         if !self.is_classifier() {
             metrics::increment_counter!("te_request_failure", "err" => "model_type");
             let message = "Model is not a classifier model".to_string();
@@ -197,7 +350,7 @@ impl Infer {
         // Tokenization
         let encoding = self
             .tokenization
-            .encode(inputs.into(), truncate)
+            .encode(inputs.into(), truncate, add_special_tokens)
             .await
             .map_err(|err| {
                 metrics::increment_counter!("te_request_failure", "err" => "tokenization");
@@ -216,6 +369,7 @@ impl Infer {
                 tokenization: start_time.elapsed(),
                 queue_time: Instant::now(),
                 prompt_tokens: encoding.input_ids.len(),
+                truncated_tokens: encoding.truncated_tokens,
             },
             encoding,
         });
@@ -233,28 +387,12 @@ impl Infer {
                 err
             })?;
 
+        response.logits = Some(response.results.clone());
+        record_batch_size(response.batch_size);
+        record_truncated_tokens(response.truncated_tokens);
+
         if !raw_scores {
-            // Softmax
-            if response.results.len() > 1 {
-                let max = *response
-                    .results
-                    .iter()
-                    .max_by(|x, y| x.abs().partial_cmp(&y.abs()).unwrap())
-                    .unwrap();
-
-                let mut den = 0.0;
-                for v in response.results.iter_mut() {
-                    *v = (*v - max).exp();
-                    den += *v;
-                }
-                for v in response.results.iter_mut() {
-                    *v /= den;
-                }
-            }
-            // Sigmoid
-            else {
-                response.results[0] = 1.0 / (1.0 + (-response.results[0]).exp());
-            }
+            activate(&mut response.results);
         }
 
         // Timings
@@ -276,6 +414,219 @@ impl Infer {
         Ok(response)
     }
 
+    /// Predict a batch of inputs with the permits of [`Infer::acquire_permits_timeout`]
+    ///
+    /// The inputs are queued together so that they fill backend batches instead of being
+    /// interleaved with other requests. Responses are returned in input order.
+    #[instrument(skip(self, inputs, _permits))]
+    pub async fn predict_batch<I: Into<EncodingInput>>(
+        &self,
+        inputs: Vec<I>,
+        truncate: bool,
+        add_special_tokens: bool,
+        raw_scores: bool,
+        _permits: OwnedSemaphorePermit,
+    ) -> Result<Vec<InferResponse>, TextEmbeddingsError> {
+        if !self.is_classifier() {
+            metrics::increment_counter!("te_request_failure", "err" => "model_type");
+            let message = "Model is not a classifier model".to_string();
+            return Err(TextEmbeddingsError::Backend(BackendError::Inference(
+                message,
+            )));
+        }
+
+        let start_time = Instant::now();
+        metrics::counter!("te_predict_count", inputs.len() as u64);
+
+        // Tokenization
+        let encodings = self
+            .tokenization
+            .encode_batch(
+                inputs.into_iter().map(Into::into).collect(),
+                truncate,
+                add_special_tokens,
+            )
+            .await
+            .map_err(|err| {
+                metrics::increment_counter!("te_request_failure", "err" => "tokenization");
+                tracing::error!("{err}");
+                err
+            })?;
+        let tokenization = start_time.elapsed();
+
+        // MPSC channels to communicate with the background batching task
+        let mut response_rxs = Vec::with_capacity(encodings.len());
+        let entries = encodings
+            .into_iter()
+            .map(|encoding| {
+                let (response_tx, response_rx) = oneshot::channel();
+                response_rxs.push(response_rx);
+                Entry {
+                    metadata: Metadata {
+                        response_tx,
+                        span: Span::current(),
+                        tokenization,
+                        queue_time: Instant::now(),
+                        prompt_tokens: encoding.input_ids.len(),
+                        truncated_tokens: encoding.truncated_tokens,
+                    },
+                    encoding,
+                }
+            })
+            .collect();
+
+        // Append the requests to the queue
+        self.queue.append_all(entries);
+
+        self.notify_batching_task.notify_one();
+
+        let mut responses = Vec::with_capacity(response_rxs.len());
+        for response_rx in response_rxs {
+            let mut response = response_rx
+                .await
+                .expect(
+                    "Infer batching task dropped the sender without sending a response. This is a bug.",
+                )
+                .map_err(|err| {
+                    metrics::increment_counter!("te_request_failure", "err" => "inference");
+                    tracing::error!("{err}");
+                    err
+                })?;
+
+            response.logits = Some(response.results.clone());
+            record_batch_size(response.batch_size);
+            record_truncated_tokens(response.truncated_tokens);
+            if !raw_scores {
+                activate(&mut response.results);
+            }
+            responses.push(response);
+        }
+
+        // Timings
+        let total_time = start_time.elapsed();
+
+        // Metrics
+        metrics::counter!("te_predict_success", responses.len() as u64);
+        metrics::histogram!("te_predict_duration", total_time.as_secs_f64());
+        metrics::histogram!(
+            "te_predict_tokenization_duration",
+            tokenization.as_secs_f64()
+        );
+
+        Ok(responses)
+    }
+
+    /// Count the tokens of an input without running the model
+    #[instrument(skip(self, inputs), fields(inputs = field::Empty))]
+    pub async fn count_tokens<I: Into<EncodingInput> + std::fmt::Debug>(
+        &self,
+        inputs: I,
+        add_special_tokens: bool,
+    ) -> Result<usize, TextEmbeddingsError> {
+        record_inputs(&inputs);
+        self.tokenization
+            .count(inputs.into(), add_special_tokens)
+            .await
+            .map_err(|err| {
+                metrics::increment_counter!("te_request_failure", "err" => "tokenization");
+                tracing::error!("{err}");
+                err
+            })
+    }
+
+    /// Get the byte offsets of the tokens of an input, without special tokens
+    #[instrument(skip_all)]
+    pub async fn token_offsets(
+        &self,
+        input: String,
+    ) -> Result<Vec<(usize, usize)>, TextEmbeddingsError> {
+        self.tokenization.offsets(input).await.map_err(|err| {
+            metrics::increment_counter!("te_request_failure", "err" => "tokenization");
+            tracing::error!("{err}");
+            err
+        })
+    }
+
+    /// Find the token whose removal changes the embedding of an input the most
+    ///
+    /// Each token is dropped from the text in turn and the input is embedded again, so this
+    /// runs one forward pass per token: inputs are limited to [`MAX_EXPLAIN_TOKENS`] tokens.
+    /// Once the tokens are known, one permit per forward pass is taken with
+    /// [`Infer::acquire_permits_timeout`].
+    #[instrument(skip(self, input), fields(inputs = field::Empty))]
+    pub async fn explain(
+        &self,
+        input: String,
+        truncate: bool,
+        add_special_tokens: bool,
+        permit_wait_timeout: Option<Duration>,
+    ) -> Result<TokenAttribution, TextEmbeddingsError> {
+        record_inputs(&input);
+        let offsets = self.token_offsets(input.clone()).await?;
+        if offsets.len() > MAX_EXPLAIN_TOKENS {
+            let message = format!(
+                "`explain` supports inputs of at most {MAX_EXPLAIN_TOKENS} tokens. Given: {}",
+                offsets.len()
+            );
+            tracing::error!("{message}");
+            return Err(TextEmbeddingsError::Validation(message));
+        }
+        let _permits = self
+            .acquire_permits_timeout(offsets.len() + 1, permit_wait_timeout)
+            .await?;
+
+        let full = self
+            .embed_once(input.clone().into(), truncate, add_special_tokens, true)
+            .await?
+            .results;
+
+        // Forward passes run concurrently so that they can be batched together. They are aborted
+        // if the request is dropped
+        let mut dropped = JoinSet::new();
+        for (index, &(start, end)) in offsets.iter().enumerate() {
+            let text = match (input.get(..start), input.get(end..)) {
+                (Some(before), Some(after)) => format!("{before}{after}"),
+                // Tokens splitting a character cannot be dropped on their own
+                _ => continue,
+            };
+            let infer = self.clone();
+            let task = async move {
+                if text.trim().is_empty() {
+                    // Dropping the only token leaves nothing to embed
+                    return Ok((index, start, end, None));
+                }
+                let response = infer
+                    .embed_once(text.into(), truncate, add_special_tokens, true)
+                    .await?;
+                Ok::<_, TextEmbeddingsError>((index, start, end, Some(response.results)))
+            };
+            dropped.spawn(task.in_current_span());
+        }
+
+        let mut attribution: Option<TokenAttribution> = None;
+        while let Some(task) = dropped.join_next().await {
+            let (index, start, end, embedding) =
+                task.expect("explain task panicked. This is a bug.")?;
+            let saliency = match embedding {
+                Some(embedding) => {
+                    1.0 - full.iter().zip(&embedding).map(|(a, b)| a * b).sum::<f32>()
+                }
+                None => 1.0,
+            };
+            // Passes complete in any order: ties keep the first token
+            if attribution.as_ref().map_or(true, |best| {
+                saliency > best.saliency || (saliency == best.saliency && index < best.index)
+            }) {
+                attribution = Some(TokenAttribution {
+                    index,
+                    token: input[start..end].to_string(),
+                    saliency,
+                });
+            }
+        }
+        attribution.ok_or(TextEmbeddingsError::EmptyInput)
+    }
+
     #[instrument(skip(self))]
     pub fn is_classifier(&self) -> bool {
         matches!(self.backend.model_type, ModelType::Classifier)
@@ -290,6 +641,207 @@ impl Infer {
     pub fn health_watcher(&self) -> watch::Receiver<bool> {
         self.backend.health_watcher()
     }
+
+    #[instrument(skip(self))]
+    pub async fn device_stats(&self) -> Option<DeviceStats> {
+        self.backend.device_stats().await
+    }
+
+    /// Device the model runs on, if the backend reports it
+    pub fn device(&self) -> Option<&str> {
+        self.backend.device.as_deref()
+    }
+
+    /// Data type the model runs in, e.g. `float16`
+    pub fn dtype(&self) -> &str {
+        &self.backend.dtype
+    }
+}
+
+tokio::task_local! {
+    /// Set for requests whose inputs must not be recorded in logs and traces
+    static REDACT_INPUTS: bool;
+}
+
+/// Run `future` without recording the inputs it sends to `Infer` in logs and traces if `redact`
+pub async fn redact_inputs<F: Future>(redact: bool, future: F) -> F::Output {
+    REDACT_INPUTS.scope(redact, future).await
+}
+
+/// Check if the inputs of the current request must not be recorded
+pub fn inputs_redacted() -> bool {
+    REDACT_INPUTS.try_with(|redact| *redact).unwrap_or(false)
+}
+
+tokio::task_local! {
+    /// Size of the backend batches that computed the responses of the current request
+    static BATCH_SIZES: RefCell<Vec<usize>>;
+}
+
+/// Run `future` and collect the size of the backend batches that computed its responses, in
+/// response order
+///
+/// Only the calls to `Infer` made from the task running `future` are collected.
+pub async fn collect_batch_sizes<F: Future>(future: F) -> (F::Output, Vec<usize>) {
+    BATCH_SIZES
+        .scope(RefCell::new(Vec::new()), async {
+            let output = future.await;
+            (output, BATCH_SIZES.with(RefCell::take))
+        })
+        .await
+}
+
+fn record_batch_size(batch_size: usize) {
+    let _ = BATCH_SIZES.try_with(|sizes| sizes.borrow_mut().push(batch_size));
+}
+
+tokio::task_local! {
+    /// Number of input tokens of the current request dropped by truncation
+    static TRUNCATED_TOKENS: Cell<usize>;
+}
+
+/// Run `future` and count the input tokens dropped by truncation in the calls to `Infer` it makes
+/// from its own task, read with [`truncated_tokens`]
+pub async fn count_truncated_tokens<F: Future>(future: F) -> F::Output {
+    TRUNCATED_TOKENS.scope(Cell::new(0), future).await
+}
+
+/// Number of input tokens dropped by truncation so far in the current request
+pub fn truncated_tokens() -> usize {
+    TRUNCATED_TOKENS.try_with(Cell::get).unwrap_or(0)
+}
+
+fn record_truncated_tokens(truncated_tokens: usize) {
+    let _ = TRUNCATED_TOKENS.try_with(|count| count.set(count.get() + truncated_tokens));
+}
+
+/// Record the inputs in the current span, unless they are redacted
+fn record_inputs(inputs: &impl std::fmt::Debug) {
+    if !inputs_redacted() {
+        Span::current().record("inputs", field::debug(inputs));
+    }
+}
+
+/// Instruction prefix, input text, `truncate`, `add_special_tokens` and `normalize` of an
+/// embedding request
+type FlightKey = (String, String, bool, bool, bool);
+
+/// Maximum number of flights shared between requests. Inputs above it are computed alone
+const MAX_FLIGHTS: usize = 10_000;
+
+/// Interval between two removals of the stale flights
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Single-flight map of the embeddings being computed
+///
+/// The first request for an input computes it and the identical requests arriving meanwhile wait
+/// for its response. Computed responses are kept for `ttl` to also serve requests arriving
+/// shortly after.
+#[derive(Debug)]
+struct Coalescer {
+    ttl: Duration,
+    flights: Mutex<Flights>,
+}
+
+#[derive(Debug)]
+struct Flights {
+    map: HashMap<FlightKey, Arc<Flight>>,
+    /// When the stale flights were last removed
+    swept: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Flight {
+    response: OnceCell<InferResponse>,
+    /// When the response was computed
+    done: OnceLock<Instant>,
+}
+
+impl Coalescer {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            flights: Mutex::new(Flights {
+                map: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    /// Run `compute` for `key`, or wait for the response of the identical request running it
+    async fn run<F, Fut>(
+        &self,
+        key: FlightKey,
+        compute: F,
+    ) -> Result<InferResponse, TextEmbeddingsError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<InferResponse, TextEmbeddingsError>>,
+    {
+        let flight = self.flight(key.clone());
+        let mut computed = false;
+        let response = flight
+            .response
+            .get_or_try_init(|| {
+                computed = true;
+                compute()
+            })
+            .await;
+        if computed {
+            self.finish(&key, &flight);
+        } else {
+            metrics::increment_counter!("te_request_coalesced");
+        }
+        response.cloned()
+    }
+
+    /// Get the flight of `key`, starting a new one if there is none
+    ///
+    /// Stale flights are removed every [`SWEEP_INTERVAL`], and new flights are not shared once
+    /// there are [`MAX_FLIGHTS`], so that the map stays bounded.
+    fn flight(&self, key: FlightKey) -> Arc<Flight> {
+        let mut flights = self.flights.lock().unwrap();
+        if let Some(flight) = flights.map.get(&key) {
+            if !flight.stale(self.ttl) {
+                return flight.clone();
+            }
+        }
+        if flights.swept.elapsed() >= SWEEP_INTERVAL {
+            flights.map.retain(|_, flight| !flight.stale(self.ttl));
+            flights.swept = Instant::now();
+        }
+
+        let flight = Arc::new(Flight::default());
+        if flights.map.len() < MAX_FLIGHTS || flights.map.contains_key(&key) {
+            flights.map.insert(key, flight.clone());
+        }
+        flight
+    }
+
+    /// Remove the flight of `key` unless its response is kept for the TTL
+    ///
+    /// Failed flights are always removed so that the next request retries.
+    fn finish(&self, key: &FlightKey, flight: &Arc<Flight>) {
+        if flight.response.initialized() && !self.ttl.is_zero() {
+            let _ = flight.done.set(Instant::now());
+            return;
+        }
+        let flights = &mut self.flights.lock().unwrap().map;
+        // A new flight may have replaced this one
+        if flights.get(key).is_some_and(|f| Arc::ptr_eq(f, flight)) {
+            flights.remove(key);
+        }
+    }
+}
+
+impl Flight {
+    /// Expired, or abandoned by all its requests before completing
+    fn stale(self: &Arc<Self>, ttl: Duration) -> bool {
+        match self.done.get() {
+            Some(done) => done.elapsed() > ttl,
+            None => !self.response.initialized() && Arc::strong_count(self) == 1,
+        }
+    }
 }
 
 #[instrument(skip_all)]
@@ -325,13 +877,19 @@ async fn backend_task(
         // Handle sending responses in another thread to avoid starving the backend
         std::thread::spawn(move || match results {
             Ok((embeddings, inference_duration)) => {
+                let batch_size = batch.0.len();
                 batch.0.into_iter().zip(embeddings).for_each(|(m, e)| {
                     let _ = m.response_tx.send(Ok(InferResponse {
-                        results: e,
+                        logits: None,
                         prompt_tokens: m.prompt_tokens,
+                        truncated_tokens: m.truncated_tokens,
                         tokenization: m.tokenization,
                         queue: m.queue_time.elapsed() - inference_duration,
                         inference: inference_duration,
+                        batch_size,
+                        raw_shape: [batch_size, e.len()],
+                        norm: l2_norm(&e) as f32,
+                        results: e,
                     }));
                 });
             }
@@ -344,11 +902,270 @@ async fn backend_task(
     }
 }
 
-#[derive(Debug)]
+/// Turn classifier logits into scores: softmax over several classes, sigmoid for a single one
+fn activate(results: &mut [f32]) {
+    // Softmax
+    if results.len() > 1 {
+        let max = *results
+            .iter()
+            .max_by(|x, y| x.abs().partial_cmp(&y.abs()).unwrap())
+            .unwrap();
+
+        let mut den = 0.0;
+        for v in results.iter_mut() {
+            *v = (*v - max).exp();
+            den += *v;
+        }
+        for v in results.iter_mut() {
+            *v /= den;
+        }
+    }
+    // Sigmoid
+    else {
+        results[0] = 1.0 / (1.0 + (-results[0]).exp());
+    }
+}
+
+/// L2 normalize an embedding in place
+///
+/// The norm is clamped to `epsilon` so that near-zero vectors stay finite instead of exploding,
+/// and zero vectors stay zero.
+fn normalize_embedding(embedding: &mut [f32], epsilon: f32) {
+    let norm = l2_norm(embedding);
+    let scale = (1.0 / norm.max(epsilon as f64)) as f32;
+    for v in embedding.iter_mut() {
+        *v *= scale;
+    }
+}
+
+/// L2 norm of an embedding, accumulated in `f64`
+fn l2_norm(embedding: &[f32]) -> f64 {
+    embedding
+        .iter()
+        .map(|v| {
+            let v = *v as f64;
+            v * v
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Flip the sign of an embedding so that its largest-magnitude component is positive
+///
+/// Ties keep the first component, so the result only depends on the values.
+fn canonicalize_sign(embedding: &mut [f32]) {
+    let mut largest = 0.0_f32;
+    for &v in embedding.iter() {
+        if v.abs() > largest.abs() {
+            largest = v;
+        }
+    }
+    if largest < 0.0 {
+        for v in embedding.iter_mut() {
+            *v = -*v;
+        }
+    }
+}
+
+/// Round each value of an embedding to its `bits` most significant mantissa bits
+///
+/// Rounds half away from zero on the bit pattern, so values that differ only in their
+/// dropped bits are rounded to the same value. Non-finite values are left unchanged.
+fn round_mantissa(embedding: &mut [f32], bits: u32) {
+    let dropped = f32::MANTISSA_DIGITS - 1 - bits.min(f32::MANTISSA_DIGITS - 1);
+    if dropped == 0 {
+        return;
+    }
+    let half = 1_u32 << (dropped - 1);
+    let mask = !((1_u32 << dropped) - 1);
+    for v in embedding.iter_mut() {
+        if v.is_finite() {
+            *v = f32::from_bits((v.to_bits() + half) & mask);
+        }
+    }
+}
+
+/// Maximum number of tokens of the inputs of [`Infer::explain`]
+pub const MAX_EXPLAIN_TOKENS: usize = 256;
+
+/// Token of an input that contributes the most to its embedding
+#[derive(Debug, Clone)]
+pub struct TokenAttribution {
+    /// Position of the token among the tokens of the input, without special tokens
+    pub index: usize,
+    pub token: String,
+    /// Cosine distance between the embeddings of the input with and without the token
+    pub saliency: f32,
+}
+
+#[derive(Debug, Clone)]
 pub struct InferResponse {
     pub results: Vec<f32>,
+    /// Unmodified classifier output, in model label order
+    pub logits: Option<Vec<f32>>,
     pub prompt_tokens: usize,
+    /// Number of input tokens dropped by truncation, without special tokens
+    pub truncated_tokens: usize,
     pub tokenization: Duration,
     pub queue: Duration,
     pub inference: Duration,
+    /// Number of requests in the backend batch that computed the response
+    pub batch_size: usize,
+    /// L2 norm of the backend output, before whitening and normalization
+    pub norm: f32,
+    /// Shape of the backend output of the batch that computed the response, as
+    /// `[batch size, dimension]`, before whitening and normalization
+    pub raw_shape: [usize; 2],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_near_zero_embedding() {
+        let mut embedding = vec![1e-30, -1e-30, 0.0];
+        normalize_embedding(&mut embedding, 1e-12);
+        assert!(embedding.iter().all(|v| v.is_finite()));
+
+        let mut embedding = vec![0.0; 4];
+        normalize_embedding(&mut embedding, 1e-12);
+        assert_eq!(embedding, vec![0.0; 4]);
+
+        let mut embedding = vec![3.0, 4.0];
+        normalize_embedding(&mut embedding, 1e-12);
+        assert!((embedding[0] - 0.6).abs() < 1e-6);
+        assert!((embedding[1] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn canonicalize_sign_of_largest_component() {
+        let mut embedding = vec![0.5, -2.0, 1.0];
+        canonicalize_sign(&mut embedding);
+        assert_eq!(embedding, vec![-0.5, 2.0, -1.0]);
+
+        canonicalize_sign(&mut embedding);
+        assert_eq!(embedding, vec![-0.5, 2.0, -1.0]);
+
+        // Ties keep the first component
+        let mut embedding = vec![-1.0, 1.0];
+        canonicalize_sign(&mut embedding);
+        assert_eq!(embedding, vec![1.0, -1.0]);
+
+        let mut embedding: Vec<f32> = Vec::new();
+        canonicalize_sign(&mut embedding);
+        assert!(embedding.is_empty());
+    }
+
+    #[test]
+    fn round_mantissa_to_bits() {
+        let mut embedding = vec![1.0 + 1e-6, 1.0 - 1e-6, -0.3, 0.0, f32::NAN];
+        round_mantissa(&mut embedding, 10);
+        assert_eq!(embedding[0], 1.0);
+        assert_eq!(embedding[1], 1.0);
+        // Rounding only changes the magnitude
+        assert!((embedding[2] + 0.3).abs() < 0.3 / 1024.0);
+        assert_eq!(embedding[3], 0.0);
+        assert!(embedding[4].is_nan());
+
+        let mut rounded = embedding[..4].to_vec();
+        round_mantissa(&mut rounded, 10);
+        assert_eq!(rounded, embedding[..4]);
+
+        let mut embedding = vec![0.1_f32];
+        round_mantissa(&mut embedding, 23);
+        assert_eq!(embedding, vec![0.1]);
+    }
+
+    #[test]
+    fn coalescer_shares_flights_of_identical_inputs() {
+        let coalescer = Coalescer::new(Duration::ZERO);
+        let key = (String::new(), "test".to_string(), true, true, true);
+        let flight = coalescer.flight(key.clone());
+        assert!(Arc::ptr_eq(&flight, &coalescer.flight(key.clone())));
+        assert!(!Arc::ptr_eq(
+            &flight,
+            &coalescer.flight((String::new(), "test".to_string(), true, true, false))
+        ));
+
+        // Failed flights are removed so that the next request retries
+        coalescer.finish(&key, &flight);
+        assert!(!Arc::ptr_eq(&flight, &coalescer.flight(key)));
+    }
+
+    #[test]
+    fn coalescer_runs_concurrent_identical_inputs_once() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let coalescer = Arc::new(Coalescer::new(Duration::ZERO));
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let (coalescer, calls) = (coalescer.clone(), calls.clone());
+                runtime.spawn(async move {
+                    let key = (String::new(), "test".to_string(), true, true, true);
+                    coalescer
+                        .run(key, || async {
+                            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            Ok(InferResponse {
+                                results: vec![1.0],
+                                logits: None,
+                                prompt_tokens: 1,
+                                truncated_tokens: 0,
+                                tokenization: Duration::ZERO,
+                                queue: Duration::ZERO,
+                                inference: Duration::ZERO,
+                                batch_size: 1,
+                                norm: 1.0,
+                                raw_shape: [1, 1],
+                            })
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            let response = runtime.block_on(task).unwrap().unwrap();
+            assert_eq!(response.results, vec![1.0]);
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        // Without a TTL, the finished flight is removed
+        assert!(coalescer.flights.lock().unwrap().map.is_empty());
+    }
+
+    #[test]
+    fn coalescer_is_bounded() {
+        let coalescer = Coalescer::new(Duration::from_secs(60));
+        let key = |i: usize| (String::new(), i.to_string(), true, true, true);
+        // Keep the flights in progress
+        let _flights: Vec<_> = (0..MAX_FLIGHTS).map(|i| coalescer.flight(key(i))).collect();
+
+        let flight = coalescer.flight(key(MAX_FLIGHTS));
+        assert!(!Arc::ptr_eq(&flight, &coalescer.flight(key(MAX_FLIGHTS))));
+        assert_eq!(coalescer.flights.lock().unwrap().map.len(), MAX_FLIGHTS);
+    }
+
+    #[test]
+    fn redaction_is_scoped_to_the_request() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        assert!(!inputs_redacted());
+        runtime.block_on(redact_inputs(true, async {
+            assert!(inputs_redacted());
+            // Nested calls, e.g. a redacted request running a job, keep their own setting
+            redact_inputs(false, async { assert!(!inputs_redacted()) }).await;
+            assert!(inputs_redacted());
+            // Spawned tasks do not inherit it
+            let spawned = tokio::spawn(async { inputs_redacted() }).await.unwrap();
+            assert!(!spawned);
+        }));
+        runtime.block_on(redact_inputs(false, async { assert!(!inputs_redacted()) }));
+        assert!(!inputs_redacted());
+    }
 }
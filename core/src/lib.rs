@@ -1,7 +1,9 @@
+pub mod dense;
 pub mod download;
 pub mod infer;
 pub mod queue;
 pub mod tokenization;
+pub mod whitening;
 
 use text_embeddings_backend::BackendError;
 use thiserror::Error;
@@ -13,6 +15,10 @@ pub enum TextEmbeddingsError {
     Tokenizer(#[from] tokenizers::Error),
     #[error("Input validation error: {0}")]
     Validation(String),
+    #[error("Input validation error: `inputs` cannot be empty")]
+    EmptyInput,
+    #[error("Input validation error: `inputs` must have less than {0} tokens. Given: {1}")]
+    InputTooLong(usize, usize),
     #[error("Model is overloaded")]
     Overloaded(#[from] TryAcquireError),
     #[error("Backend error: {0}")]
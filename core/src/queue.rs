@@ -29,6 +29,8 @@ pub struct Metadata {
     pub queue_time: Instant,
     /// Number of tokens in the prompt
     pub prompt_tokens: usize,
+    /// Number of input tokens dropped by truncation
+    pub truncated_tokens: usize,
 }
 
 /// Request Queue
@@ -43,6 +45,7 @@ impl Queue {
         padded_model: bool,
         max_batch_tokens: usize,
         max_batch_requests: Option<usize>,
+        min_batch_requests: Option<usize>,
         max_concurrent_requests: usize,
     ) -> Self {
         // Create channels
@@ -54,6 +57,7 @@ impl Queue {
                 padded_model,
                 max_batch_tokens,
                 max_batch_requests,
+                min_batch_requests,
                 max_concurrent_requests,
                 queue_receiver,
             )
@@ -72,6 +76,16 @@ impl Queue {
             .expect("Queue background task dropped the receiver. This is a bug.");
     }
 
+    /// Append several entries to the queue at once so that they are not interleaved with others
+    #[instrument(skip_all)]
+    pub fn append_all(&self, entries: Vec<Entry>) {
+        // Send append command to the background task managing the state
+        // Unwrap is safe here
+        self.queue_sender
+            .send(QueueCommand::AppendAll(entries, Span::current()))
+            .expect("Queue background task dropped the receiver. This is a bug.");
+    }
+
     /// Get the next batch from the queue
     #[instrument(skip(self))]
     pub async fn next_batch(&self) -> Option<NextBatch> {
@@ -98,6 +112,7 @@ fn queue_blocking_task(
     padded_model: bool,
     max_batch_tokens: usize,
     max_batch_requests: Option<usize>,
+    min_batch_requests: Option<usize>,
     max_concurrent_requests: usize,
     mut queue_receiver: mpsc::UnboundedReceiver<QueueCommand>,
 ) {
@@ -112,6 +127,11 @@ fn queue_blocking_task(
                 entries.push_back(*entry);
                 metrics::increment_gauge!("te_queue_size", 1.0);
             }
+            QueueCommand::AppendAll(new_entries, span) => {
+                let _span = span.entered();
+                metrics::increment_gauge!("te_queue_size", new_entries.len() as f64);
+                entries.extend(new_entries);
+            }
             QueueCommand::NextBatch {
                 response_sender,
                 span,
@@ -167,6 +187,42 @@ fn queue_blocking_task(
                 }
 
                 let batch_size = metadata.len();
+
+                // Pad the batch up to `min_batch_requests` with copies of the first token of the
+                // batch. Their responses are discarded
+                let padding = (input_ids.first().copied(), position_ids.first().copied());
+                if let (Some(input_id), Some(position_id)) = padding {
+                    let token_type_id = token_type_ids[0];
+                    while metadata.len() < min_batch_requests.unwrap_or(0)
+                        && max_batch_requests.map_or(true, |max| metadata.len() < max)
+                    {
+                        let total_tokens = if padded_model {
+                            max_length as usize * (metadata.len() + 1)
+                        } else {
+                            current_tokens + 1
+                        };
+                        if total_tokens > max_batch_tokens {
+                            break;
+                        }
+
+                        input_ids.push(input_id);
+                        token_type_ids.push(token_type_id);
+                        position_ids.push(position_id);
+
+                        current_tokens += 1;
+                        metadata.push(Metadata {
+                            response_tx: oneshot::channel().0,
+                            span: Span::none(),
+                            tokenization: Duration::ZERO,
+                            queue_time: Instant::now(),
+                            prompt_tokens: 1,
+                            truncated_tokens: 0,
+                        });
+                        cu_seq_lengths.push(current_tokens as u32);
+                    }
+                    metrics::counter!("te_batch_padding", (metadata.len() - batch_size) as u64);
+                }
+
                 let next_batch = if metadata.is_empty() {
                     None
                 } else {
@@ -197,8 +253,77 @@ pub type NextBatch = (Vec<Metadata>, Batch);
 #[derive(Debug)]
 enum QueueCommand {
     Append(Box<Entry>, Span),
+    AppendAll(Vec<Entry>, Span),
     NextBatch {
         response_sender: oneshot::Sender<Option<NextBatch>>,
         span: Span,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Response = oneshot::Receiver<Result<InferResponse, BackendError>>;
+
+    fn entry(input_ids: Vec<u32>) -> (Entry, Response) {
+        let (response_tx, response_rx) = oneshot::channel();
+        let len = input_ids.len();
+        let entry = Entry {
+            encoding: Encoding {
+                token_type_ids: vec![0; len],
+                position_ids: (0..len as u32).collect(),
+                input_ids,
+                truncated_tokens: 0,
+            },
+            metadata: Metadata {
+                response_tx,
+                span: Span::none(),
+                tokenization: Duration::ZERO,
+                queue_time: Instant::now(),
+                prompt_tokens: len,
+                truncated_tokens: 0,
+            },
+        };
+        (entry, response_rx)
+    }
+
+    fn next_batch(queue: &Queue) -> Option<NextBatch> {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(queue.next_batch())
+    }
+
+    #[test]
+    fn batches_are_padded_to_min_batch_requests() {
+        let queue = Queue::new(false, 16, None, Some(4), 8);
+        let (first, _first_rx) = entry(vec![101, 7, 102]);
+        let (second, _second_rx) = entry(vec![101, 102]);
+        queue.append_all(vec![first, second]);
+
+        let (metadata, batch) = next_batch(&queue).unwrap();
+        assert_eq!(metadata.len(), 4);
+        assert_eq!(batch.input_ids, vec![101, 7, 102, 101, 102, 101, 101]);
+        assert_eq!(batch.position_ids, vec![0, 1, 2, 0, 1, 0, 0]);
+        assert_eq!(batch.cumulative_seq_lengths, vec![0, 3, 5, 6, 7]);
+        assert_eq!(batch.max_length, 3);
+        assert!(next_batch(&queue).is_none());
+    }
+
+    #[test]
+    fn padding_stays_within_batch_limits() {
+        // Padded models count `max_length` tokens per input
+        let queue = Queue::new(true, 8, None, Some(4), 8);
+        let (first, _rx) = entry(vec![101, 7, 102]);
+        queue.append(first);
+        let (metadata, _) = next_batch(&queue).unwrap();
+        assert_eq!(metadata.len(), 2);
+
+        let queue = Queue::new(false, 16, Some(3), Some(4), 8);
+        let (first, _rx) = entry(vec![101, 7, 102]);
+        queue.append(first);
+        let (metadata, _) = next_batch(&queue).unwrap();
+        assert_eq!(metadata.len(), 3);
+    }
+}
@@ -1,35 +1,47 @@
 /// Payload tokenization logic
 use crate::TextEmbeddingsError;
+use std::sync::{Arc, Mutex};
 use tokenizers::tokenizer::Tokenizer;
-use tokenizers::{EncodeInput, TruncationDirection, TruncationParams, TruncationStrategy};
-use tokio::sync::{mpsc, oneshot};
+use tokenizers::{
+    EncodeInput, PostProcessor, TruncationDirection, TruncationParams, TruncationStrategy,
+};
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore};
 use tracing::{instrument, Span};
 
 /// Validation
 #[derive(Debug, Clone)]
 pub struct Tokenization {
-    /// Channel to communicate with the background tokenization task
-    sender: mpsc::UnboundedSender<TokenizerRequest>,
+    /// Queue shared by the tokenization workers
+    sender: mpsc::UnboundedSender<(TokenizerRequest, OwnedSemaphorePermit)>,
+    /// Limit of the requests tokenized at the same time, released by the workers
+    permits: Arc<Semaphore>,
 }
 
 impl Tokenization {
+    /// `permits` limits the number of inputs tokenized at the same time so that workers leave CPU
+    /// time to the async runtime. It may be shared with the tokenization of other models, as each
+    /// model has its own workers. It defaults to one permit per worker.
     pub fn new(
         workers: usize,
+        permits: Option<Arc<Semaphore>>,
         tokenizer: Tokenizer,
         max_input_length: usize,
         position_offset: usize,
+        pair_separator: Option<String>,
     ) -> Self {
         tracing::info!("Starting {workers} tokenization workers");
 
         // Create channel
-        let (sender, mut round_robin_receiver) = mpsc::unbounded_channel();
-        let mut senders = Vec::with_capacity(workers);
+        // Workers pull from the same queue so that a long input does not delay the requests
+        // queued behind it while other workers are idle
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let receiver = Arc::new(Mutex::new(receiver));
 
         // Create workers
         for _ in 0..workers {
             let tokenizer_clone = tokenizer.clone();
-            let (tokenizer_sender, tokenizer_receiver) = mpsc::unbounded_channel();
-            senders.push(tokenizer_sender);
+            let pair_separator_clone = pair_separator.clone();
+            let receiver_clone = receiver.clone();
 
             // Spawn worker
             std::thread::spawn(move || {
@@ -37,25 +49,29 @@ impl Tokenization {
                     tokenizer_clone,
                     max_input_length,
                     position_offset,
-                    tokenizer_receiver,
+                    pair_separator_clone,
+                    receiver_clone,
                 )
             });
         }
 
-        // Create tokenization round robin task
-        tokio::spawn(async move {
-            // Loop over requests
-            loop {
-                for sender in &senders {
-                    match round_robin_receiver.recv().await {
-                        None => return,
-                        Some(request) => sender.send(request).unwrap(),
-                    };
-                }
-            }
-        });
+        Self {
+            sender,
+            permits: permits.unwrap_or_else(|| Arc::new(Semaphore::new(workers))),
+        }
+    }
 
-        Self { sender }
+    /// Wait until a worker may start tokenizing, then queue a request
+    async fn send(&self, request: TokenizerRequest) {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Semaphore has been closed. This is a bug.");
+        self.sender
+            .send((request, permit))
+            .expect("Tokenization background task dropped the receiver. This is a bug.");
     }
 
     #[instrument(skip_all)]
@@ -63,21 +79,107 @@ impl Tokenization {
         &self,
         inputs: EncodingInput,
         truncate: bool,
+        add_special_tokens: bool,
     ) -> Result<Encoding, TextEmbeddingsError> {
         // Check if inputs is empty
         if inputs.is_empty() {
-            return Err(TextEmbeddingsError::Validation(
-                "`inputs` cannot be empty".to_string(),
-            ));
+            return Err(TextEmbeddingsError::EmptyInput);
         }
 
         // Create response channel
         let (response_sender, response_receiver) = oneshot::channel();
         // Send request to the background validation task
+        self.send(TokenizerRequest::Encode(
+            inputs,
+            truncate,
+            add_special_tokens,
+            response_sender,
+            Span::current(),
+        ))
+        .await;
+
+        // Await on response channel
         // Unwrap is safe here
-        self.sender
-            .send((inputs, truncate, response_sender, Span::current()))
-            .expect("Tokenization background task dropped the receiver. This is a bug.");
+        response_receiver.await.expect("Tokenization background task dropped the sender without sending a response. This is a bug.")
+    }
+
+    /// Encode several inputs in parallel
+    ///
+    /// All the inputs are sent to the workers before waiting on the first encoding.
+    #[instrument(skip_all)]
+    pub async fn encode_batch(
+        &self,
+        inputs: Vec<EncodingInput>,
+        truncate: bool,
+        add_special_tokens: bool,
+    ) -> Result<Vec<Encoding>, TextEmbeddingsError> {
+        // Check if inputs is empty
+        if inputs.iter().any(EncodingInput::is_empty) {
+            return Err(TextEmbeddingsError::EmptyInput);
+        }
+
+        let mut response_receivers = Vec::with_capacity(inputs.len());
+        for inputs in inputs {
+            // Create response channel
+            let (response_sender, response_receiver) = oneshot::channel();
+            // Send request to the background validation task
+            // Workers release their permit once done, so this does not wait on the receivers
+            self.send(TokenizerRequest::Encode(
+                inputs,
+                truncate,
+                add_special_tokens,
+                response_sender,
+                Span::current(),
+            ))
+            .await;
+            response_receivers.push(response_receiver);
+        }
+
+        let mut encodings = Vec::with_capacity(response_receivers.len());
+        for response_receiver in response_receivers {
+            // Await on response channel
+            // Unwrap is safe here
+            let encoding = response_receiver.await.expect("Tokenization background task dropped the sender without sending a response. This is a bug.")?;
+            encodings.push(encoding);
+        }
+        Ok(encodings)
+    }
+
+    /// Get the byte offsets of the tokens of an input, without special tokens or truncation
+    #[instrument(skip_all)]
+    pub async fn offsets(&self, input: String) -> Result<Vec<(usize, usize)>, TextEmbeddingsError> {
+        // Create response channel
+        let (response_sender, response_receiver) = oneshot::channel();
+        // Send request to the background validation task
+        self.send(TokenizerRequest::Offsets(
+            input,
+            response_sender,
+            Span::current(),
+        ))
+        .await;
+
+        // Await on response channel
+        // Unwrap is safe here
+        response_receiver.await.expect("Tokenization background task dropped the sender without sending a response. This is a bug.")
+    }
+
+    /// Count the tokens of an input without truncating it
+    #[instrument(skip_all)]
+    pub async fn count(
+        &self,
+        inputs: EncodingInput,
+        add_special_tokens: bool,
+    ) -> Result<usize, TextEmbeddingsError> {
+        // Create response channel
+        let (response_sender, response_receiver) = oneshot::channel();
+        // Send request to the background validation task
+        self.send(TokenizerRequest::Count(
+            inputs,
+            add_special_tokens,
+            response_sender,
+            Span::current(),
+        ))
+        .await;
 
         // Await on response channel
         // Unwrap is safe here
@@ -90,23 +192,75 @@ fn tokenizer_worker(
     mut tokenizer: Tokenizer,
     max_input_length: usize,
     position_offset: usize,
-    mut receiver: mpsc::UnboundedReceiver<TokenizerRequest>,
+    pair_separator: Option<String>,
+    receiver: Arc<Mutex<mpsc::UnboundedReceiver<(TokenizerRequest, OwnedSemaphorePermit)>>>,
 ) {
     // Loop over requests
-    while let Some((inputs, truncate, response_tx, parent_span)) = receiver.blocking_recv() {
-        parent_span.in_scope(|| {
-            if !response_tx.is_closed() {
-                // It's possible that the user dropped its request resulting in a send error.
-                // We just discard the error
-                let _ = response_tx.send(encode_input(
-                    inputs,
-                    truncate,
-                    max_input_length,
-                    position_offset,
-                    &mut tokenizer,
-                ));
+    loop {
+        // Only one idle worker waits on the queue at a time
+        // The lock is released before the request is processed
+        let request = receiver.lock().unwrap().blocking_recv();
+        // The permit is released once the request is processed
+        let Some((request, _permit)) = request else {
+            return;
+        };
+        match request {
+            TokenizerRequest::Encode(
+                inputs,
+                truncate,
+                add_special_tokens,
+                response_tx,
+                parent_span,
+            ) => {
+                parent_span.in_scope(|| {
+                    if !response_tx.is_closed() {
+                        // It's possible that the user dropped its request resulting in a send error.
+                        // We just discard the error
+                        let _ = response_tx.send(encode_input(
+                            inputs,
+                            truncate,
+                            add_special_tokens,
+                            max_input_length,
+                            position_offset,
+                            pair_separator.as_deref(),
+                            &mut tokenizer,
+                        ));
+                    }
+                })
             }
-        })
+            TokenizerRequest::Count(inputs, add_special_tokens, response_tx, parent_span) => {
+                parent_span.in_scope(|| {
+                    if !response_tx.is_closed() {
+                        let _ = response_tx.send(count_input(
+                            inputs,
+                            add_special_tokens,
+                            pair_separator.as_deref(),
+                            &mut tokenizer,
+                        ));
+                    }
+                })
+            }
+            TokenizerRequest::Offsets(input, response_tx, parent_span) => {
+                parent_span.in_scope(|| {
+                    if !response_tx.is_closed() {
+                        let _ = response_tx.send(token_offsets(input, &mut tokenizer));
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Convert an input to the tokenizer input
+fn to_encode_input(inputs: EncodingInput, pair_separator: Option<&str>) -> EncodeInput<'static> {
+    match inputs {
+        EncodingInput::Single(s) => s.into(),
+        EncodingInput::Prefixed(prefix, content) => format!("{prefix}{content}").into(),
+        // Join pairs as a single sequence for models that do not use the tokenizer pair template
+        EncodingInput::Dual(s1, s2) => match pair_separator {
+            Some(separator) => format!("{s1}{separator}{s2}").into(),
+            None => (s1, s2).into(),
+        },
     }
 }
 
@@ -114,8 +268,10 @@ fn tokenizer_worker(
 fn encode_input(
     inputs: EncodingInput,
     truncate: bool,
+    add_special_tokens: bool,
     max_input_length: usize,
     position_offset: usize,
+    pair_separator: Option<&str>,
     tokenizer: &mut Tokenizer,
 ) -> Result<Encoding, TextEmbeddingsError> {
     // Default truncation params
@@ -126,20 +282,43 @@ fn encode_input(
         stride: 0,
     });
 
-    let inputs: EncodeInput = match inputs {
-        EncodingInput::Single(s) => s.into(),
-        EncodingInput::Dual(s1, s2) => (s1, s2).into(),
+    tokenizer.with_truncation(truncate_params)?;
+    let (encoding, truncated_tokens) = match inputs {
+        EncodingInput::Single(input) if truncate => {
+            let encoding = encode_prefix(&input, add_special_tokens, max_input_length, tokenizer)?;
+            let truncated_tokens = overflowing_tokens(&encoding);
+            (encoding, truncated_tokens)
+        }
+        EncodingInput::Prefixed(prefix, content) => encode_prefixed(
+            &prefix,
+            &content,
+            truncate,
+            add_special_tokens,
+            max_input_length,
+            tokenizer,
+        )?,
+        inputs => {
+            let inputs = to_encode_input(inputs, pair_separator);
+            let encoding = tokenizer.encode(inputs.clone(), add_special_tokens)?;
+            let truncated_tokens = match inputs {
+                _ if encoding.get_overflowing().is_empty() => 0,
+                // The overflowing tokens of both sequences are combined with each other, so a
+                // truncated pair is counted again without truncation
+                EncodeInput::Dual(..) => {
+                    let full = tokenizer
+                        .with_truncation(None)?
+                        .encode(inputs, add_special_tokens)?;
+                    full.len().saturating_sub(encoding.len())
+                }
+                EncodeInput::Single(_) => overflowing_tokens(&encoding),
+            };
+            (encoding, truncated_tokens)
+        }
     };
-
-    let encoding = tokenizer
-        .with_truncation(truncate_params)?
-        .encode(inputs, true)?;
     let seq_len = encoding.len();
 
     if seq_len > max_input_length {
-        return Err(TextEmbeddingsError::Validation(format!(
-            "`inputs` must have less than {max_input_length} tokens. Given: {seq_len}"
-        )));
+        return Err(TextEmbeddingsError::InputTooLong(max_input_length, seq_len));
     }
 
     metrics::histogram!("te_request_input_length", seq_len as f64);
@@ -149,27 +328,166 @@ fn encode_input(
         token_type_ids: encoding.get_type_ids().to_vec(),
         position_ids: (position_offset as u32..(seq_len + position_offset) as u32)
             .collect::<Vec<_>>(),
+        truncated_tokens,
     })
 }
 
+/// Number of tokens dropped by the truncation of an encoding, without special tokens
+fn overflowing_tokens(encoding: &tokenizers::Encoding) -> usize {
+    encoding
+        .get_overflowing()
+        .iter()
+        .map(|overflowing| {
+            overflowing
+                .get_special_tokens_mask()
+                .iter()
+                .filter(|special| **special == 0)
+                .count()
+        })
+        .sum()
+}
+
+/// Bytes of input tokenized per kept token on the first attempt of [`encode_prefix`]
+const PREFIX_BYTES_PER_TOKEN: usize = 8;
+
+/// Encode a single input that is truncated to `max_input_length` tokens
+///
+/// Only a prefix of large inputs is tokenized: it is cut on whitespace and grown until it has
+/// more tokens than are kept, at which point the kept tokens are the same as for the full input.
+/// The tokenizer must already be set to truncate.
+fn encode_prefix(
+    input: &str,
+    add_special_tokens: bool,
+    max_input_length: usize,
+    tokenizer: &Tokenizer,
+) -> Result<tokenizers::Encoding, TextEmbeddingsError> {
+    let mut max_bytes = max_input_length.saturating_mul(PREFIX_BYTES_PER_TOKEN);
+    while max_bytes < input.len() {
+        if let Some(prefix) = whitespace_prefix(input, max_bytes) {
+            let encoding = tokenizer.encode(prefix, add_special_tokens)?;
+            // Tokens beyond the kept ones were found, so the prefix was long enough
+            if !encoding.get_overflowing().is_empty() {
+                return Ok(encoding);
+            }
+        }
+        max_bytes = max_bytes.saturating_mul(2);
+    }
+    Ok(tokenizer.encode(input, add_special_tokens)?)
+}
+
+/// Encode an instruction prefix followed by the content it applies to, with the number of
+/// content tokens dropped by truncation
+///
+/// Both are encoded as a single text, as without a prefix. Only when it has to be truncated are
+/// they encoded separately, so that the content is truncated and the prefix is kept whole.
+fn encode_prefixed(
+    prefix: &str,
+    content: &str,
+    truncate: bool,
+    add_special_tokens: bool,
+    max_input_length: usize,
+    tokenizer: &mut Tokenizer,
+) -> Result<(tokenizers::Encoding, usize), TextEmbeddingsError> {
+    let joined = tokenizer
+        .with_truncation(None)?
+        .encode(format!("{prefix}{content}"), add_special_tokens)?;
+    if !truncate || joined.len() <= max_input_length {
+        return Ok((joined, 0));
+    }
+
+    let mut encoding = tokenizer.encode(prefix, false)?;
+    let added_tokens = match (add_special_tokens, tokenizer.get_post_processor()) {
+        (true, Some(post_processor)) => post_processor.added_tokens(false),
+        _ => 0,
+    };
+    // Tokens left to the content once the prefix and the special tokens are counted
+    let budget = max_input_length.saturating_sub(encoding.len() + added_tokens);
+
+    let mut content = match budget {
+        // The prefix alone is too long, which is reported once it is post-processed
+        0 => tokenizers::Encoding::default(),
+        budget => {
+            tokenizer.with_truncation(Some(TruncationParams {
+                direction: TruncationDirection::Right,
+                max_length: budget,
+                strategy: TruncationStrategy::LongestFirst,
+                stride: 0,
+            }))?;
+            let content = encode_prefix(content, false, budget, tokenizer)?;
+            tokenizer.with_truncation(None)?;
+            content
+        }
+    };
+    // The content is encoded without special tokens
+    let truncated_tokens = content
+        .take_overflowing()
+        .iter()
+        .map(tokenizers::Encoding::len)
+        .sum();
+    encoding.merge_with(content, true);
+    let encoding = tokenizer.post_process(encoding, None, add_special_tokens)?;
+    Ok((encoding, truncated_tokens))
+}
+
+/// Longest prefix of at most `max_bytes` ending before a whitespace
+fn whitespace_prefix(input: &str, max_bytes: usize) -> Option<&str> {
+    let mut end = max_bytes.min(input.len());
+    while !input.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = input[..end].rfind(char::is_whitespace)?;
+    Some(input[..end].trim_end())
+}
+
+/// Get the number of tokens of an input
+fn count_input(
+    inputs: EncodingInput,
+    add_special_tokens: bool,
+    pair_separator: Option<&str>,
+    tokenizer: &mut Tokenizer,
+) -> Result<usize, TextEmbeddingsError> {
+    let inputs = to_encode_input(inputs, pair_separator);
+
+    let encoding = tokenizer
+        .with_truncation(None)?
+        .encode(inputs, add_special_tokens)?;
+    Ok(encoding.len())
+}
+
+/// Get the byte offsets of the tokens of an input
+fn token_offsets(
+    input: String,
+    tokenizer: &mut Tokenizer,
+) -> Result<Vec<(usize, usize)>, TextEmbeddingsError> {
+    let encoding = tokenizer.with_truncation(None)?.encode(input, false)?;
+    Ok(encoding.get_offsets().to_vec())
+}
+
 #[derive(Debug)]
 pub struct Encoding {
     pub input_ids: Vec<u32>,
     pub token_type_ids: Vec<u32>,
     pub position_ids: Vec<u32>,
+    /// Number of input tokens dropped by truncation, without special tokens. Only a prefix of
+    /// large inputs is tokenized, so only the dropped tokens of that prefix are counted
+    pub truncated_tokens: usize,
 }
 
 #[derive(Debug)]
 pub enum EncodingInput {
     Single(String),
     Dual(String, String),
+    /// Instruction prefix and the content it applies to, of which only the content is truncated
+    Prefixed(String, String),
 }
 
 impl EncodingInput {
     fn is_empty(&self) -> bool {
         match self {
             EncodingInput::Single(s) => s.is_empty(),
-            EncodingInput::Dual(s1, s2) => s1.is_empty() && s2.is_empty(),
+            EncodingInput::Dual(s1, s2) | EncodingInput::Prefixed(s1, s2) => {
+                s1.is_empty() && s2.is_empty()
+            }
         }
     }
 }
@@ -186,9 +504,239 @@ impl From<(String, String)> for EncodingInput {
     }
 }
 
-type TokenizerRequest = (
-    EncodingInput,
-    bool,
-    oneshot::Sender<Result<Encoding, TextEmbeddingsError>>,
-    Span,
-);
+enum TokenizerRequest {
+    Encode(
+        EncodingInput,
+        bool,
+        bool,
+        oneshot::Sender<Result<Encoding, TextEmbeddingsError>>,
+        Span,
+    ),
+    Count(
+        EncodingInput,
+        bool,
+        oneshot::Sender<Result<usize, TextEmbeddingsError>>,
+        Span,
+    ),
+    Offsets(
+        String,
+        oneshot::Sender<Result<Vec<(usize, usize)>, TextEmbeddingsError>>,
+        Span,
+    ),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::Whitespace;
+    use tokenizers::PreTokenizerWrapper;
+
+    fn word_tokenizer() -> Tokenizer {
+        let vocab = HashMap::from([
+            ("[UNK]".to_string(), 0),
+            ("a".to_string(), 1),
+            ("b".to_string(), 2),
+            ("ba".to_string(), 3),
+        ]);
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(PreTokenizerWrapper::from(Whitespace {}));
+        tokenizer
+    }
+
+    #[test]
+    fn truncated_large_input_matches_full_encoding() {
+        let input = "a b ".repeat(10_000) + "a";
+        let mut tokenizer = word_tokenizer();
+        let encoding = encode_input(
+            EncodingInput::Single(input.clone()),
+            true,
+            false,
+            16,
+            0,
+            None,
+            &mut tokenizer,
+        )
+        .unwrap();
+
+        let full = tokenizer.encode(input, false).unwrap();
+        assert_eq!(encoding.input_ids, full.get_ids());
+        assert_eq!(encoding.input_ids.len(), 16);
+    }
+
+    #[test]
+    fn truncation_counts_the_dropped_tokens() {
+        let mut tokenizer = word_tokenizer();
+        let mut encode = |inputs, truncate| {
+            encode_input(inputs, truncate, false, 16, 0, None, &mut tokenizer).unwrap()
+        };
+
+        let encoding = encode(EncodingInput::Single("a ".repeat(20)), true);
+        assert_eq!(encoding.input_ids.len(), 16);
+        assert_eq!(encoding.truncated_tokens, 4);
+
+        let encoding = encode(EncodingInput::Dual("a ".repeat(10), "b ".repeat(10)), true);
+        assert_eq!(encoding.input_ids.len(), 16);
+        assert_eq!(encoding.truncated_tokens, 4);
+
+        let encoding = encode(EncodingInput::Single("a ".repeat(10)), true);
+        assert_eq!(encoding.truncated_tokens, 0);
+    }
+
+    #[test]
+    fn prefixed_input_only_truncates_content() {
+        let mut tokenizer = word_tokenizer();
+        let encoding = encode_input(
+            EncodingInput::Prefixed("b b ".to_string(), "a ".repeat(100)),
+            true,
+            false,
+            4,
+            0,
+            None,
+            &mut tokenizer,
+        )
+        .unwrap();
+        assert_eq!(encoding.input_ids, vec![2, 2, 1, 1]);
+        // Only a prefix of the content is tokenized, so not all its dropped tokens are counted
+        assert_eq!(encoding.truncated_tokens, 6);
+
+        // The prefix is never truncated
+        let result = encode_input(
+            EncodingInput::Prefixed("b ".repeat(8), "a".to_string()),
+            true,
+            false,
+            4,
+            0,
+            None,
+            &mut tokenizer,
+        );
+        assert!(matches!(
+            result,
+            Err(TextEmbeddingsError::InputTooLong(4, 8))
+        ));
+    }
+
+    #[test]
+    fn prefixed_input_that_fits_is_encoded_as_one_text() {
+        let mut tokenizer = word_tokenizer();
+        // The prefix and the content form a single word
+        let encoding = encode_input(
+            EncodingInput::Prefixed("b".to_string(), "a".to_string()),
+            true,
+            false,
+            4,
+            0,
+            None,
+            &mut tokenizer,
+        )
+        .unwrap();
+        let joined = tokenizer.encode("ba", false).unwrap();
+        assert_eq!(encoding.input_ids, joined.get_ids());
+        assert_eq!(encoding.input_ids, vec![3]);
+        assert_eq!(encoding.truncated_tokens, 0);
+
+        let count = count_input(
+            EncodingInput::Prefixed("b".to_string(), "a".to_string()),
+            false,
+            None,
+            &mut tokenizer,
+        )
+        .unwrap();
+        assert_eq!(count, encoding.input_ids.len());
+    }
+
+    #[test]
+    fn encode_batch_keeps_input_order() {
+        let tokenization = Tokenization::new(2, None, word_tokenizer(), 16, 0, None);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let inputs = vec![
+            EncodingInput::Single("a ".repeat(1000)),
+            EncodingInput::Single("b".to_string()),
+            EncodingInput::Dual("a".to_string(), "b".to_string()),
+        ];
+        let encodings = runtime
+            .block_on(tokenization.encode_batch(inputs, true, false))
+            .unwrap();
+        let ids: Vec<Vec<u32>> = encodings.into_iter().map(|e| e.input_ids).collect();
+        assert_eq!(ids, vec![vec![1; 16], vec![2], vec![1, 2]]);
+
+        // A single empty input rejects the batch
+        let inputs = vec![
+            EncodingInput::Single("a".to_string()),
+            EncodingInput::Single(String::new()),
+        ];
+        let result = runtime.block_on(tokenization.encode_batch(inputs, true, false));
+        assert!(matches!(result, Err(TextEmbeddingsError::EmptyInput)));
+    }
+
+    #[test]
+    fn long_input_does_not_delay_the_next_requests() {
+        let tokenization = Tokenization::new(2, None, word_tokenizer(), 10_000_000, 0, None);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+
+        let long = runtime.spawn({
+            let tokenization = tokenization.clone();
+            async move {
+                let input = EncodingInput::Single("a ".repeat(200_000));
+                tokenization.encode(input, false, false).await
+            }
+        });
+        // Let a worker start on the long input
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        // Sent one after the other, so that a round robin would queue one of them behind the
+        // long input
+        runtime.block_on(async {
+            for _ in 0..4 {
+                let input = EncodingInput::Single("b".to_string());
+                let encoding = tokenization.encode(input, false, false).await.unwrap();
+                assert_eq!(encoding.input_ids, vec![2]);
+            }
+        });
+        assert!(!long.is_finished());
+        assert_eq!(
+            runtime.block_on(long).unwrap().unwrap().input_ids.len(),
+            200_000
+        );
+    }
+
+    #[test]
+    fn models_share_tokenization_permits() {
+        let permits = Arc::new(Semaphore::new(1));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        // More workers than permits, and each batch needs the permit several times
+        for _ in 0..2 {
+            let tokenization =
+                Tokenization::new(4, Some(permits.clone()), word_tokenizer(), 16, 0, None);
+            let inputs = (0..3)
+                .map(|_| EncodingInput::Single("a b".to_string()))
+                .collect();
+            let encodings = runtime
+                .block_on(tokenization.encode_batch(inputs, true, false))
+                .unwrap();
+            assert_eq!(encodings.len(), 3);
+        }
+    }
+
+    #[test]
+    fn whitespace_prefix_cuts_on_char_boundary() {
+        assert_eq!(whitespace_prefix("ab cé d", 5), Some("ab"));
+        assert_eq!(whitespace_prefix("ab  cd", 4), Some("ab"));
+        assert_eq!(whitespace_prefix("abcd", 2), None);
+    }
+}
@@ -0,0 +1,86 @@
+/// Linear transform applied to embeddings after inference: `W · (v - mean)`
+///
+/// `W` can have fewer rows than columns to also reduce the dimension, as with PCA.
+#[derive(Debug, Clone)]
+pub struct Whitening {
+    /// Row-major `output_dim x input_dim` matrix
+    matrix: Vec<f32>,
+    mean: Vec<f32>,
+}
+
+impl Whitening {
+    /// Create a transform from the rows of `W` and the mean subtracted before applying it
+    ///
+    /// A missing mean is treated as zero.
+    pub fn new(matrix: Vec<Vec<f32>>, mean: Option<Vec<f32>>) -> Result<Self, String> {
+        let input_dim = match matrix.first() {
+            Some(row) if !row.is_empty() => row.len(),
+            _ => return Err("the matrix is empty".to_string()),
+        };
+        if let Some(i) = matrix.iter().position(|row| row.len() != input_dim) {
+            return Err(format!(
+                "row {i} has {} columns, expected {input_dim}",
+                matrix[i].len()
+            ));
+        }
+        let mean = mean.unwrap_or_else(|| vec![0.0; input_dim]);
+        if mean.len() != input_dim {
+            return Err(format!(
+                "the mean has {} values, expected {input_dim}",
+                mean.len()
+            ));
+        }
+
+        Ok(Self {
+            matrix: matrix.into_iter().flatten().collect(),
+            mean,
+        })
+    }
+
+    /// Dimension of the embeddings the transform applies to
+    pub fn input_dim(&self) -> usize {
+        self.mean.len()
+    }
+
+    /// Dimension of the transformed embeddings
+    pub fn output_dim(&self) -> usize {
+        self.matrix.len() / self.mean.len()
+    }
+
+    /// Transform an embedding of `input_dim` values
+    pub fn apply(&self, embedding: &[f32]) -> Vec<f32> {
+        let centered: Vec<f32> = embedding
+            .iter()
+            .zip(&self.mean)
+            .map(|(v, mean)| v - mean)
+            .collect();
+        self.matrix
+            .chunks_exact(self.input_dim())
+            .map(|row| row.iter().zip(&centered).map(|(w, v)| w * v).sum())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_centers_and_projects() {
+        let whitening = Whitening::new(
+            vec![vec![1.0, 0.0, 0.0], vec![0.0, 2.0, -1.0]],
+            Some(vec![1.0, 1.0, 1.0]),
+        )
+        .unwrap();
+        assert_eq!(whitening.input_dim(), 3);
+        assert_eq!(whitening.output_dim(), 2);
+        assert_eq!(whitening.apply(&[2.0, 3.0, 4.0]), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn new_rejects_ragged_matrix() {
+        assert!(Whitening::new(vec![vec![1.0, 0.0], vec![1.0]], None).is_err());
+        assert!(Whitening::new(vec![vec![1.0, 0.0]], Some(vec![0.0])).is_err());
+        assert!(Whitening::new(Vec::new(), None).is_err());
+    }
+}
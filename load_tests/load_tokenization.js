@@ -0,0 +1,56 @@
+import {check} from 'k6';
+import http from 'k6/http';
+import {Trend} from 'k6/metrics';
+
+const host = __ENV.HOST || '127.0.0.1:8080';
+
+const totalTime = new Trend('total_time', true);
+const tokenizationTIme = new Trend('tokenization_time', true);
+const queueTime = new Trend('queue_time', true);
+const inferenceTime = new Trend('inference_time', true);
+
+// Short inputs at a high rate, so that tokenization dominates. One request in a hundred is a
+// long input, which must not delay the short inputs queued behind it
+const shortInput = 'What is Deep Learning?';
+const longInput = 'Deep Learning is a subset of machine learning. '.repeat(2000);
+
+export const options = {
+    thresholds: {
+        http_req_failed: ['rate==0'],
+    },
+    scenarios: {
+        load_test: {
+            executor: 'constant-arrival-rate',
+            duration: '30s',
+            preAllocatedVUs: 2000,
+            rate: 5000,
+            timeUnit: '1s',
+            gracefulStop: '1s',
+        },
+    },
+};
+
+export default function () {
+    const payload = JSON.stringify({
+        inputs: Math.random() < 0.01 ? longInput : shortInput,
+        truncate: true,
+    });
+
+    const headers = {'Content-Type': 'application/json'};
+    const res = http.post(`http://${host}/embed`, payload, {
+        headers, timeout: '20m'
+    });
+
+    check(res, {
+        'Post status is 200': (r) => res.status === 200,
+    });
+
+    if (res.status === 200) {
+        totalTime.add(res.headers["X-Total-Time"]);
+        tokenizationTIme.add(res.headers["X-Tokenization-Time"]);
+        queueTime.add(res.headers["X-Queue-Time"]);
+        inferenceTime.add(res.headers["X-Inference-Time"]);
+    } else {
+        console.log(res.error);
+    }
+}
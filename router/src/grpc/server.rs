@@ -3,8 +3,11 @@ use crate::grpc::{
     EmbedRequest, EmbedResponse, InfoRequest, InfoResponse, PredictRequest, PredictResponse,
     Prediction, Rank, RerankRequest, RerankResponse,
 };
+use crate::shutdown::ShuttingDown;
 use crate::ResponseMetadata;
-use crate::{grpc, shutdown, ErrorResponse, ErrorType, Info, ModelType};
+use crate::{
+    check_query_tokens, grpc, mean_duration, shutdown, ErrorResponse, ErrorType, Info, ModelType,
+};
 use futures::future::join_all;
 use metrics_exporter_prometheus::PrometheusBuilder;
 use std::net::SocketAddr;
@@ -39,6 +42,8 @@ struct TextEmbeddingsService {
     infer: Infer,
     info: Info,
     max_parallel_stream_requests: usize,
+    /// Maximum time the inputs of a request wait for a concurrency permit
+    permit_wait_timeout: Option<Duration>,
 }
 
 impl TextEmbeddingsService {
@@ -47,10 +52,15 @@ impl TextEmbeddingsService {
             .ok()
             .and_then(|s| s.parse::<usize>().ok())
             .unwrap_or(1024);
+        let permit_wait_timeout = std::env::var("PERMIT_WAIT_TIMEOUT_MS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(Duration::from_millis);
         Self {
             infer,
             info,
             max_parallel_stream_requests,
+            permit_wait_timeout,
         }
     }
 
@@ -76,7 +86,13 @@ impl TextEmbeddingsService {
         let compute_chars = request.inputs.chars().count();
         let response = self
             .infer
-            .embed(request.inputs, request.truncate, request.normalize, permit)
+            .embed(
+                request.inputs,
+                request.truncate,
+                true,
+                request.normalize,
+                permit,
+            )
             .await
             .map_err(ErrorResponse::from)?;
 
@@ -124,7 +140,13 @@ impl TextEmbeddingsService {
         let compute_chars = request.inputs.chars().count();
         let response = self
             .infer
-            .predict(request.inputs, request.truncate, request.raw_scores, permit)
+            .predict(
+                request.inputs,
+                request.truncate,
+                true,
+                request.raw_scores,
+                permit,
+            )
             .await
             .map_err(ErrorResponse::from)?;
 
@@ -251,7 +273,17 @@ impl grpc::embed_server::Embed for TextEmbeddingsService {
         tokio::spawn(async move {
             while let Some((request, mut sender)) = embed_receiver.recv().await {
                 // Wait on permit before spawning the task to avoid creating more tasks than needed
-                let permit = local.infer.acquire_permit().await;
+                let permit = match local
+                    .infer
+                    .acquire_permit_timeout(local.permit_wait_timeout)
+                    .await
+                {
+                    Ok(permit) => permit,
+                    Err(err) => {
+                        let _ = sender.send(Err(ErrorResponse::from(err).into()));
+                        continue;
+                    }
+                };
 
                 // Required for the async move below
                 let task_local = local.clone();
@@ -367,7 +399,17 @@ impl grpc::predict_server::Predict for TextEmbeddingsService {
         tokio::spawn(async move {
             while let Some((request, mut sender)) = predict_receiver.recv().await {
                 // Wait on permit before spawning the task to avoid creating more tasks than needed
-                let permit = local.infer.acquire_permit().await;
+                let permit = match local
+                    .infer
+                    .acquire_permit_timeout(local.permit_wait_timeout)
+                    .await
+                {
+                    Ok(permit) => permit,
+                    Err(err) => {
+                        let _ = sender.send(Err(ErrorResponse::from(err).into()));
+                        continue;
+                    }
+                };
 
                 // Required for the async move below
                 let task_local = local.clone();
@@ -472,15 +514,19 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
         }?;
 
         // Closure for rerank
+        let permit_wait_timeout = self.permit_wait_timeout;
         let rerank_inner = move |query: String,
                                  text: String,
                                  truncate: bool,
                                  raw_scores: bool,
                                  infer: Infer| async move {
-            let permit = infer.acquire_permit().await;
+            let permit = infer
+                .acquire_permit_timeout(permit_wait_timeout)
+                .await
+                .map_err(ErrorResponse::from)?;
 
             let response = infer
-                .predict((query, text), truncate, raw_scores, permit)
+                .predict((query, text), truncate, true, raw_scores, permit)
                 .await
                 .map_err(ErrorResponse::from)?;
 
@@ -507,11 +553,14 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
             let err = ErrorResponse {
                 error: message,
                 error_type: ErrorType::Validation,
+                code: "batch_too_large".to_string(),
             };
             metrics::increment_counter!("te_request_failure", "err" => "batch_size");
             Err(err)?;
         }
 
+        check_query_tokens(&self.infer, &self.info, &request.query).await?;
+
         let mut futures = Vec::with_capacity(batch_size);
         let query_chars = request.query.chars().count();
         let mut total_compute_chars = query_chars * batch_size;
@@ -568,9 +617,9 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
             total_compute_chars,
             total_compute_tokens,
             start_time,
-            Duration::from_nanos(total_tokenization_time / batch_size),
-            Duration::from_nanos(total_queue_time / batch_size),
-            Duration::from_nanos(total_inference_time / batch_size),
+            mean_duration(total_tokenization_time, batch_size),
+            mean_duration(total_queue_time, batch_size),
+            mean_duration(total_inference_time, batch_size),
         );
         response_metadata.record_span(&span);
         response_metadata.record_metrics();
@@ -635,7 +684,7 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
                                  infer: Infer,
                                  permit: OwnedSemaphorePermit| async move {
             let response = infer
-                .predict((query, text.clone()), truncate, raw_scores, permit)
+                .predict((query, text.clone()), truncate, true, raw_scores, permit)
                 .await
                 .map_err(ErrorResponse::from)?;
 
@@ -667,6 +716,7 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
 
         // Required for the async move below
         let local_infer = self.infer.clone();
+        let permit_wait_timeout = self.permit_wait_timeout;
 
         // Background task that uses the bounded channel
         tokio::spawn(async move {
@@ -674,7 +724,16 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
                 rerank_receiver.recv().await
             {
                 // Wait on permit before spawning the task to avoid creating more tasks than needed
-                let permit = local_infer.acquire_permit().await;
+                let permit = match local_infer
+                    .acquire_permit_timeout(permit_wait_timeout)
+                    .await
+                {
+                    Ok(permit) => permit,
+                    Err(err) => {
+                        let _ = sender.send(Err(ErrorResponse::from(err)));
+                        continue;
+                    }
+                };
 
                 // Required for the async move below
                 let task_infer = local_infer.clone();
@@ -698,6 +757,8 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
         // Set by first request
         let mut raw_scores = None;
         let mut return_text = None;
+        // Last query checked against `max_query_tokens`, as messages usually repeat the query
+        let mut checked_query = None;
 
         // Intermediate channels
         // Required to keep the order of the requests
@@ -706,6 +767,11 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
         while let Some(request) = request_stream.next().await {
             let request = request?;
 
+            if checked_query.as_ref() != Some(&request.query) {
+                check_query_tokens(&self.infer, &self.info, &request.query).await?;
+                checked_query = Some(request.query.clone());
+            }
+
             // Create return channel
             let (result_sender, result_receiver) = oneshot::channel();
             // Push to intermediate channel and preserve ordering
@@ -780,6 +846,7 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
             let err = ErrorResponse {
                 error: message,
                 error_type: ErrorType::Backend,
+                code: "missing_values".to_string(),
             };
             metrics::increment_counter!("te_request_failure", "err" => "missing_values");
             Err(err)?;
@@ -797,9 +864,9 @@ impl grpc::rerank_server::Rerank for TextEmbeddingsService {
             total_compute_chars,
             total_compute_tokens,
             start_time,
-            Duration::from_nanos(total_tokenization_time / batch_size),
-            Duration::from_nanos(total_queue_time / batch_size),
-            Duration::from_nanos(total_inference_time / batch_size),
+            mean_duration(total_tokenization_time, batch_size),
+            mean_duration(total_queue_time, batch_size),
+            mean_duration(total_inference_time, batch_size),
         );
         response_metadata.record_span(&span);
         response_metadata.record_metrics();
@@ -927,7 +994,10 @@ pub async fn run(
         .add_service(grpc::EmbedServer::new(service.clone()))
         .add_service(grpc::PredictServer::new(service.clone()))
         .add_service(grpc::RerankServer::new(service))
-        .serve_with_shutdown(addr, shutdown::shutdown_signal())
+        .serve_with_shutdown(
+            addr,
+            shutdown::shutdown_signal(ShuttingDown::default(), Duration::ZERO),
+        )
         .await?;
 
     Ok(())
@@ -941,6 +1011,10 @@ impl From<ErrorResponse> for Status {
             ErrorType::Overloaded => Code::ResourceExhausted,
             ErrorType::Validation => Code::InvalidArgument,
             ErrorType::Tokenizer => Code::FailedPrecondition,
+            ErrorType::Unauthorized => Code::Unauthenticated,
+            ErrorType::NotFound => Code::NotFound,
+            ErrorType::Unavailable => Code::Unavailable,
+            ErrorType::InvalidParameter => Code::InvalidArgument,
         };
 
         Status::new(code, value.error)
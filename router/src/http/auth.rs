@@ -0,0 +1,258 @@
+/// Validation of the API keys sent as bearer tokens
+use crate::{ErrorResponse, ErrorType};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Scope required by the `/admin` routes
+pub(crate) const ADMIN_SCOPE: &str = "admin";
+
+/// Client authenticated by its API key, attached to the request extensions
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct Principal {
+    /// Tenant the key was issued to
+    pub tenant: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Maximum number of inputs of a request, replacing `max_client_batch_size`
+    #[serde(default)]
+    pub max_batch_size: Option<usize>,
+}
+
+impl Principal {
+    pub(crate) fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// Validator of API keys, selected with `API_KEY`, `API_KEYS_FILE` or `AUTH_INTROSPECTION_URL`
+#[derive(Clone, Debug)]
+pub(crate) enum Authenticator {
+    /// A single key with the `admin` scope
+    Static(String),
+    /// Keys mapped to their principal
+    KeysFile(Arc<HashMap<String, Principal>>),
+    /// Keys validated by an OAuth 2.0 token introspection endpoint (RFC 7662)
+    Introspection(Arc<Introspection>),
+}
+
+impl Authenticator {
+    /// Read the JSON object mapping keys to their principal at `path`
+    pub(crate) fn keys_file(path: &str) -> Result<Self> {
+        let keys = fs::read_to_string(path)
+            .with_context(|| format!("Could not read `API_KEYS_FILE` file `{path}`"))?;
+        let keys = serde_json::from_str(&keys).with_context(|| {
+            format!(
+                "`API_KEYS_FILE` file `{path}` is not a JSON object mapping keys to \
+                `{{\"tenant\", \"scopes\", \"max_batch_size\"}}`"
+            )
+        })?;
+        Ok(Self::KeysFile(Arc::new(keys)))
+    }
+
+    /// Validate keys with the introspection endpoint at `url`, caching active keys for `ttl`
+    /// and inactive keys for at most [`INACTIVE_KEY_TTL`]
+    pub(crate) fn introspection(url: String, ttl: Duration) -> Self {
+        Self::Introspection(Arc::new(Introspection {
+            url,
+            ttl,
+            client: reqwest::Client::builder()
+                .timeout(INTROSPECTION_TIMEOUT)
+                .build()
+                .expect("Failed to build the introspection client. This is a bug."),
+            cache: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Get the principal of an API key, `None` if the key is not valid
+    pub(crate) async fn authenticate(&self, key: &str) -> Result<Option<Principal>, ErrorResponse> {
+        match self {
+            Self::Static(api_key) => Ok(constant_time_eq(key, api_key).then(|| Principal {
+                tenant: "default".to_string(),
+                scopes: vec![ADMIN_SCOPE.to_string()],
+                max_batch_size: None,
+            })),
+            Self::KeysFile(keys) => Ok(keys.get(key).cloned()),
+            Self::Introspection(introspection) => introspection.introspect(key).await,
+        }
+    }
+}
+
+/// Compare two keys in a time that does not depend on their common prefix
+///
+/// The SHA-256 digests of the keys are compared, so that the time does not depend on their
+/// lengths either.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter()
+        .zip(b.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// Maximum duration of a request to the introspection endpoint
+const INTROSPECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum duration inactive keys are cached, so that repeated invalid keys do not each
+/// call the introspection endpoint while newly issued keys are soon accepted
+const INACTIVE_KEY_TTL: Duration = Duration::from_secs(5);
+
+/// Maximum number of keys in the introspection cache
+const INTROSPECTION_CACHE_SIZE: usize = 10_000;
+
+#[derive(Debug)]
+pub(crate) struct Introspection {
+    url: String,
+    ttl: Duration,
+    client: reqwest::Client,
+    /// Principals of the keys, `None` if inactive, with the time they were introspected
+    cache: Mutex<HashMap<String, (Option<Principal>, Instant)>>,
+}
+
+/// Response of an introspection endpoint
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    /// Tenant of the key
+    sub: Option<String>,
+    /// Space separated scopes
+    scope: Option<String>,
+    max_batch_size: Option<usize>,
+}
+
+impl Introspection {
+    async fn introspect(&self, key: &str) -> Result<Option<Principal>, ErrorResponse> {
+        if let Some(principal) = self.cached(key) {
+            return Ok(principal);
+        }
+
+        let response = self
+            .client
+            .post(&self.url)
+            .form(&[("token", key)])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(introspection_failed)?;
+        let body = response.bytes().await.map_err(introspection_failed)?;
+        let response: IntrospectionResponse =
+            serde_json::from_slice(&body).map_err(introspection_failed)?;
+
+        let principal = response.active.then(|| Principal {
+            tenant: response.sub.unwrap_or_default(),
+            scopes: response
+                .scope
+                .map(|scope| scope.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+            max_batch_size: response.max_batch_size,
+        });
+        self.cache(key, principal.clone());
+        Ok(principal)
+    }
+
+    /// Get the principal of a key introspected less than its TTL ago
+    fn cached(&self, key: &str) -> Option<Option<Principal>> {
+        let cache = self.cache.lock().unwrap();
+        let (principal, introspected) = cache.get(key)?;
+        let ttl = match principal {
+            Some(_) => self.ttl,
+            None => self.ttl.min(INACTIVE_KEY_TTL),
+        };
+        (introspected.elapsed() < ttl).then(|| principal.clone())
+    }
+
+    /// Cache the principal of a key, evicting the expired keys or else the oldest one when full
+    fn cache(&self, key: &str, principal: Option<Principal>) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= INTROSPECTION_CACHE_SIZE && !cache.contains_key(key) {
+            cache.retain(|_, (_, introspected)| introspected.elapsed() < self.ttl);
+            if cache.len() >= INTROSPECTION_CACHE_SIZE {
+                let oldest = cache
+                    .iter()
+                    .min_by_key(|(_, (_, introspected))| *introspected)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    cache.remove(&oldest);
+                }
+            }
+        }
+        cache.insert(key.to_string(), (principal, Instant::now()));
+    }
+}
+
+fn introspection_failed(err: impl std::fmt::Display) -> ErrorResponse {
+    tracing::error!("API key introspection failed: {err}");
+    metrics::increment_counter!("te_request_failure", "err" => "auth_unavailable");
+    ErrorResponse {
+        error: "Could not validate the API key".to_string(),
+        error_type: ErrorType::Unavailable,
+        code: "auth_unavailable".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn introspection() -> Arc<Introspection> {
+        match Authenticator::introspection(
+            "http://127.0.0.1:1".to_string(),
+            Duration::from_secs(3600),
+        ) {
+            Authenticator::Introspection(introspection) => introspection,
+            _ => unreachable!(),
+        }
+    }
+
+    fn principal() -> Principal {
+        Principal {
+            tenant: "acme".to_string(),
+            scopes: Vec::new(),
+            max_batch_size: None,
+        }
+    }
+
+    #[test]
+    fn constant_time_eq_compares_keys() {
+        assert!(constant_time_eq("key", "key"));
+        assert!(!constant_time_eq("key", "kez"));
+        assert!(!constant_time_eq("key", "key2"));
+        assert!(!constant_time_eq("", "key"));
+    }
+
+    #[test]
+    fn inactive_keys_are_cached_for_a_shorter_ttl() {
+        let introspection = introspection();
+        introspection.cache("active", Some(principal()));
+        introspection.cache("inactive", None);
+        assert_eq!(introspection.cached("active"), Some(Some(principal())));
+        assert_eq!(introspection.cached("inactive"), Some(None));
+        assert_eq!(introspection.cached("unknown"), None);
+
+        let introspected = Instant::now() - INACTIVE_KEY_TTL;
+        for (_, entry) in introspection.cache.lock().unwrap().iter_mut() {
+            entry.1 = introspected;
+        }
+        assert_eq!(introspection.cached("active"), Some(Some(principal())));
+        assert_eq!(introspection.cached("inactive"), None);
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let introspection = introspection();
+        for i in 0..INTROSPECTION_CACHE_SIZE + 10 {
+            introspection.cache(&i.to_string(), None);
+        }
+        assert_eq!(
+            introspection.cache.lock().unwrap().len(),
+            INTROSPECTION_CACHE_SIZE
+        );
+        // The last key is kept
+        let last = (INTROSPECTION_CACHE_SIZE + 9).to_string();
+        assert_eq!(introspection.cached(&last), Some(None));
+    }
+}
@@ -0,0 +1,576 @@
+/// Optional HTTP server settings read from the environment
+use crate::http::auth::Authenticator;
+use crate::http::types::WeaviateTask;
+use crate::Info;
+use anyhow::{Context, Result};
+use axum::http::HeaderValue;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub(crate) struct ServerConfig {
+    /// Maximum estimated size in bytes of an embeddings response body
+    pub max_response_bytes: Option<usize>,
+    /// Path under which all routes are served, e.g. `/embeddings-service`
+    pub route_prefix: Option<String>,
+    /// Also serve `/health` at the root when a route prefix is set
+    pub unprefixed_health: bool,
+    /// Validator of the API keys sent as bearer tokens. The `/admin` routes are disabled if unset
+    pub authenticator: Option<Authenticator>,
+    /// Reject inference requests without a valid API key
+    pub require_auth: bool,
+    /// Maximum number of inputs of an asynchronous job
+    pub max_job_inputs: usize,
+    /// How long finished jobs are kept before being evicted
+    pub job_ttl: Duration,
+    /// Maximum number of jobs kept, pending or finished, rejected with a 429 above it
+    pub max_jobs: usize,
+    /// Proxies allowed to set the client IP with `X-Forwarded-For`
+    pub trusted_proxies: Vec<IpNet>,
+    /// Origins allowed by CORS. All origins are allowed if unset
+    pub cors_allow_origin: Option<Vec<HeaderValue>>,
+    /// Version of the Weaviate t2v-transformers contract served on `/vectors`
+    pub weaviate_protocol: WeaviateProtocol,
+    /// Wrap the `/meta` response in the shape probed by Weaviate
+    pub weaviate_meta: bool,
+    /// Task of `/vectors` requests that do not set one
+    pub weaviate_default_task: Option<WeaviateTask>,
+    /// Prefix prepended to the text of `query` requests on `/vectors`
+    pub weaviate_query_prefix: Option<String>,
+    /// Prefix prepended to the text of `passage` requests on `/vectors`
+    pub weaviate_passage_prefix: Option<String>,
+    /// Send `ETag` and `Cache-Control` headers on embedding routes and answer `If-None-Match`
+    pub http_cache: bool,
+    /// `max-age` of the `Cache-Control` header
+    pub http_cache_max_age: Duration,
+    /// Model id or local path loaded by `/admin/reload` when the request does not set one
+    pub reload_model_path: Option<String>,
+    /// Time after which batch requests return the inputs that completed
+    pub request_timeout: Option<Duration>,
+    /// Maximum time the inputs of a request wait for a concurrency permit before a 429 error
+    pub permit_wait_timeout: Option<Duration>,
+    /// Maximum number of concurrent requests per endpoint path, rejected with a 503 above it
+    pub endpoint_concurrency: Vec<(String, usize)>,
+    /// Maximum number of batch requests in flight, rejected with a 429 above it
+    pub max_inflight_batches: Option<usize>,
+    /// Named instructions selected with `instruction_key` on `/embed`
+    pub instructions: HashMap<String, String>,
+    /// Group of each classifier label, aggregated with `aggregate_groups` on `/predict`
+    pub label_groups: HashMap<String, String>,
+    /// Aggregation of the scores of the labels of a group
+    pub label_group_aggregation: GroupAggregation,
+    /// Labeled embeddings searched by `/classify_by_similarity`. The route is disabled if unset
+    pub reference_set: Option<Arc<Vec<Reference>>>,
+    /// Queue the pairs of a `/rerank` request together instead of as concurrent single requests
+    pub rerank_batching: bool,
+    /// Decode Weaviate request bodies from the charset of their `Content-Type` instead of UTF-8
+    pub transcode_charset: bool,
+    /// Deserialize Weaviate request bodies as they are received instead of buffering them first,
+    /// so that parsing overlaps with the upload. Bodies decoded with `TRANSCODE_CHARSET` are still
+    /// buffered
+    pub stream_parse: bool,
+    /// Model name returned by OpenAI routes when the request does not set one
+    pub public_model_name: Option<String>,
+    /// Models loaded next to the default one, selected with the `X-Model-Id` header or the
+    /// `model` field of OpenAI requests
+    pub additional_model_ids: Vec<String>,
+    /// Serve `/health/detail` and export device stats as Prometheus gauges
+    pub report_device_stats: bool,
+    /// Reject requests with an empty batch of inputs instead of returning an empty result
+    pub reject_empty_batch: bool,
+    /// Start the model backend on the first inference request instead of at startup
+    pub lazy_load: bool,
+    /// Report ready before the backend of a lazily loaded model is started
+    pub lazy_ready_on_demand: bool,
+    /// Bearer tokens of the clients allowed to send batches of up to `trusted_max_batch` inputs
+    pub trusted_api_keys: Vec<String>,
+    /// Maximum number of inputs of a request from a trusted client
+    pub trusted_max_batch: Option<usize>,
+    /// Time during which `/ready` returns 503 after a shutdown signal before the server stops
+    /// accepting requests
+    pub shutdown_grace_period: Duration,
+    /// Set the `X-Truncated`, `X-Input-Tokens` and `X-Used-Tokens` headers on `/embed`,
+    /// `/predict` and `/vectors` responses
+    pub report_truncation: bool,
+    /// Route paths with trailing slashes as the paths without them, e.g. `/embed/` as `/embed`
+    pub trim_trailing_slash: bool,
+    /// Set the `X-Backend-Device` and `X-Batch-Composition` headers on responses. They expose
+    /// internal scheduling details and are only meant for debugging
+    pub debug_headers: bool,
+    /// Allow diagnostic request options such as `return_raw_shape`, meant for model onboarding
+    pub debug_mode: bool,
+    /// Tenants whose requests are labeled with their tenant in the request metrics. The requests
+    /// of the other tenants and of anonymous clients are labeled `other`
+    pub tenant_metrics: Option<Vec<String>>,
+    /// Response of `GET /`
+    pub root_response: RootResponse,
+    /// HTTP versions accepted by the server
+    pub http_version: HttpVersion,
+    /// Maximum number of open connections. Connections above it are closed as soon as they are
+    /// accepted
+    pub max_connections: Option<usize>,
+    /// Time without any request in flight nor any byte read or written after which a connection
+    /// counted against `max_connections` is closed, so that idle keep-alive connections free
+    /// their slot
+    pub connection_idle_timeout: Duration,
+}
+
+/// Response of `GET /`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum RootResponse {
+    /// Same as `/health`
+    #[default]
+    Health,
+    /// JSON document describing the service and its routes
+    Info,
+}
+
+/// HTTP versions accepted by the server, set with `HTTP_VERSION`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum HttpVersion {
+    /// HTTP/1 and HTTP/2, selected per connection
+    #[default]
+    Auto,
+    /// Only HTTP/1
+    Http1,
+    /// Only HTTP/2, without TLS so clients must use prior knowledge
+    Http2,
+}
+
+/// Labeled embedding of the reference set, normalized when it is loaded
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Reference {
+    pub label: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Maximum number of embeddings of the reference set, which is searched exhaustively
+const MAX_REFERENCE_SET_SIZE: usize = 10_000;
+
+/// Aggregation of the label scores of a group, set with `LABEL_GROUP_AGGREGATION`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum GroupAggregation {
+    #[default]
+    Sum,
+    Max,
+    Mean,
+}
+
+/// Supported versions of the Weaviate t2v-transformers inference contract
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum WeaviateProtocol {
+    /// `{"text"}` requests and `{"error": "..."}` errors
+    V1,
+    /// Requests may carry a `config` object and errors are `{"error": [{"message": "..."}]}`
+    #[default]
+    V2,
+}
+
+impl ServerConfig {
+    /// Read the settings from the environment
+    ///
+    /// Every setting is checked before failing so that all invalid values are reported at once.
+    pub(crate) fn from_env() -> Result<Self> {
+        let mut errors = Vec::new();
+        let config = Self {
+            max_response_bytes: check(&mut errors, parse_env("MAX_RESPONSE_BYTES")),
+            route_prefix: env::var("ROUTE_PREFIX").ok().and_then(|prefix| {
+                // Normalize to a leading slash and no trailing slash
+                let prefix = prefix.trim_matches('/');
+                (!prefix.is_empty()).then(|| format!("/{prefix}"))
+            }),
+            unprefixed_health: check(&mut errors, parse_env("UNPREFIXED_HEALTH")).unwrap_or(false),
+            authenticator: check(&mut errors, parse_authenticator()),
+            require_auth: check(&mut errors, parse_env("REQUIRE_AUTH")).unwrap_or(false),
+            max_job_inputs: check(&mut errors, parse_env("MAX_JOB_INPUTS")).unwrap_or(100_000),
+            job_ttl: check(&mut errors, parse_secs("JOB_TTL_SECS"))
+                .unwrap_or(Duration::from_secs(3600)),
+            max_jobs: check(&mut errors, parse_env("MAX_JOBS")).unwrap_or(1000),
+            trusted_proxies: check(&mut errors, parse_trusted_proxies()),
+            cors_allow_origin: check(&mut errors, parse_cors_allow_origin()),
+            weaviate_protocol: check(&mut errors, parse_weaviate_protocol()),
+            weaviate_meta: check(&mut errors, parse_env("WEAVIATE_META")).unwrap_or(false),
+            weaviate_default_task: check(&mut errors, parse_weaviate_default_task()),
+            weaviate_query_prefix: env::var("WEAVIATE_QUERY_PREFIX").ok(),
+            weaviate_passage_prefix: env::var("WEAVIATE_PASSAGE_PREFIX").ok(),
+            http_cache: check(&mut errors, parse_env("ENABLE_HTTP_CACHE")).unwrap_or(false),
+            http_cache_max_age: check(&mut errors, parse_secs("HTTP_CACHE_MAX_AGE_SECS"))
+                .unwrap_or(Duration::from_secs(3600)),
+            reload_model_path: env::var("RELOAD_MODEL_PATH").ok(),
+            request_timeout: check(&mut errors, parse_secs("REQUEST_TIMEOUT_SECS")),
+            permit_wait_timeout: check(&mut errors, parse_env("PERMIT_WAIT_TIMEOUT_MS"))
+                .map(Duration::from_millis),
+            endpoint_concurrency: check(&mut errors, parse_endpoint_concurrency()),
+            max_inflight_batches: check(&mut errors, parse_env("MAX_INFLIGHT_BATCHES")),
+            instructions: check(&mut errors, parse_instructions()),
+            label_groups: check(&mut errors, parse_label_groups()),
+            label_group_aggregation: check(&mut errors, parse_group_aggregation()),
+            reference_set: check(&mut errors, parse_reference_set()),
+            rerank_batching: check(&mut errors, parse_env("RERANK_BATCHING")).unwrap_or(false),
+            transcode_charset: check(&mut errors, parse_env("TRANSCODE_CHARSET")).unwrap_or(false),
+            stream_parse: check(&mut errors, parse_env("STREAM_PARSE")).unwrap_or(false),
+            public_model_name: env::var("PUBLIC_MODEL_NAME")
+                .ok()
+                .filter(|name| !name.is_empty()),
+            additional_model_ids: env::var("ADDITIONAL_MODEL_IDS")
+                .map(|ids| {
+                    ids.split(',')
+                        .map(str::trim)
+                        .filter(|id| !id.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            report_device_stats: check(&mut errors, parse_env("REPORT_DEVICE_STATS"))
+                .unwrap_or(false),
+            reject_empty_batch: check(&mut errors, parse_env("REJECT_EMPTY_BATCH"))
+                .unwrap_or(false),
+            lazy_load: check(&mut errors, parse_env("LAZY_LOAD")).unwrap_or(false),
+            lazy_ready_on_demand: check(&mut errors, parse_env("LAZY_READY_ON_DEMAND"))
+                .unwrap_or(false),
+            trusted_api_keys: env::var("TRUSTED_API_KEYS")
+                .map(|keys| {
+                    keys.split(',')
+                        .map(str::trim)
+                        .filter(|key| !key.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            trusted_max_batch: check(&mut errors, parse_env("TRUSTED_MAX_BATCH")),
+            shutdown_grace_period: check(&mut errors, parse_secs("SHUTDOWN_GRACE_PERIOD_SECS"))
+                .unwrap_or(Duration::ZERO),
+            report_truncation: check(&mut errors, parse_env("REPORT_TRUNCATION")).unwrap_or(false),
+            trim_trailing_slash: check(&mut errors, parse_env("TRIM_TRAILING_SLASH"))
+                .unwrap_or(true),
+            debug_headers: check(&mut errors, parse_env("DEBUG_HEADERS")).unwrap_or(false),
+            debug_mode: check(&mut errors, parse_env("DEBUG_MODE")).unwrap_or(false),
+            tenant_metrics: env::var("TENANT_METRICS").ok().map(|tenants| {
+                tenants
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tenant| !tenant.is_empty())
+                    .map(String::from)
+                    .collect()
+            }),
+            root_response: check(&mut errors, parse_root_response()),
+            http_version: check(&mut errors, parse_http_version()),
+            max_connections: check(&mut errors, parse_env("MAX_CONNECTIONS")),
+            connection_idle_timeout: check(&mut errors, parse_secs("CONNECTION_IDLE_TIMEOUT_SECS"))
+                .unwrap_or(Duration::from_secs(60)),
+        };
+        if config.max_inflight_batches == Some(0) {
+            errors.push("`MAX_INFLIGHT_BATCHES` must be at least 1".to_string());
+        }
+        if config.max_jobs == 0 {
+            errors.push("`MAX_JOBS` must be at least 1".to_string());
+        }
+        if config.max_connections == Some(0) {
+            errors.push("`MAX_CONNECTIONS` must be at least 1".to_string());
+        }
+        if config.connection_idle_timeout.is_zero() {
+            errors.push("`CONNECTION_IDLE_TIMEOUT_SECS` must be positive".to_string());
+        }
+        if !config.trusted_api_keys.is_empty() && config.trusted_max_batch.is_none() {
+            errors.push("`TRUSTED_API_KEYS` requires `TRUSTED_MAX_BATCH`".to_string());
+        }
+        if config.require_auth && config.authenticator.is_none() {
+            errors.push(
+                "`REQUIRE_AUTH` requires `API_KEY`, `API_KEYS_FILE` or `AUTH_INTROSPECTION_URL`"
+                    .to_string(),
+            );
+        }
+        if config.tenant_metrics.is_some() && config.authenticator.is_none() {
+            errors.push(
+                "`TENANT_METRICS` requires `API_KEY`, `API_KEYS_FILE` or `AUTH_INTROSPECTION_URL`"
+                    .to_string(),
+            );
+        }
+
+        if !errors.is_empty() {
+            anyhow::bail!("Invalid configuration:\n  - {}", errors.join("\n  - "));
+        }
+        Ok(config)
+    }
+
+    /// Prefix to prepend to the text of a `/vectors` request
+    pub(crate) fn weaviate_prefix(&self, task: Option<WeaviateTask>) -> Option<&str> {
+        match task.or(self.weaviate_default_task)? {
+            WeaviateTask::Query => self.weaviate_query_prefix.as_deref(),
+            WeaviateTask::Passage => self.weaviate_passage_prefix.as_deref(),
+        }
+    }
+
+    /// Check that the reference set has the dimension of the embeddings of the model
+    pub(crate) fn check_reference_set(&self, info: &Info) -> Result<()> {
+        let references = match &self.reference_set {
+            Some(references) => references,
+            None => return Ok(()),
+        };
+        let dim = references[0].embedding.len();
+        match info.embedding_dim {
+            Some(embedding_dim) if embedding_dim == dim => Ok(()),
+            Some(embedding_dim) => anyhow::bail!(
+                "`REFERENCE_SET_PATH` embeddings have dimension {dim}, but model `{}` embeddings \
+                have dimension {embedding_dim}",
+                info.model_id
+            ),
+            None => anyhow::bail!(
+                "`REFERENCE_SET_PATH` requires an embedding model, but model `{}` is a classifier",
+                info.model_id
+            ),
+        }
+    }
+}
+
+/// Parse `WEAVIATE_PROTOCOL_VERSION`
+fn parse_weaviate_protocol() -> Result<WeaviateProtocol> {
+    match env::var("WEAVIATE_PROTOCOL_VERSION").ok().as_deref() {
+        Some("1") => Ok(WeaviateProtocol::V1),
+        Some("2") | None => Ok(WeaviateProtocol::V2),
+        Some(version) => anyhow::bail!(
+            "Invalid value `{version}` for `WEAVIATE_PROTOCOL_VERSION`, expected `1` or `2`"
+        ),
+    }
+}
+
+/// Parse `ROOT_RESPONSE`
+fn parse_root_response() -> Result<RootResponse> {
+    match env::var("ROOT_RESPONSE").ok().as_deref() {
+        Some("health") | None => Ok(RootResponse::Health),
+        Some("info") => Ok(RootResponse::Info),
+        Some(response) => anyhow::bail!(
+            "Invalid value `{response}` for `ROOT_RESPONSE`, expected `health` or `info`"
+        ),
+    }
+}
+
+/// Parse `HTTP_VERSION`
+fn parse_http_version() -> Result<HttpVersion> {
+    match env::var("HTTP_VERSION").ok().as_deref() {
+        Some("auto") | None => Ok(HttpVersion::Auto),
+        Some("http1") => Ok(HttpVersion::Http1),
+        Some("http2") => Ok(HttpVersion::Http2),
+        Some(version) => anyhow::bail!(
+            "Invalid value `{version}` for `HTTP_VERSION`, expected `auto`, `http1` or `http2`"
+        ),
+    }
+}
+
+/// Parse `WEAVIATE_DEFAULT_TASK`
+fn parse_weaviate_default_task() -> Result<Option<WeaviateTask>> {
+    match env::var("WEAVIATE_DEFAULT_TASK").ok().as_deref() {
+        Some("query") => Ok(Some(WeaviateTask::Query)),
+        Some("passage") => Ok(Some(WeaviateTask::Passage)),
+        None => Ok(None),
+        Some(task) => anyhow::bail!(
+            "Invalid value `{task}` for `WEAVIATE_DEFAULT_TASK`, expected `query` or `passage`"
+        ),
+    }
+}
+
+/// Select the validator of API keys from `API_KEY`, `API_KEYS_FILE` or `AUTH_INTROSPECTION_URL`
+fn parse_authenticator() -> Result<Option<Authenticator>> {
+    let api_key = env::var("API_KEY").ok().filter(|key| !key.is_empty());
+    let keys_file = env::var("API_KEYS_FILE").ok();
+    let introspection_url = env::var("AUTH_INTROSPECTION_URL").ok();
+    match (api_key, keys_file, introspection_url) {
+        (None, None, None) => Ok(None),
+        (Some(api_key), None, None) => Ok(Some(Authenticator::Static(api_key))),
+        (None, Some(path), None) => Authenticator::keys_file(&path).map(Some),
+        (None, None, Some(url)) => {
+            let ttl = parse_secs("AUTH_INTROSPECTION_TTL_SECS")?.unwrap_or(Duration::from_secs(60));
+            Ok(Some(Authenticator::introspection(url, ttl)))
+        }
+        _ => anyhow::bail!(
+            "Only one of `API_KEY`, `API_KEYS_FILE` and `AUTH_INTROSPECTION_URL` can be set"
+        ),
+    }
+}
+
+/// Parse the comma separated origins of `CORS_ALLOW_ORIGIN`
+fn parse_cors_allow_origin() -> Result<Option<Vec<HeaderValue>>> {
+    let origins = match env::var("CORS_ALLOW_ORIGIN") {
+        Ok(origins) => origins,
+        Err(_) => return Ok(None),
+    };
+    origins
+        .split(',')
+        .map(|origin| {
+            origin
+                .parse::<HeaderValue>()
+                .with_context(|| format!("Invalid origin `{origin}` in `CORS_ALLOW_ORIGIN`"))
+        })
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+/// Read the JSON object of named instructions at `INSTRUCTIONS_PATH`
+fn parse_instructions() -> Result<HashMap<String, String>> {
+    let path = match env::var("INSTRUCTIONS_PATH") {
+        Ok(path) => path,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let instructions = fs::read_to_string(&path)
+        .with_context(|| format!("Could not read `INSTRUCTIONS_PATH` file `{path}`"))?;
+    serde_json::from_str(&instructions).with_context(|| {
+        format!("`INSTRUCTIONS_PATH` file `{path}` is not a JSON object of strings")
+    })
+}
+
+/// Read the JSON object mapping classifier labels to their group at `LABEL_GROUPS_PATH`
+fn parse_label_groups() -> Result<HashMap<String, String>> {
+    let path = match env::var("LABEL_GROUPS_PATH") {
+        Ok(path) => path,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let groups = fs::read_to_string(&path)
+        .with_context(|| format!("Could not read `LABEL_GROUPS_PATH` file `{path}`"))?;
+    serde_json::from_str(&groups).with_context(|| {
+        format!("`LABEL_GROUPS_PATH` file `{path}` is not a JSON object mapping labels to groups")
+    })
+}
+
+/// Read the JSON array of `{"label", "embedding"}` objects at `REFERENCE_SET_PATH`
+fn parse_reference_set() -> Result<Option<Arc<Vec<Reference>>>> {
+    let path = match env::var("REFERENCE_SET_PATH") {
+        Ok(path) => path,
+        Err(_) => return Ok(None),
+    };
+    let references = fs::read_to_string(&path)
+        .with_context(|| format!("Could not read `REFERENCE_SET_PATH` file `{path}`"))?;
+    let mut references: Vec<Reference> = serde_json::from_str(&references).with_context(|| {
+        format!(
+            "`REFERENCE_SET_PATH` file `{path}` is not a JSON array of \
+            `{{\"label\", \"embedding\"}}` objects"
+        )
+    })?;
+
+    let dim = match references.first() {
+        Some(reference) => reference.embedding.len(),
+        None => anyhow::bail!("`REFERENCE_SET_PATH` file `{path}` is empty"),
+    };
+    if references.len() > MAX_REFERENCE_SET_SIZE {
+        anyhow::bail!(
+            "`REFERENCE_SET_PATH` file `{path}` has {} embeddings, the maximum is \
+            {MAX_REFERENCE_SET_SIZE}",
+            references.len()
+        );
+    }
+    for (i, reference) in references.iter_mut().enumerate() {
+        if reference.embedding.len() != dim {
+            anyhow::bail!(
+                "Embedding {i} of `REFERENCE_SET_PATH` file `{path}` has dimension {}, expected \
+                {dim}",
+                reference.embedding.len()
+            );
+        }
+        let norm = reference
+            .embedding
+            .iter()
+            .map(|v| v * v)
+            .sum::<f32>()
+            .sqrt();
+        if !norm.is_normal() {
+            anyhow::bail!(
+                "Embedding {i} of `REFERENCE_SET_PATH` file `{path}` has a zero or non-finite norm"
+            );
+        }
+        // Scores are then dot products with the normalized input embeddings
+        reference.embedding.iter_mut().for_each(|v| *v /= norm);
+    }
+    Ok(Some(Arc::new(references)))
+}
+
+/// Parse `LABEL_GROUP_AGGREGATION`
+fn parse_group_aggregation() -> Result<GroupAggregation> {
+    match env::var("LABEL_GROUP_AGGREGATION").ok().as_deref() {
+        Some("sum") | None => Ok(GroupAggregation::Sum),
+        Some("max") => Ok(GroupAggregation::Max),
+        Some("mean") => Ok(GroupAggregation::Mean),
+        Some(aggregation) => anyhow::bail!(
+            "Invalid value `{aggregation}` for `LABEL_GROUP_AGGREGATION`, expected `sum`, `max` \
+            or `mean`"
+        ),
+    }
+}
+
+/// Parse the comma separated IPs and CIDR ranges of `TRUSTED_PROXIES`
+fn parse_trusted_proxies() -> Result<Vec<IpNet>> {
+    let proxies = match env::var("TRUSTED_PROXIES") {
+        Ok(proxies) => proxies,
+        Err(_) => return Ok(Vec::new()),
+    };
+    proxies
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse::<IpNet>()
+                .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                .with_context(|| format!("Invalid value `{proxy}` for `TRUSTED_PROXIES`"))
+        })
+        .collect()
+}
+
+/// Parse the comma separated `path=limit` pairs of `ENDPOINT_MAX_CONCURRENCY`
+fn parse_endpoint_concurrency() -> Result<Vec<(String, usize)>> {
+    let limits = match env::var("ENDPOINT_MAX_CONCURRENCY") {
+        Ok(limits) => limits,
+        Err(_) => return Ok(Vec::new()),
+    };
+    limits
+        .split(',')
+        .map(str::trim)
+        .filter(|limit| !limit.is_empty())
+        .map(|limit| {
+            let invalid = || format!("Invalid value `{limit}` for `ENDPOINT_MAX_CONCURRENCY`");
+            let (path, max) = limit.split_once('=').with_context(invalid)?;
+            let max = max.trim().parse::<usize>().with_context(invalid)?;
+            let path = format!("/{}", path.trim().trim_start_matches('/'));
+            Ok((path, max))
+        })
+        .collect()
+}
+
+/// Parse an optional environment variable holding a number of seconds
+fn parse_secs(name: &str) -> Result<Option<Duration>> {
+    parse_env::<f64>(name)?
+        .map(|secs| {
+            Duration::try_from_secs_f64(secs)
+                .with_context(|| format!("Invalid value `{secs}` for `{name}`, expected seconds"))
+        })
+        .transpose()
+}
+
+/// Record the error of a setting and fall back to its default value
+fn check<T: Default>(errors: &mut Vec<String>, result: Result<T>) -> T {
+    result.unwrap_or_else(|err| {
+        errors.push(format!("{err:#}"));
+        T::default()
+    })
+}
+
+/// Parse an optional environment variable
+fn parse_env<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    env::var(name)
+        .ok()
+        .map(|value| {
+            value
+                .parse::<T>()
+                .with_context(|| format!("Invalid value `{value}` for `{name}`"))
+        })
+        .transpose()
+}
@@ -0,0 +1,271 @@
+/// Limit of the number of connections open at the same time, set with `MAX_CONNECTIONS`
+use axum::extract::connect_info::Connected;
+use hyper::server::accept::Accept;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, Sleep};
+
+/// Listener closing the connections accepted above the limit, so that they do not use file
+/// descriptors while the open connections keep being served
+pub(crate) struct LimitedIncoming {
+    incoming: AddrIncoming,
+    /// One permit per connection that can be opened. Unlimited if unset
+    permits: Option<Arc<Semaphore>>,
+    /// Time without any request in flight nor any byte read or written after which a limited
+    /// connection is closed, so that idle keep-alive connections do not hold their permit
+    idle_timeout: Duration,
+}
+
+impl LimitedIncoming {
+    pub(crate) fn new(
+        incoming: AddrIncoming,
+        max_connections: Option<usize>,
+        idle_timeout: Duration,
+    ) -> Self {
+        Self {
+            incoming,
+            permits: max_connections.map(|max| Arc::new(Semaphore::new(max))),
+            idle_timeout,
+        }
+    }
+}
+
+impl Accept for LimitedIncoming {
+    type Conn = LimitedStream;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        loop {
+            let stream = match ready!(Pin::new(&mut self.incoming).poll_accept(cx)) {
+                Some(Ok(stream)) => stream,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            };
+            let permit = match &self.permits {
+                None => None,
+                Some(permits) => match permits.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_) => {
+                        // Dropping the stream closes the connection
+                        tracing::warn!(
+                            "Refused connection from {}: too many open connections",
+                            stream.remote_addr()
+                        );
+                        metrics::increment_counter!("te_connection_refused");
+                        continue;
+                    }
+                },
+            };
+            // Unlimited connections do not hold a permit and are never closed for being idle
+            let idle = permit.as_ref().map(|_| IdleTimer::new(self.idle_timeout));
+            return Poll::Ready(Some(Ok(LimitedStream {
+                stream,
+                _permit: permit,
+                idle,
+                in_flight: Arc::new(AtomicUsize::new(0)),
+            })));
+        }
+    }
+}
+
+/// Accepted connection, releasing its permit when closed
+pub(crate) struct LimitedStream {
+    stream: AddrStream,
+    _permit: Option<OwnedSemaphorePermit>,
+    idle: Option<IdleTimer>,
+    /// Number of requests of the connection being served, counted by [`Connection::start`]
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Connection info of a request: the address of the client and the requests in flight on the
+/// connection, which is not idle until they are all served
+#[derive(Clone, Debug)]
+pub(crate) struct Connection {
+    pub(crate) remote_addr: SocketAddr,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Connection {
+    /// Count a request as in flight until the returned guard is dropped
+    pub(crate) fn start(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(self.in_flight.clone())
+    }
+}
+
+/// Request in flight on a connection
+pub(crate) struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Deadline of a connection, pushed back every time a byte is read or written
+struct IdleTimer {
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl IdleTimer {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+        }
+    }
+
+    fn reset(&mut self) {
+        let deadline = Instant::now() + self.timeout;
+        self.deadline.as_mut().reset(deadline);
+    }
+
+    /// Whether the deadline is reached, registering the task to be woken up when it is not
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> bool {
+        self.deadline.as_mut().poll(cx).is_ready()
+    }
+}
+
+impl LimitedStream {
+    /// Push back the idle deadline after some bytes are written
+    fn written(&mut self, poll: &Poll<io::Result<usize>>) {
+        if let (Some(idle), Poll::Ready(Ok(1..))) = (&mut self.idle, poll) {
+            idle.reset();
+        }
+    }
+}
+
+impl Connected<&LimitedStream> for Connection {
+    fn connect_info(target: &LimitedStream) -> Self {
+        Self {
+            remote_addr: target.stream.remote_addr(),
+            in_flight: target.in_flight.clone(),
+        }
+    }
+}
+
+impl AsyncRead for LimitedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut this.stream).poll_read(cx, buf);
+        if let Some(idle) = &mut this.idle {
+            match poll {
+                Poll::Ready(Ok(())) if buf.filled().len() > filled => idle.reset(),
+                Poll::Pending if idle.poll_expired(cx) => {
+                    // Requests being served, possibly without reading or writing for a while,
+                    // keep their connection open: check again after another timeout
+                    if this.in_flight.load(Ordering::Relaxed) > 0 {
+                        idle.reset();
+                        let _ = idle.poll_expired(cx);
+                        return Poll::Pending;
+                    }
+                    tracing::debug!(
+                        "Closing connection from {}: idle for {:?}",
+                        this.stream.remote_addr(),
+                        idle.timeout
+                    );
+                    return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+                }
+                _ => {}
+            }
+        }
+        poll
+    }
+}
+
+impl AsyncWrite for LimitedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.stream).poll_write(cx, buf);
+        self.written(&poll);
+        poll
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.stream).poll_write_vectored(cx, bufs);
+        self.written(&poll);
+        poll
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+
+    /// Wait for a byte from the client or for the connection to be closed, for at most `timeout`
+    async fn read(stream: &mut LimitedStream, timeout: Duration) -> Option<io::Result<()>> {
+        let mut buf = [0; 1];
+        let read = poll_fn(|cx| Pin::new(&mut *stream).poll_read(cx, &mut ReadBuf::new(&mut buf)));
+        tokio::time::timeout(timeout, read).await.ok()
+    }
+
+    #[test]
+    fn requests_in_flight_keep_their_connection_open() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let incoming = AddrIncoming::bind(&([127, 0, 0, 1], 0).into()).unwrap();
+            let addr = incoming.local_addr();
+            let mut incoming = LimitedIncoming::new(incoming, Some(1), Duration::from_millis(100));
+            let _client = std::net::TcpStream::connect(addr).unwrap();
+            let mut stream = poll_fn(|cx| Pin::new(&mut incoming).poll_accept(cx))
+                .await
+                .unwrap()
+                .unwrap();
+
+            // A request served for longer than the idle timeout keeps its connection open
+            let in_flight = Connection::connect_info(&stream).start();
+            assert!(read(&mut stream, Duration::from_millis(300))
+                .await
+                .is_none());
+
+            // Once it is served, the connection is closed for being idle
+            drop(in_flight);
+            let err = read(&mut stream, Duration::from_millis(300))
+                .await
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        });
+    }
+}
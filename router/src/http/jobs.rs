@@ -0,0 +1,174 @@
+/// In-memory store of asynchronous embedding jobs
+use crate::http::types::{JobResponse, JobStatus};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub(crate) struct Job {
+    pub status: JobStatus,
+    pub total: usize,
+    pub embeddings: Vec<Vec<f32>>,
+    pub error: Option<String>,
+    /// Set once the job is completed or failed
+    finished_at: Option<Instant>,
+}
+
+impl Job {
+    pub(crate) fn complete(&mut self) {
+        self.status = JobStatus::Completed;
+        self.finished_at = Some(Instant::now());
+    }
+
+    pub(crate) fn fail(&mut self, error: String) {
+        self.status = JobStatus::Failed;
+        self.error = Some(error);
+        // Partial results are useless to the client
+        self.embeddings = Vec::new();
+        self.finished_at = Some(Instant::now());
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.finished_at
+            .is_some_and(|finished_at| finished_at.elapsed() >= ttl)
+    }
+}
+
+/// Interval at which expired jobs are evicted
+const EVICTION_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub(crate) struct JobStore {
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+    /// How long finished jobs are kept
+    ttl: Duration,
+    /// Maximum number of jobs kept, pending or finished
+    max_jobs: usize,
+}
+
+impl JobStore {
+    pub(crate) fn new(ttl: Duration, max_jobs: usize) -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            max_jobs,
+        }
+    }
+
+    /// Register a new pending job and return its id
+    ///
+    /// Returns `None` if the store is full of pending or unexpired jobs.
+    pub(crate) fn create(&self, total: usize) -> Option<String> {
+        let id = Uuid::new_v4().to_string();
+        let mut jobs = self.jobs.lock().unwrap();
+
+        if jobs.len() >= self.max_jobs {
+            jobs.retain(|_, job| !job.is_expired(self.ttl));
+            if jobs.len() >= self.max_jobs {
+                return None;
+            }
+        }
+
+        jobs.insert(
+            id.clone(),
+            Job {
+                status: JobStatus::Pending,
+                total,
+                embeddings: Vec::with_capacity(total),
+                error: None,
+                finished_at: None,
+            },
+        );
+        Some(id)
+    }
+
+    /// Evict the expired jobs every [`EVICTION_INTERVAL`], so that they do not use memory until
+    /// the next job is created
+    pub(crate) async fn evict_expired(self) {
+        let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.retain(|_, job| !job.is_expired(self.ttl));
+        }
+    }
+
+    pub(crate) fn update<F: FnOnce(&mut Job)>(&self, id: &str, f: F) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(id) {
+            f(job)
+        }
+    }
+
+    /// Get a job, unless it expired since the last eviction
+    pub(crate) fn get(&self, id: &str) -> Option<JobResponse> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(id).filter(|job| !job.is_expired(self.ttl));
+        job.map(|job| JobResponse {
+            id: id.to_string(),
+            status: job.status,
+            total: job.total,
+            processed: job.embeddings.len(),
+            embeddings: (job.status == JobStatus::Completed).then(|| job.embeddings.clone()),
+            error: job.error.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_are_polled_until_they_finish() {
+        let jobs = JobStore::new(Duration::from_secs(60), 10);
+        let id = jobs.create(2).unwrap();
+        let job = jobs.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!((job.total, job.processed), (2, 0));
+        assert!(job.embeddings.is_none());
+
+        jobs.update(&id, |job| {
+            job.status = JobStatus::Running;
+            job.embeddings.push(vec![1.0]);
+        });
+        let job = jobs.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Running);
+        assert_eq!(job.processed, 1);
+        assert!(job.embeddings.is_none());
+
+        jobs.update(&id, |job| {
+            job.embeddings.push(vec![2.0]);
+            job.complete();
+        });
+        let job = jobs.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.embeddings, Some(vec![vec![1.0], vec![2.0]]));
+
+        assert!(jobs.get("unknown").is_none());
+    }
+
+    #[test]
+    fn finished_jobs_expire() {
+        let jobs = JobStore::new(Duration::ZERO, 10);
+        let pending = jobs.create(1).unwrap();
+        let failed = jobs.create(1).unwrap();
+        jobs.update(&failed, |job| job.fail("error".to_string()));
+
+        // Only finished jobs expire
+        assert!(jobs.get(&pending).is_some());
+        assert!(jobs.get(&failed).is_none());
+    }
+
+    #[test]
+    fn full_store_refuses_jobs_until_one_expires() {
+        let jobs = JobStore::new(Duration::ZERO, 2);
+        let first = jobs.create(1).unwrap();
+        jobs.create(1).unwrap();
+        assert!(jobs.create(1).is_none());
+
+        jobs.update(&first, |job| job.complete());
+        assert!(jobs.create(1).is_some());
+        assert_eq!(jobs.jobs.lock().unwrap().len(), 2);
+    }
+}
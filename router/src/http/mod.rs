@@ -1,2 +1,9 @@
+mod auth;
+pub(crate) mod config;
+mod connections;
+mod jobs;
+mod models;
+mod preprocessing;
 pub mod server;
 mod types;
+mod validation;
@@ -0,0 +1,167 @@
+/// Loaded models that requests can be routed to
+use crate::{ErrorResponse, ErrorType, Info, PreparedModel};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use text_embeddings_core::infer::Infer;
+use tokio::sync::OnceCell;
+
+#[derive(Clone, Debug)]
+pub(crate) struct ModelRegistry {
+    inner: Arc<RwLock<Models>>,
+}
+
+#[derive(Debug)]
+struct Models {
+    /// Id of the model serving requests that do not select one
+    default: String,
+    /// Each model has its own queue and backend
+    models: HashMap<String, (Infer, Info)>,
+    /// Default model whose backend is only started on the first inference request
+    lazy: Option<Arc<LazyModel>>,
+}
+
+#[derive(Debug)]
+struct LazyModel {
+    model: PreparedModel,
+    /// Set once the backend is started, shared by the requests waiting for it
+    loaded: OnceCell<(Infer, Info)>,
+}
+
+impl ModelRegistry {
+    pub(crate) fn new(infer: Infer, info: Info) -> Self {
+        let default = info.model_id.clone();
+        let models = HashMap::from([(default.clone(), (infer, info))]);
+        Self {
+            inner: Arc::new(RwLock::new(Models {
+                default,
+                models,
+                lazy: None,
+            })),
+        }
+    }
+
+    /// Create a registry whose default model is started by [`ModelRegistry::load_default`]
+    pub(crate) fn lazy(model: PreparedModel) -> Self {
+        let default = model.info.model_id.clone();
+        let lazy = Arc::new(LazyModel {
+            model,
+            loaded: OnceCell::new(),
+        });
+        Self {
+            inner: Arc::new(RwLock::new(Models {
+                default,
+                models: HashMap::new(),
+                lazy: Some(lazy),
+            })),
+        }
+    }
+
+    /// Get the model serving requests that do not select one
+    ///
+    /// Returns `None` if its backend is not started yet.
+    pub(crate) fn default_model(&self) -> Option<(Infer, Info)> {
+        let inner = self.inner.read().unwrap();
+        inner.models.get(&inner.default).cloned()
+    }
+
+    /// Get the info of the model serving requests that do not select one, started or not
+    pub(crate) fn default_info(&self) -> Info {
+        let inner = self.inner.read().unwrap();
+        match &inner.lazy {
+            Some(lazy) => lazy.model.info.clone(),
+            None => inner.models[&inner.default].1.clone(),
+        }
+    }
+
+    /// Get the model serving requests that do not select one, starting its backend if needed
+    ///
+    /// Concurrent requests wait for the same start.
+    pub(crate) async fn load_default(&self) -> Result<(Infer, Info), ErrorResponse> {
+        let lazy = self.inner.read().unwrap().lazy.clone();
+        let lazy = match lazy {
+            Some(lazy) => lazy,
+            None => return self.default_model().ok_or_else(not_loaded),
+        };
+
+        let (infer, info) = lazy
+            .loaded
+            .get_or_try_init(|| async {
+                tracing::info!(
+                    "Starting model {} on first request",
+                    lazy.model.info.model_id
+                );
+                lazy.model.start().await.map_err(|err| {
+                    let message = format!("Could not load model: {err:#}");
+                    tracing::error!("{message}");
+                    ErrorResponse {
+                        error: message,
+                        error_type: ErrorType::Unavailable,
+                        code: "model_load_failed".to_string(),
+                    }
+                })
+            })
+            .await?
+            .clone();
+
+        let mut inner = self.inner.write().unwrap();
+        // The model may have been replaced while it was starting
+        if inner
+            .lazy
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &lazy))
+        {
+            inner.lazy = None;
+            inner
+                .models
+                .insert(info.model_id.clone(), (infer.clone(), info.clone()));
+        }
+        Ok((infer, info))
+    }
+
+    /// Get the model loaded under `model_id`
+    ///
+    /// Returns a `NotFound` error listing the available ids if it is not loaded.
+    pub(crate) fn get(&self, model_id: &str) -> Result<(Infer, Info), ErrorResponse> {
+        let inner = self.inner.read().unwrap();
+        inner.models.get(model_id).cloned().ok_or_else(|| {
+            let mut ids: Vec<&str> = inner.models.keys().map(String::as_str).collect();
+            ids.sort_unstable();
+            ErrorResponse {
+                error: format!(
+                    "Model `{model_id}` is not loaded. Available models: {}",
+                    ids.join(", ")
+                ),
+                error_type: ErrorType::NotFound,
+                code: "model_not_found".to_string(),
+            }
+        })
+    }
+
+    /// Serve a model next to the default one
+    pub(crate) fn insert(&self, infer: Infer, info: Info) {
+        let mut inner = self.inner.write().unwrap();
+        inner.models.insert(info.model_id.clone(), (infer, info));
+    }
+
+    /// Replace the default model and return the previous one, if it was started
+    ///
+    /// Requests already holding the previous model keep running on it.
+    pub(crate) fn replace_default(&self, infer: Infer, info: Info) -> Option<(Infer, Info)> {
+        let mut inner = self.inner.write().unwrap();
+        let default = info.model_id.clone();
+        let previous = std::mem::replace(&mut inner.default, default.clone());
+        let previous = inner.models.remove(&previous);
+        inner.lazy = None;
+        inner.models.insert(default, (infer, info));
+        previous
+    }
+}
+
+/// Error of requests needing a model that is not started
+pub(crate) fn not_loaded() -> ErrorResponse {
+    ErrorResponse {
+        error: "Model is not loaded yet".to_string(),
+        error_type: ErrorType::Unavailable,
+        code: "model_not_loaded".to_string(),
+    }
+}
@@ -0,0 +1,304 @@
+use crate::http::types::DetectedLanguage;
+
+/// Tags whose content is never visible text
+const SKIPPED_TAGS: [&str; 2] = ["script", "style"];
+
+/// Extract the text content of an HTML document
+///
+/// Tags and comments are removed, the content of `<script>` and `<style>` elements is dropped,
+/// common character entities are decoded and whitespace is normalized.
+pub(crate) fn strip_html(input: &str) -> String {
+    let mut text = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let tag = &rest[start..];
+
+        // Comments can contain `>`, so they must be matched on their closing sequence
+        if tag.starts_with("<!--") {
+            rest = match tag.find("-->") {
+                Some(end) => &tag[end + 3..],
+                None => "",
+            };
+            text.push(' ');
+            continue;
+        }
+
+        let end = match tag.find('>') {
+            Some(end) => end,
+            None => {
+                // Unclosed `<`: keep it as text
+                text.push_str(tag);
+                rest = "";
+                break;
+            }
+        };
+
+        let name = tag[1..end]
+            .trim_start()
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        rest = &tag[end + 1..];
+
+        if SKIPPED_TAGS.contains(&name.as_str()) && !tag[..end].ends_with('/') {
+            // Skip everything until the matching closing tag
+            let closing = format!("</{name}");
+            rest = match rest.to_ascii_lowercase().find(&closing) {
+                Some(close) => {
+                    let after = &rest[close..];
+                    match after.find('>') {
+                        Some(close_end) => &after[close_end + 1..],
+                        None => "",
+                    }
+                }
+                None => "",
+            };
+        }
+        // Tags are replaced by a space so that words in adjacent blocks do not get merged
+        text.push(' ');
+    }
+    text.push_str(rest);
+
+    normalize_whitespace(&decode_entities(&text))
+}
+
+/// Decode named and numeric HTML character entities
+fn decode_entities(input: &str) -> String {
+    let mut decoded = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let entity = &rest[start..];
+
+        let value = entity.find(';').and_then(|end| {
+            let c = match &entity[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                name => {
+                    if let Some(hex) = name
+                        .strip_prefix("#x")
+                        .or_else(|| name.strip_prefix("#X"))
+                    {
+                        u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                    } else if let Some(dec) = name.strip_prefix('#') {
+                        dec.parse::<u32>().ok().and_then(char::from_u32)
+                    } else {
+                        None
+                    }
+                }
+            };
+            c.map(|c| (c, end))
+        });
+
+        match value {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &entity[end + 1..];
+            }
+            None => {
+                // Not an entity: keep the ampersand as is
+                decoded.push('&');
+                rest = &entity[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Characters ending a sentence
+const SENTENCE_TERMINATORS: [char; 6] = ['.', '!', '?', '。', '！', '？'];
+
+/// Split an input on `split_on`, or into sentences if it is `sentences`
+///
+/// Segments are trimmed and empty segments are dropped.
+pub(crate) fn split_input(input: &str, split_on: &str) -> Vec<String> {
+    let segments: Vec<&str> = if split_on == "sentences" {
+        split_sentences(input)
+    } else {
+        input.split(split_on).collect()
+    };
+    segments
+        .into_iter()
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Split a text after each sentence terminator followed by whitespace
+///
+/// Terminators are not followed by whitespace in CJK scripts, so their full-width forms always
+/// end a sentence.
+fn split_sentences(input: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !SENTENCE_TERMINATORS.contains(&c) {
+            continue;
+        }
+        let end = i + c.len_utf8();
+        let ends_sentence = match chars.peek() {
+            Some((_, next)) => next.is_whitespace() || !c.is_ascii(),
+            None => true,
+        };
+        if ends_sentence {
+            sentences.push(&input[start..end]);
+            start = end;
+        }
+    }
+    sentences.push(&input[start..]);
+    sentences
+}
+
+/// Collapse runs of whitespace into a single space and trim both ends
+pub(crate) fn normalize_whitespace(input: &str) -> String {
+    input.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Detect the language of an input
+///
+/// This does not change the input: the result is only returned as metadata.
+pub(crate) fn detect_language(input: &str) -> DetectedLanguage {
+    match whatlang::detect(input) {
+        Some(info) => DetectedLanguage {
+            code: info.lang().code(),
+            confidence: info.confidence() as f32,
+        },
+        None => DetectedLanguage {
+            code: "und",
+            confidence: 0.0,
+        },
+    }
+}
+
+/// Keywords starting a line of code in common programming languages
+const CODE_KEYWORDS: [&str; 16] = [
+    "def ",
+    "fn ",
+    "class ",
+    "import ",
+    "from ",
+    "function ",
+    "return ",
+    "#include",
+    "public ",
+    "private ",
+    "let ",
+    "const ",
+    "var ",
+    "if (",
+    "for (",
+    "SELECT ",
+];
+
+/// Guess the coarse content type of an input from its formatting: `code`, `table` or `prose`
+///
+/// This is a heuristic on the shape of the lines, not a trained classifier: `table` needs most
+/// lines to have the same number of `|`, tab or comma separators as the first one, and `code`
+/// most lines to start with a keyword or end with a statement or block delimiter.
+pub(crate) fn classify_content(input: &str) -> &'static str {
+    let lines: Vec<&str> = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return "prose";
+    }
+    // Most of the lines must match for the input to be classified
+    let majority = |count: usize| count * 2 > lines.len();
+
+    if lines.len() > 1 {
+        // Minimum number of separators of a row, as prose often has a comma per line
+        for (separator, min_separators) in [('|', 2), ('\t', 1), (',', 2)] {
+            let separators = lines[0].matches(separator).count();
+            let rows = lines
+                .iter()
+                .filter(|line| line.matches(separator).count() == separators)
+                .count();
+            if separators >= min_separators && majority(rows) {
+                return "table";
+            }
+        }
+    }
+
+    let code_lines = lines
+        .iter()
+        .filter(|line| {
+            CODE_KEYWORDS
+                .iter()
+                .any(|keyword| line.starts_with(keyword))
+                || line.ends_with([';', '{', '}', ':', ')'])
+        })
+        .count();
+    if majority(code_lines) {
+        return "code";
+    }
+    "prose"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_html_keeps_visible_text() {
+        assert_eq!(
+            strip_html("<p>Hello <b>world</b></p><div>next</div>"),
+            "Hello world next"
+        );
+        // Script and style contents are dropped, whatever the case of their tags
+        assert_eq!(
+            strip_html("a<SCRIPT type=\"x\">if (a < b) {}</script>b<style>p {}</Style>c"),
+            "a b c"
+        );
+        // Self-closing skipped tags do not swallow the rest of the document
+        assert_eq!(strip_html("a<script src=\"x\"/>b"), "a b");
+        // Comments end on their closing sequence, even if they contain `>`
+        assert_eq!(strip_html("a<!-- x > y -->b<!-- unclosed"), "a b");
+        // An unclosed `<` is text
+        assert_eq!(strip_html("a < b"), "a < b");
+        assert_eq!(strip_html("&lt;p&gt; &amp;amp;"), "<p> &amp;");
+    }
+
+    #[test]
+    fn decode_entities_decodes_named_and_numeric_entities() {
+        assert_eq!(
+            decode_entities("&amp;&lt;&gt;&quot;&apos;&nbsp;"),
+            "&<>\"' "
+        );
+        assert_eq!(decode_entities("&#233;&#xE9;&#XE9;"), "ééé");
+        // Unknown or invalid entities are kept as is
+        assert_eq!(
+            decode_entities("a & b &foo; &#xZZ; &#1114112;"),
+            "a & b &foo; &#xZZ; &#1114112;"
+        );
+        assert_eq!(decode_entities("&amp"), "&amp");
+    }
+
+    #[test]
+    fn classify_content_detects_tables_and_code() {
+        assert_eq!(classify_content("| a | b |\n| 1 | 2 |\n| 3 | 4 |"), "table");
+        assert_eq!(classify_content("a\tb\n1\t2"), "table");
+        assert_eq!(classify_content("name,age,city\nAnn,32,Paris"), "table");
+        assert_eq!(classify_content("fn main() {\n    let a = 1;\n}"), "code");
+        assert_eq!(classify_content("def f(x):\n    return x"), "code");
+        // A comma per line is not enough to be a table
+        assert_eq!(
+            classify_content("Hello, world.\nThis is prose, mostly."),
+            "prose"
+        );
+        assert_eq!(classify_content("A single sentence."), "prose");
+        assert_eq!(classify_content("  \n "), "prose");
+    }
+}
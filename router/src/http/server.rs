@@ -1,35 +1,76 @@
 /// HTTP Server logic
+use crate::http::auth::{constant_time_eq, Principal, ADMIN_SCOPE};
+use crate::http::config::{
+    GroupAggregation, HttpVersion, Reference, RootResponse, ServerConfig, WeaviateProtocol,
+};
+use crate::http::connections::{Connection, LimitedIncoming};
+use crate::http::jobs::JobStore;
+use crate::http::models::{not_loaded, ModelRegistry};
+use crate::http::preprocessing::{
+    classify_content, detect_language, normalize_whitespace, split_input, strip_html,
+};
 use crate::http::types::{
-    EmbedRequest, EmbedResponse, EmbedWeaviateRequest, EmbedWeaviateResponse, Input, OpenAICompatEmbedding, OpenAICompatErrorResponse,
-    OpenAICompatRequest, OpenAICompatResponse, OpenAICompatUsage, PredictInput, PredictRequest,
-    PredictResponse, Prediction, Rank, RerankRequest, RerankResponse, Sequence,
+    default_warmup_iterations, BenchRequest, BenchResponse, ChunkedEmbeddings,
+    ClassifyBySimilarityRequest, ClassifyBySimilarityResponse, CountTokensRequest,
+    CountTokensResponse, DetectedLanguage, Dimension, Duplicates, EmbedInput, EmbedRequest,
+    EmbedResponse, EmbedWeaviateBatchRequest, EmbedWeaviateBatchResponse, EmbedWeaviateRequest,
+    EmbedWeaviateResponse, Embedding, EmbeddingChunk, EncodingFormat, HealthDetailResponse, Input,
+    JobRequest, JobResponse, JobStatus, LabelSimilarity, LogLevel, MetaResponse,
+    OpenAICompatEmbedding, OpenAICompatErrorResponse, OpenAICompatRequest, OpenAICompatResponse,
+    OpenAICompatUsage, OpenAICompatVector, PredictFormat, PredictInput, PredictRequest,
+    PredictResponse, Prediction, Predictions, Rank, RankField, RawOutput, ReloadRequest,
+    RerankRequest, RerankResponse, RootInfo, ScoreScale, Segment, Sequence, SimilarityMetric,
+    SimilarityRequest, SimilarityResponse, SimilarityScore, StreamedEmbedding, TokenAttribution,
+    TokenCount, VectorDtype, WarmupRequest, WarmupResponse, WeaviateErrorMessage,
+    WeaviateErrorResponse, WeaviateTask, WeaviateVectorizeConfig,
 };
+use crate::http::validation::{self, Validate};
+use crate::logging::{set_log_filter, LogFilterError};
+use crate::prometheus::{self, with_tenant};
+use crate::shutdown::ShuttingDown;
 use crate::{
-    shutdown, ClassifierModel, EmbeddingModel, ErrorResponse, ErrorType, Info, ModelType,
-    ResponseMetadata,
+    check_query_tokens, load_model, mean_duration, shutdown, ClassifierModel, EmbeddingModel,
+    ErrorResponse, ErrorType, Info, ModelOptions, ModelType, PreparedModel, ResponseMetadata,
 };
 use axum::{body::Bytes};
 use serde_json::from_slice;
 use anyhow::Context;
-use axum::extract::Extension;
+use axum::body::{Body, HttpBody, StreamBody};
+use axum::extract::{BodyStream, ConnectInfo, Extension, FromRequest, Path, State};
 use axum::http::HeaderValue;
-use axum::http::{HeaderMap, Method, StatusCode};
-use axum::routing::{get, post};
+use axum::http::{header, HeaderMap, Method, Request, StatusCode, Uri};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
 use axum::{http, Json, Router};
 use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
-use futures::future::join_all;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use encoding_rs::{Encoding, UTF_8};
+use futures::future::{join_all, Future};
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use half::f16;
+use hyper::server::conn::AddrIncoming;
+use ipnet::IpNet;
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
-use std::env;
-use std::net::SocketAddr;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use text_embeddings_backend::BackendError;
-use text_embeddings_core::infer::{Infer, InferResponse};
+use text_embeddings_backend::{BackendError, DeviceStats};
+use text_embeddings_core::infer::{
+    collect_batch_sizes, count_truncated_tokens, inputs_redacted, redact_inputs, truncated_tokens,
+    Infer, InferResponse,
+};
+use text_embeddings_core::tokenization::EncodingInput;
 use text_embeddings_core::TextEmbeddingsError;
-use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::cors::any;
 use tracing::instrument;
-use tracing::{info, error};
+use tracing::{error, info};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -38,11 +79,18 @@ use utoipa_swagger_ui::SwaggerUi;
 get,
 tag = "Text Embeddings Inference",
 path = "/meta",
-responses((status = 200, description = "Served model info", body = Info))
+responses((status = 200, description = "Served model info", body = MetaResponse))
 )]
-#[instrument]
-async fn get_model_info(info: Extension<Info>) -> Json<Info> {
-    Json(info.0)
+#[instrument(skip(config))]
+async fn get_model_info(
+    info: Extension<Info>,
+    config: Extension<ServerConfig>,
+) -> Json<MetaResponse> {
+    if config.weaviate_meta {
+        Json(MetaResponse::Weaviate { model: info.0 })
+    } else {
+        Json(MetaResponse::Info(info.0))
+    }
 }
 
 #[utoipa::path(
@@ -51,8 +99,8 @@ tag = "Text Embeddings Inference",
 path = "/.well-known/live",
 responses((status = 204, description = "Everything is working fine"))
 )]
-#[instrument(skip(infer))]
-async fn live(infer: Extension<Infer>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+#[instrument]
+async fn live() -> Result<(), (StatusCode, Json<ErrorResponse>)> {
     Ok(())
 }
 
@@ -60,13 +108,75 @@ async fn live(infer: Extension<Infer>) -> Result<(), (StatusCode, Json<ErrorResp
 get,
 tag = "Text Embeddings Inference",
 path = "/.well-known/ready",
-responses((status = 204, description = "Everything is working fine"))
+responses(
+(status = 204, description = "Everything is working fine"),
+(status = 503, description = "The model is started on the first request and is not loaded yet, \
+or the server is shutting down",
+body = ErrorResponse,
+example = json ! ({"error": "Model is not loaded yet", "error_type": "unavailable", "code": "model_not_loaded"})),
+)
 )]
-#[instrument(skip(infer))]
-async fn ready(infer: Extension<Infer>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+#[instrument(skip(infer, config, shutting_down))]
+async fn ready(
+    infer: Option<Extension<Infer>>,
+    config: Extension<ServerConfig>,
+    shutting_down: Extension<ShuttingDown>,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    // Load balancers stop routing new requests while the requests in flight are drained
+    if shutting_down.get() {
+        Err(ErrorResponse {
+            error: "Server is shutting down".to_string(),
+            error_type: ErrorType::Unavailable,
+            code: "shutting_down".to_string(),
+        })?;
+    }
+    // Lazily loaded models can report ready so that they receive the request starting them
+    if infer.is_none() && !config.lazy_ready_on_demand {
+        Err(not_loaded())?;
+    }
     Ok(())
 }
 
+/// Describe the service and its routes on `GET /` when `ROOT_RESPONSE` is `info`
+#[instrument(skip_all)]
+async fn root_info(info: Extension<Info>, root: Extension<RootInfo>) -> Json<RootInfo> {
+    Json(RootInfo {
+        model_id: info.model_id.clone(),
+        ..root.0
+    })
+}
+
+/// Answer requests to unknown routes with a JSON error listing the available endpoints
+///
+/// Paths under `/v1/` are assumed to come from OpenAI clients and get an OpenAI error.
+#[instrument(skip_all)]
+async fn not_found(
+    config: Extension<ServerConfig>,
+    root: Extension<RootInfo>,
+    uri: Uri,
+) -> Response {
+    let path = uri.path();
+    let message = format!(
+        "Route `{path}` not found. Available endpoints: {}",
+        root.endpoints.join(", ")
+    );
+    tracing::debug!("{message}");
+    let err = ErrorResponse {
+        error: message,
+        error_type: ErrorType::NotFound,
+        code: "route_not_found".to_string(),
+    };
+    let prefix = config.route_prefix.as_deref().unwrap_or_default();
+    let openai = path
+        .strip_prefix(prefix)
+        .is_some_and(|path| path.starts_with("/v1/"));
+    if openai {
+        <(StatusCode, Json<OpenAICompatErrorResponse>)>::from(err).into_response()
+    } else {
+        <(StatusCode, Json<ErrorResponse>)>::from(err).into_response()
+    }
+}
+
 #[utoipa::path(
 get,
 tag = "Text Embeddings Inference",
@@ -74,21 +184,83 @@ path = "/health",
 responses(
 (status = 200, description = "Everything is working fine"),
 (status = 503, description = "Text embeddings Inference is down", body = ErrorResponse,
-example = json ! ({"error": "unhealthy", "error_type": "unhealthy"})),
+example = json ! ({"error": "unhealthy", "error_type": "unhealthy", "code": "unhealthy"})),
 )
 )]
 #[instrument(skip(infer))]
 /// Health check method
-async fn health(infer: Extension<Infer>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+async fn health(infer: Option<Extension<Infer>>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    // A model that is not loaded yet is started by the next inference request
+    let infer = match infer {
+        Some(infer) => infer,
+        None => return Ok(()),
+    };
     match infer.health().await {
         true => Ok(()),
         false => Err(ErrorResponse {
             error: "unhealthy".to_string(),
             error_type: ErrorType::Unhealthy,
+            code: "unhealthy".to_string(),
         })?,
     }
 }
 
+/// Health check with the resource usage of the device. Served when `REPORT_DEVICE_STATS` is set
+#[utoipa::path(
+get,
+tag = "Text Embeddings Inference",
+path = "/health/detail",
+responses(
+(status = 200, description = "Health and device stats", body = HealthDetailResponse),
+)
+)]
+#[instrument(skip(infer))]
+async fn health_detail(infer: Option<Extension<Infer>>) -> Json<HealthDetailResponse> {
+    let (healthy, stats) = match infer {
+        Some(infer) => (
+            infer.health().await,
+            infer.device_stats().await.unwrap_or_default(),
+        ),
+        // The device is not used before the model is loaded
+        None => (true, DeviceStats::default()),
+    };
+    record_device_stats(&stats);
+    Json(HealthDetailResponse {
+        healthy,
+        memory_used_bytes: stats.memory_used,
+        memory_total_bytes: stats.memory_total,
+        utilization: stats.utilization,
+    })
+}
+
+/// Interval between two samples of the device stats exported as gauges
+const DEVICE_STATS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Periodically export the device stats of the default model as Prometheus gauges
+async fn sample_device_stats(models: ModelRegistry) {
+    let mut interval = tokio::time::interval(DEVICE_STATS_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Some((infer, _)) = models.default_model() {
+            if let Some(stats) = infer.device_stats().await {
+                record_device_stats(&stats);
+            }
+        }
+    }
+}
+
+fn record_device_stats(stats: &DeviceStats) {
+    if let Some(memory_used) = stats.memory_used {
+        metrics::gauge!("te_device_memory_used_bytes", memory_used as f64);
+    }
+    if let Some(memory_total) = stats.memory_total {
+        metrics::gauge!("te_device_memory_total_bytes", memory_total as f64);
+    }
+    if let Some(utilization) = stats.utilization {
+        metrics::gauge!("te_device_utilization", utilization as f64);
+    }
+}
+
 /// Get Predictions. Returns a 424 status code if the model is not a Sequence Classification model
 #[utoipa::path(
 post,
@@ -98,13 +270,13 @@ request_body = PredictRequest,
 responses(
 (status = 200, description = "Predictions", body = PredictResponse),
 (status = 424, description = "Prediction Error", body = ErrorResponse,
-example = json ! ({"error": "Inference failed", "error_type": "backend"})),
+example = json ! ({"error": "Inference failed", "error_type": "backend", "code": "backend_error"})),
 (status = 429, description = "Model is overloaded", body = ErrorResponse,
-example = json ! ({"error": "Model is overloaded", "error_type": "overloaded"})),
+example = json ! ({"error": "Model is overloaded", "error_type": "overloaded", "code": "overloaded"})),
 (status = 422, description = "Tokenization error", body = ErrorResponse,
-example = json ! ({"error": "Tokenization error", "error_type": "tokenizer"})),
+example = json ! ({"error": "Tokenization error", "error_type": "tokenizer", "code": "tokenizer_error"})),
 (status = 413, description = "Batch size error", body = ErrorResponse,
-example = json ! ({"error": "Batch size error", "error_type": "validation"})),
+example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
 )
 )]
 #[instrument(
@@ -114,25 +286,53 @@ example = json ! ({"error": "Batch size error", "error_type": "validation"})),
 async fn predict(
     infer: Extension<Infer>,
     info: Extension<Info>,
+    config: Extension<ServerConfig>,
+    limits: Extension<ConcurrencyLimits>,
     Json(req): Json<PredictRequest>,
 ) -> Result<(HeaderMap, Json<PredictResponse>), (StatusCode, Json<ErrorResponse>)> {
     let span = tracing::Span::current();
     let start_time = Instant::now();
 
+    if req.aggregate_groups && config.label_groups.is_empty() {
+        Err(validation::invalid(
+            "aggregate_groups",
+            "requires the server to run with `LABEL_GROUPS_PATH`".to_string(),
+        ))?;
+    }
+    let to_predictions = |predictions: Vec<Prediction>| {
+        if !req.aggregate_groups {
+            return format_predictions(predictions, req.format);
+        }
+        let groups = group_predictions(
+            &predictions,
+            &config.label_groups,
+            config.label_group_aggregation,
+        );
+        Predictions::Grouped {
+            labels: Box::new(format_predictions(predictions, req.format)),
+            groups: Box::new(format_predictions(groups, req.format)),
+        }
+    };
+
+    let permit_wait_timeout = config.permit_wait_timeout;
     // Closure for predict
     let predict_inner = move |inputs: Sequence,
                               truncate: bool,
+                              add_special_tokens: bool,
                               raw_scores: bool,
                               infer: Infer,
                               info: Info,
                               permit: Option<OwnedSemaphorePermit>| async move {
         let permit = match permit {
-            None => infer.acquire_permit().await,
+            None => infer
+                .acquire_permit_timeout(permit_wait_timeout)
+                .await
+                .map_err(ErrorResponse::from)?,
             Some(permit) => permit,
         };
 
         let response = infer
-            .predict(inputs, truncate, raw_scores, permit)
+            .predict(inputs, truncate, add_special_tokens, raw_scores, permit)
             .await
             .map_err(ErrorResponse::from)?;
 
@@ -142,51 +342,73 @@ async fn predict(
             _ => panic!(),
         };
 
-        let mut predictions: Vec<Prediction> = {
-            // Map score to label
-            response
-                .results
-                .into_iter()
-                .enumerate()
-                .map(|(i, s)| Prediction {
-                    score: s,
-                    label: id2label.get(&i.to_string()).unwrap().clone(),
-                })
-                .collect()
-        };
-        // Reverse sort
-        predictions.sort_by(|x, y| x.score.partial_cmp(&y.score).unwrap());
-        predictions.reverse();
+        // Map score to label, in model label order
+        let predictions: Vec<Prediction> = response
+            .results
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| Prediction {
+                score: s,
+                label: id2label.get(&i.to_string()).unwrap().clone(),
+            })
+            .collect();
 
-        Ok::<(usize, Duration, Duration, Duration, Vec<Prediction>), ErrorResponse>((
+        Ok::<
+            (
+                usize,
+                Duration,
+                Duration,
+                Duration,
+                Vec<Prediction>,
+                Vec<f32>,
+            ),
+            ErrorResponse,
+        >((
             response.prompt_tokens,
             response.tokenization,
             response.queue,
             response.inference,
             predictions,
+            response.logits.unwrap_or_default(),
         ))
     };
 
+    let _batch_slot = match &req.inputs {
+        PredictInput::Single(_) => None,
+        PredictInput::Batch(_) => limits.acquire_batch()?,
+    };
     let (response, metadata) = match req.inputs {
         PredictInput::Single(inputs) => {
             metrics::increment_counter!("te_request_count", "method" => "single");
 
             let compute_chars = inputs.count_chars();
             let permit = infer.try_acquire_permit().map_err(ErrorResponse::from)?;
-            let (prompt_tokens, tokenization, queue, inference, predictions) = predict_inner(
-                inputs,
-                req.truncate,
-                req.raw_scores,
-                infer.0,
-                info.0,
-                Some(permit),
-            )
-            .await?;
+            let (prompt_tokens, tokenization, queue, inference, predictions, logits) =
+                predict_inner(
+                    inputs,
+                    req.truncate,
+                    req.add_special_tokens,
+                    req.raw_scores,
+                    infer.0.clone(),
+                    info.0,
+                    Some(permit),
+                )
+                .await?;
 
             metrics::increment_counter!("te_request_success", "method" => "single");
 
+            let predictions = to_predictions(predictions);
+            let response = if req.return_logits {
+                PredictResponse::SingleWithLogits {
+                    predictions,
+                    logits,
+                }
+            } else {
+                PredictResponse::Single(predictions)
+            };
+
             (
-                PredictResponse::Single(predictions),
+                response,
                 ResponseMetadata::new(
                     compute_chars,
                     prompt_tokens,
@@ -201,6 +423,7 @@ async fn predict(
             metrics::increment_counter!("te_request_count", "method" => "batch");
 
             let batch_size = inputs.len();
+            metrics::histogram!("te_batch_size", batch_size as f64);
             if batch_size > info.max_client_batch_size {
                 let message = format!(
                     "batch size {batch_size} > maximum allowed batch size {}",
@@ -210,11 +433,14 @@ async fn predict(
                 let err = ErrorResponse {
                     error: message,
                     error_type: ErrorType::Validation,
+                    code: "batch_too_large".to_string(),
                 };
                 metrics::increment_counter!("te_request_failure", "err" => "batch_size");
                 Err(err)?;
             }
 
+            check_empty_batch(&config, batch_size)?;
+
             let mut futures = Vec::with_capacity(batch_size);
             let mut compute_chars = 0;
 
@@ -225,6 +451,7 @@ async fn predict(
                 futures.push(predict_inner(
                     input,
                     req.truncate,
+                    req.add_special_tokens,
                     req.raw_scores,
                     local_infer.0,
                     local_info.0,
@@ -232,11 +459,19 @@ async fn predict(
                 ))
             }
             let results = join_all(futures).await.into_iter().collect::<Result<
-                Vec<(usize, Duration, Duration, Duration, Vec<Prediction>)>,
+                Vec<(
+                    usize,
+                    Duration,
+                    Duration,
+                    Duration,
+                    Vec<Prediction>,
+                    Vec<f32>,
+                )>,
                 ErrorResponse,
             >>()?;
 
             let mut predictions = Vec::with_capacity(batch_size);
+            let mut logits = Vec::with_capacity(batch_size);
             let mut total_tokenization_time = 0;
             let mut total_queue_time = 0;
             let mut total_inference_time = 0;
@@ -247,21 +482,31 @@ async fn predict(
                 total_tokenization_time += r.1.as_nanos() as u64;
                 total_queue_time += r.2.as_nanos() as u64;
                 total_inference_time += r.3.as_nanos() as u64;
-                predictions.push(r.4);
+                predictions.push(to_predictions(r.4));
+                logits.push(r.5);
             }
             let batch_size = batch_size as u64;
 
             metrics::increment_counter!("te_request_success", "method" => "batch");
 
+            let response = if req.return_logits {
+                PredictResponse::BatchWithLogits {
+                    predictions,
+                    logits,
+                }
+            } else {
+                PredictResponse::Batch(predictions)
+            };
+
             (
-                PredictResponse::Batch(predictions),
+                response,
                 ResponseMetadata::new(
                     compute_chars,
                     total_compute_tokens,
                     start_time,
-                    Duration::from_nanos(total_tokenization_time / batch_size),
-                    Duration::from_nanos(total_queue_time / batch_size),
-                    Duration::from_nanos(total_inference_time / batch_size),
+                    mean_duration(total_tokenization_time, batch_size),
+                    mean_duration(total_queue_time, batch_size),
+                    mean_duration(total_inference_time, batch_size),
                 ),
             )
         }
@@ -270,13 +515,73 @@ async fn predict(
     metadata.record_span(&span);
     metadata.record_metrics();
 
-    let headers = HeaderMap::from(metadata);
+    let used_tokens = metadata.compute_tokens;
+    let mut headers = HeaderMap::from(metadata);
+    if config.report_truncation {
+        truncation_headers(&mut headers, used_tokens);
+    }
 
     tracing::info!("Success");
 
     Ok((headers, Json(response)))
 }
 
+/// Sort predictions by descending score, or key them by label in model label order
+fn format_predictions(mut predictions: Vec<Prediction>, format: PredictFormat) -> Predictions {
+    match format {
+        PredictFormat::List => {
+            // Reverse sort
+            predictions.sort_by(|x, y| x.score.partial_cmp(&y.score).unwrap());
+            predictions.reverse();
+            Predictions::List(predictions)
+        }
+        PredictFormat::Map => Predictions::Map(predictions),
+    }
+}
+
+/// Aggregate the scores of the labels of each group, in the order of their first label
+///
+/// Labels without a group are left out.
+fn group_predictions(
+    predictions: &[Prediction],
+    label_groups: &HashMap<String, String>,
+    aggregation: GroupAggregation,
+) -> Vec<Prediction> {
+    // Score and number of labels of each group
+    let mut groups: Vec<(Prediction, usize)> = Vec::new();
+    for prediction in predictions {
+        let group = match label_groups.get(&prediction.label) {
+            Some(group) => group,
+            None => continue,
+        };
+        match groups.iter_mut().find(|(g, _)| &g.label == group) {
+            Some((g, count)) => {
+                g.score = match aggregation {
+                    GroupAggregation::Sum | GroupAggregation::Mean => g.score + prediction.score,
+                    GroupAggregation::Max => g.score.max(prediction.score),
+                };
+                *count += 1;
+            }
+            None => groups.push((
+                Prediction {
+                    score: prediction.score,
+                    label: group.clone(),
+                },
+                1,
+            )),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(mut group, count)| {
+            if aggregation == GroupAggregation::Mean {
+                group.score /= count as f32;
+            }
+            group
+        })
+        .collect()
+}
+
 /// Get Ranks. Returns a 424 status code if the model is not a Sequence Classification model with
 /// a single class.
 #[utoipa::path(
@@ -285,15 +590,18 @@ tag = "Text Embeddings Inference",
 path = "/rerank",
 request_body = RerankRequest,
 responses(
-(status = 200, description = "Ranks", body = RerankResponse),
+(status = 200, description = "Ranks. With `stream`, one rank per line as `application/x-ndjson`, \
+in completion order", body = RerankResponse),
 (status = 424, description = "Rerank Error", body = ErrorResponse,
-example = json ! ({"error": "Inference failed", "error_type": "backend"})),
+example = json ! ({"error": "Inference failed", "error_type": "backend", "code": "backend_error"})),
 (status = 429, description = "Model is overloaded", body = ErrorResponse,
-example = json ! ({"error": "Model is overloaded", "error_type": "overloaded"})),
+example = json ! ({"error": "Model is overloaded", "error_type": "overloaded", "code": "overloaded"})),
 (status = 422, description = "Tokenization error", body = ErrorResponse,
-example = json ! ({"error": "Tokenization error", "error_type": "tokenizer"})),
+example = json ! ({"error": "Tokenization error", "error_type": "tokenizer", "code": "tokenizer_error"})),
 (status = 413, description = "Batch size error", body = ErrorResponse,
-example = json ! ({"error": "Batch size error", "error_type": "validation"})),
+example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
+(status = 422, description = "Query is longer than `MAX_QUERY_TOKENS`", body = ErrorResponse,
+example = json ! ({"error": "query has 600 tokens > maximum allowed query tokens 512", "error_type": "invalid_parameter", "code": "query_too_long"})),
 )
 )]
 #[instrument(
@@ -303,8 +611,10 @@ example = json ! ({"error": "Batch size error", "error_type": "validation"})),
 async fn rerank(
     infer: Extension<Infer>,
     info: Extension<Info>,
-    Json(req): Json<RerankRequest>,
-) -> Result<(HeaderMap, Json<RerankResponse>), (StatusCode, Json<ErrorResponse>)> {
+    config: Extension<ServerConfig>,
+    limits: Extension<ConcurrencyLimits>,
+    Json(mut req): Json<RerankRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     let span = tracing::Span::current();
     let start_time = Instant::now();
 
@@ -327,19 +637,36 @@ async fn rerank(
     }
     .map_err(|err| {
         tracing::error!("{err}");
-        ErrorResponse::from(err)
+        ErrorResponse {
+            code: "model_type_mismatch".to_string(),
+            ..ErrorResponse::from(err)
+        }
     })?;
 
+    req.validate()?;
+    if req.normalize_whitespace {
+        req.query = normalize_whitespace(&req.query);
+        for text in &mut req.texts {
+            *text = normalize_whitespace(text);
+        }
+    }
+    // The sigmoid is applied to the logits of the model
+    let raw_scores = req.raw_scores || req.score_scale == ScoreScale::Sigmoid;
+
+    let permit_wait_timeout = config.permit_wait_timeout;
     // Closure for rerank
     let rerank_inner = move |query: String,
                              text: String,
                              truncate: bool,
                              raw_scores: bool,
                              infer: Infer| async move {
-        let permit = infer.acquire_permit().await;
+        let permit = infer
+            .acquire_permit_timeout(permit_wait_timeout)
+            .await
+            .map_err(ErrorResponse::from)?;
 
         let response = infer
-            .predict((query, text), truncate, raw_scores, permit)
+            .predict((query, text), truncate, true, raw_scores, permit)
             .await
             .map_err(ErrorResponse::from)?;
 
@@ -358,6 +685,7 @@ async fn rerank(
         metrics::increment_counter!("te_request_count", "method" => "batch");
 
         let batch_size = req.texts.len();
+        metrics::histogram!("te_batch_size", batch_size as f64);
         if batch_size > info.max_client_batch_size {
             let message = format!(
                 "batch size {batch_size} > maximum allowed batch size {}",
@@ -367,30 +695,84 @@ async fn rerank(
             let err = ErrorResponse {
                 error: message,
                 error_type: ErrorType::Validation,
+                code: "batch_too_large".to_string(),
             };
             metrics::increment_counter!("te_request_failure", "err" => "batch_size");
             Err(err)?;
         }
 
-        let mut futures = Vec::with_capacity(batch_size);
+        check_empty_batch(&config, batch_size)?;
+
+        check_query_tokens(&infer, &info, &req.query).await?;
+
+        let batch_slot = limits.acquire_batch()?;
+        if req.stream {
+            return Ok(rerank_stream(
+                infer.0,
+                req,
+                config.permit_wait_timeout,
+                batch_slot,
+            ));
+        }
+        let _batch_slot = batch_slot;
+
+        // Index of the text scored for each text: its first occurrence with `dedupe`
+        let sources: Vec<usize> = if req.dedupe {
+            first_occurrences(&req.texts)
+        } else {
+            (0..batch_size).collect()
+        };
+        let scored: Vec<usize> = (0..batch_size)
+            .filter(|index| sources[*index] == *index)
+            .collect();
+
         let query_chars = req.query.chars().count();
-        let mut compute_chars = query_chars * batch_size;
-
-        for text in &req.texts {
-            compute_chars += text.chars().count();
-            let local_infer = infer.clone();
-            futures.push(rerank_inner(
-                req.query.clone(),
-                text.clone(),
-                req.truncate,
-                req.raw_scores,
-                local_infer.0,
-            ))
+        let mut compute_chars = query_chars * scored.len();
+        for index in &scored {
+            compute_chars += req.texts[*index].chars().count();
         }
-        let results = join_all(futures)
-            .await
-            .into_iter()
-            .collect::<Result<Vec<(usize, Duration, Duration, Duration, f32)>, ErrorResponse>>()?;
+
+        let results: Vec<(usize, Duration, Duration, Duration, f32)> = if config.rerank_batching {
+            let pairs: Vec<(String, String)> = scored
+                .iter()
+                .map(|index| (req.query.clone(), req.texts[*index].clone()))
+                .collect();
+            let permits = infer
+                .acquire_permits_timeout(pairs.len(), config.permit_wait_timeout)
+                .await
+                .map_err(ErrorResponse::from)?;
+            infer
+                .predict_batch(pairs, req.truncate, true, raw_scores, permits)
+                .await
+                .map_err(ErrorResponse::from)?
+                .into_iter()
+                .map(|r| {
+                    (
+                        r.prompt_tokens,
+                        r.tokenization,
+                        r.queue,
+                        r.inference,
+                        r.results[0],
+                    )
+                })
+                .collect()
+        } else {
+            let mut futures = Vec::with_capacity(scored.len());
+            for index in &scored {
+                let local_infer = infer.clone();
+                futures.push(rerank_inner(
+                    req.query.clone(),
+                    req.texts[*index].clone(),
+                    req.truncate,
+                    raw_scores,
+                    local_infer.0,
+                ))
+            }
+            join_all(futures)
+                .await
+                .into_iter()
+                .collect::<Result<_, ErrorResponse>>()?
+        };
 
         let mut ranks = Vec::with_capacity(batch_size);
         let mut total_tokenization_time = 0;
@@ -398,41 +780,65 @@ async fn rerank(
         let mut total_inference_time = 0;
         let mut total_compute_tokens = 0;
 
-        for (index, r) in results.into_iter().enumerate() {
+        let mut scored_scores = Vec::with_capacity(scored.len());
+        for r in results {
             total_compute_tokens += r.0;
             total_tokenization_time += r.1.as_nanos() as u64;
             total_queue_time += r.2.as_nanos() as u64;
             total_inference_time += r.3.as_nanos() as u64;
+            scored_scores.push(r.4);
+        }
+        // Identical texts share the score of the text that was scored
+        let mut source_scores = vec![0.0; batch_size];
+        for (index, score) in scored.iter().zip(scored_scores) {
+            source_scores[*index] = score;
+        }
+        let scores = sources
+            .iter()
+            .map(|source| source_scores[*source])
+            .collect();
+        let mut scores = scale_scores(scores, req.score_scale);
+        if let Some(prior_scores) = &req.prior_scores {
+            scores = fuse_scores(&scores, prior_scores, req.fusion_weight.unwrap_or(0.5));
+        }
+
+        for (index, score) in scores.into_iter().enumerate() {
+            if req.duplicates == Duplicates::Collapse && sources[index] != index {
+                continue;
+            }
             let text = if req.return_text {
                 Some(req.texts[index].clone())
             } else {
                 None
             };
 
-            ranks.push(Rank {
-                index,
-                text,
-                score: r.4,
-            })
+            ranks.push(Rank { index, text, score })
         }
 
         // Reverse sort
         ranks.sort_by(|x, y| x.score.partial_cmp(&y.score).unwrap());
         ranks.reverse();
 
-        let batch_size = batch_size as u64;
+        let batch_size = scored.len() as u64;
 
         metrics::increment_counter!("te_request_success", "method" => "batch");
 
+        let response = match &req.fields {
+            Some(fields) => RerankResponse::Selected(
+                ranks.into_iter().map(|rank| rank.select(fields)).collect(),
+            ),
+            None => RerankResponse::Ranks(ranks),
+        };
+
         (
-            RerankResponse(ranks),
+            response,
             ResponseMetadata::new(
                 compute_chars,
                 total_compute_tokens,
                 start_time,
-                Duration::from_nanos(total_tokenization_time / batch_size),
-                Duration::from_nanos(total_queue_time / batch_size),
-                Duration::from_nanos(total_inference_time / batch_size),
+                mean_duration(total_tokenization_time, batch_size),
+                mean_duration(total_queue_time, batch_size),
+                mean_duration(total_inference_time, batch_size),
             ),
         )
     };
@@ -444,7 +850,131 @@ async fn rerank(
 
     tracing::info!("Success");
 
-    Ok((headers, Json(response)))
+    Ok((headers, Json(response)).into_response())
+}
+
+/// Index of the first occurrence of each text
+fn first_occurrences(texts: &[String]) -> Vec<usize> {
+    let mut first = HashMap::with_capacity(texts.len());
+    texts
+        .iter()
+        .enumerate()
+        .map(|(index, text)| *first.entry(text.as_str()).or_insert(index))
+        .collect()
+}
+
+/// Scale rerank scores as requested with `score_scale`
+fn scale_scores(scores: Vec<f32>, scale: ScoreScale) -> Vec<f32> {
+    match scale {
+        ScoreScale::Raw => scores,
+        ScoreScale::Sigmoid => scores.into_iter().map(sigmoid).collect(),
+        ScoreScale::Minmax => min_max_normalize(&scores),
+    }
+}
+
+fn sigmoid(logit: f32) -> f32 {
+    1.0 / (1.0 + (-logit).exp())
+}
+
+/// Fuse rerank scores with the prior scores of another scorer: `w * score + (1 - w) * prior`
+///
+/// Both sets of scores are min-max normalized first so that their scales do not matter.
+fn fuse_scores(scores: &[f32], prior_scores: &[f32], weight: f32) -> Vec<f32> {
+    min_max_normalize(scores)
+        .into_iter()
+        .zip(min_max_normalize(prior_scores))
+        .map(|(score, prior_score)| weight * score + (1.0 - weight) * prior_score)
+        .collect()
+}
+
+/// Scale scores to `[0, 1]`. Scores that are all equal carry no ranking signal and become 0
+fn min_max_normalize(scores: &[f32]) -> Vec<f32> {
+    let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+    scores
+        .iter()
+        .map(|score| {
+            if range > 0.0 {
+                (score - min) / range
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Stream the ranks of a rerank request as newline-delimited JSON, each as soon as it is scored
+///
+/// Ranks are written in completion order and are not sorted. If a text fails, the last line is
+/// the error. The batch slot is held until the stream ends.
+fn rerank_stream(
+    infer: Infer,
+    req: RerankRequest,
+    permit_wait_timeout: Option<Duration>,
+    batch_slot: Option<OwnedSemaphorePermit>,
+) -> Response {
+    let raw_scores = req.raw_scores || req.score_scale == ScoreScale::Sigmoid;
+    let mut scores: FuturesUnordered<_> = req
+        .texts
+        .iter()
+        .enumerate()
+        .map(|(index, text)| {
+            let infer = infer.clone();
+            let input = (req.query.clone(), text.clone());
+            async move {
+                let permit = infer.acquire_permit_timeout(permit_wait_timeout).await?;
+                let response = infer
+                    .predict(input, req.truncate, true, raw_scores, permit)
+                    .await?;
+                // `minmax` is not allowed when streaming
+                let score = match req.score_scale {
+                    ScoreScale::Sigmoid => sigmoid(response.results[0]),
+                    _ => response.results[0],
+                };
+                Ok::<_, TextEmbeddingsError>((index, score))
+            }
+        })
+        .collect();
+
+    let (sender, receiver) = futures::channel::mpsc::unbounded::<Result<Bytes, Infallible>>();
+    // Spawned tasks do not inherit the redaction of the request
+    tokio::spawn(redact_inputs(inputs_redacted(), async move {
+        let _batch_slot = batch_slot;
+        let send = |line: String| sender.unbounded_send(Ok(Bytes::from(format!("{line}\n"))));
+
+        while let Some(result) = scores.next().await {
+            let (index, score) = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    let error = serde_json::to_string(&ErrorResponse::from(err)).unwrap();
+                    let _ = send(error);
+                    return;
+                }
+            };
+            let rank = Rank {
+                index,
+                text: req.return_text.then(|| req.texts[index].clone()),
+                score,
+            };
+            let rank = match &req.fields {
+                Some(fields) => serde_json::to_string(&rank.select(fields)),
+                None => serde_json::to_string(&rank),
+            }
+            .unwrap();
+            // The client is gone: dropping the futures cancels the remaining texts
+            if send(rank).is_err() {
+                return;
+            }
+        }
+        metrics::increment_counter!("te_request_success", "method" => "stream");
+    }));
+
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        StreamBody::new(receiver),
+    )
+        .into_response()
 }
 
 /// Get Embeddings. Returns a 424 status code if the model is not an embedding model.
@@ -454,15 +984,20 @@ async fn rerank(
     path = "/embed",
     request_body = EmbedRequest,
     responses(
-    (status = 200, description = "Embeddings", body = EmbedResponse),
+    (status = 200, description = "Embeddings. With `Accept: application/octet-stream`, positional embeddings are \
+    returned as concatenated little-endian float32 values described by the `X-Shape` and `X-Dtype` headers",
+    content(
+    ("application/json" = EmbedResponse),
+    ("application/octet-stream" = Vec<u8>),
+    )),
     (status = 424, description = "Embedding Error", body = ErrorResponse,
-    example = json ! ({"error": "Inference failed", "error_type": "backend"})),
+    example = json ! ({"error": "Inference failed", "error_type": "backend", "code": "backend_error"})),
     (status = 429, description = "Model is overloaded", body = ErrorResponse,
-    example = json ! ({"error": "Model is overloaded", "error_type": "overloaded"})),
+    example = json ! ({"error": "Model is overloaded", "error_type": "overloaded", "code": "overloaded"})),
     (status = 422, description = "Tokenization error", body = ErrorResponse,
-    example = json ! ({"error": "Tokenization error", "error_type": "tokenizer"})),
+    example = json ! ({"error": "Tokenization error", "error_type": "tokenizer", "code": "tokenizer_error"})),
     (status = 413, description = "Batch size error", body = ErrorResponse,
-    example = json ! ({"error": "Batch size error", "error_type": "validation"})),
+    example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
     )
     )]
     #[instrument(
@@ -472,27 +1007,178 @@ async fn rerank(
     async fn embed(
         infer: Extension<Infer>,
         info: Extension<Info>,
+        config: Extension<ServerConfig>,
+        limits: Extension<ConcurrencyLimits>,
+        request_headers: HeaderMap,
         Json(req): Json<EmbedRequest>,
-    ) -> Result<(HeaderMap, Json<EmbedResponse>), (StatusCode, Json<ErrorResponse>)> {
+    ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
         let span = tracing::Span::current();
         let start_time = Instant::now();
+
+        req.validate()?;
+        if req.return_raw_shape && !config.debug_mode {
+            Err(validation::invalid(
+                "return_raw_shape",
+                "requires the server to run with `DEBUG_MODE`".to_string(),
+            ))?;
+        }
+        if let (Some(shards), Some(dim)) = (req.shards, info.embedding_dim) {
+            check_shards(shards, dim)?;
+        }
+        let normalize = req.normalize || req.target_norm.is_some();
+        let instruction = resolve_instruction(
+            &config,
+            req.instruction.as_deref(),
+            req.instruction_key.as_deref(),
+        )?;
+
+        if req.stream {
+            metrics::increment_counter!("te_request_count", "method" => "stream");
+
+            let batch_size = match &req.inputs {
+                EmbedInput::Positional(Input::Batch(inputs)) => inputs.len(),
+                _ => 1,
+            };
+            if batch_size > info.max_client_batch_size {
+                let message = format!(
+                    "batch size {batch_size} > maximum allowed batch size {}",
+                    info.max_client_batch_size
+                );
+                tracing::error!("{message}");
+                metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+                Err(ErrorResponse {
+                    error: message,
+                    error_type: ErrorType::Validation,
+                    code: "batch_too_large".to_string(),
+                })?;
+            }
+            check_empty_batch(&config, batch_size)?;
+            check_response_size(&config, &info, batch_size)?;
+            let batch_slot = match &req.inputs {
+                EmbedInput::Positional(Input::Batch(_)) => limits.acquire_batch()?,
+                _ => None,
+            };
+
+            let instruction = instruction.map(String::from);
+            return Ok(embed_stream(
+                infer.0,
+                req,
+                instruction,
+                config.permit_wait_timeout,
+                batch_slot,
+            ));
+        }
+
+        if let Some(poolings) = &req.poolings {
+            check_poolings(&info, poolings)?;
+        }
+
+        let batch = req.chunk_size.is_some()
+            || req.split_on.is_some()
+            || !matches!(req.inputs, EmbedInput::Positional(Input::Single(_)));
+        let _batch_slot = if batch {
+            limits.acquire_batch()?
+        } else {
+            None
+        };
+
+        if let Some(chunk_size) = req.chunk_size {
+            let (headers, response) = embed_chunked(
+                &infer,
+                &info,
+                &config,
+                &req,
+                instruction,
+                chunk_size,
+                start_time,
+            )
+            .await?;
+            return Ok((headers, Json(response)).into_response());
+        }
     
-        let (response, metadata) = match req.inputs {
+        // Keyed inputs are embedded as a batch and mapped back to their ids
+        let (inputs, mut keys) = match req.inputs {
+            EmbedInput::Positional(inputs) => (inputs, None),
+            EmbedInput::Keyed(inputs) => {
+                let (keys, inputs): (Vec<String>, Vec<String>) = inputs.into_iter().unzip();
+                (Input::Batch(inputs), Some(keys))
+            }
+        };
+
+        // The segments of a split input are embedded as a batch
+        let (inputs, mut segments) = match (&req.split_on, inputs) {
+            (Some(split_on), Input::Single(input)) => {
+                let mut segments = split_input(&input, split_on);
+                if segments.is_empty() {
+                    // Embedding the input returns the empty input error
+                    segments.push(input);
+                }
+                (Input::Batch(segments.clone()), Some(segments))
+            }
+            (_, inputs) => (inputs, None),
+        };
+
+        // Inputs are echoed as sent, before any preprocessing
+        let mut texts = match (&inputs, req.return_text) {
+            (Input::Single(input), true) => vec![input.clone()],
+            (Input::Batch(inputs), true) => inputs.clone(),
+            (_, false) => Vec::new(),
+        };
+
+        let mut languages: Vec<DetectedLanguage> = Vec::new();
+        let mut content_types: Vec<&'static str> = Vec::new();
+        // Inputs as embedded, kept to be explained after the request
+        let mut explained: Vec<String> = Vec::new();
+        let mut hashes: Vec<String> = Vec::new();
+        // Norms and backend output shapes of the embeddings that completed
+        let mut confidences: Vec<f32> = Vec::new();
+        let mut raw_outputs: Vec<RawOutput> = Vec::new();
+        // Indices of the batch inputs that did not complete before the request timeout
+        let mut missing: Vec<usize> = Vec::new();
+        // Errors of the batch inputs that failed, when `partial_failure` is set
+        let mut failed: Vec<(usize, ErrorResponse)> = Vec::new();
+
+        let (embeddings, metadata) = match inputs {
             Input::Single(input) => {
                 metrics::increment_counter!("te_request_count", "method" => "single");
     
-                let compute_chars = input.chars().count();
+                let input = if req.strip_html {
+                    strip_html(&input)
+                } else {
+                    input
+                };
+                if req.detect_language {
+                    languages.push(detect_language(&input));
+                }
+                if req.classify_content {
+                    content_types.push(classify_content(&input));
+                }
+                let text = with_instruction(instruction, &input);
+                if req.return_hash {
+                    hashes.push(content_hash(&text));
+                }
+                if req.explain {
+                    explained.push(text.clone());
+                }
+                let compute_chars = text.chars().count();
+                let input = instructed(instruction, input);
     
                 let permit = infer.try_acquire_permit().map_err(ErrorResponse::from)?;
                 let response = infer
-                    .embed(input, req.truncate, req.normalize, permit)
+                    .embed(input, req.truncate, req.add_special_tokens, normalize, permit)
                     .await
                     .map_err(ErrorResponse::from)?;
+                if req.return_confidence {
+                    confidences.push(response.norm);
+                }
+                if req.return_raw_shape {
+                    raw_outputs.push(raw_output(&infer, &response));
+                }
     
                 metrics::increment_counter!("te_request_success", "method" => "single");
     
                 (
-                    EmbedResponse(vec![response.results]),
+                    vec![response.results],
                     ResponseMetadata::new(
                         compute_chars,
                         response.prompt_tokens,
@@ -507,6 +1193,7 @@ async fn rerank(
                 metrics::increment_counter!("te_request_count", "method" => "batch");
     
                 let batch_size = inputs.len();
+                metrics::histogram!("te_batch_size", batch_size as f64);
                 if batch_size > info.max_client_batch_size {
                     let message = format!(
                         "batch size {batch_size} > maximum allowed batch size {}",
@@ -516,30 +1203,71 @@ async fn rerank(
                     let err = ErrorResponse {
                         error: message,
                         error_type: ErrorType::Validation,
+                        code: "batch_too_large".to_string(),
                     };
                     metrics::increment_counter!("te_request_failure", "err" => "batch_size");
                     Err(err)?;
                 }
+
+                check_empty_batch(&config, batch_size)?;
+                check_response_size(&config, &info, batch_size)?;
     
                 let mut futures = Vec::with_capacity(batch_size);
                 let mut compute_chars = 0;
     
                 for input in inputs {
-                    compute_chars += input.chars().count();
+                    let input = if req.strip_html {
+                        strip_html(&input)
+                    } else {
+                        input
+                    };
+                    if req.detect_language {
+                        languages.push(detect_language(&input));
+                    }
+                    if req.classify_content {
+                        content_types.push(classify_content(&input));
+                    }
+                    let text = with_instruction(instruction, &input);
+                    if req.return_hash {
+                        hashes.push(content_hash(&text));
+                    }
+                    if req.explain {
+                        explained.push(text.clone());
+                    }
+                    compute_chars += text.chars().count();
+                    let input = instructed(instruction, input);
     
                     let local_infer = infer.clone();
+                    let permit_wait_timeout = config.permit_wait_timeout;
                     futures.push(async move {
-                        let permit = local_infer.acquire_permit().await;
+                        let permit = local_infer
+                            .acquire_permit_timeout(permit_wait_timeout)
+                            .await?;
                         local_infer
-                            .embed(input, req.truncate, req.normalize, permit)
+                            .embed(input, req.truncate, req.add_special_tokens, normalize, permit)
                             .await
                     })
                 }
-                let results = join_all(futures)
-                    .await
-                    .into_iter()
-                    .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
-                    .map_err(ErrorResponse::from)?;
+                let results = join_batch(futures, config.request_timeout).await;
+                missing = missing_indices(&results);
+                let results = if req.partial_failure {
+                    let mut completed = Vec::with_capacity(batch_size);
+                    for (i, result) in results.into_iter().enumerate() {
+                        match result {
+                            Some(Ok(response)) => completed.push(response),
+                            Some(Err(err)) => failed.push((i, ErrorResponse::from(err))),
+                            None => {}
+                        }
+                    }
+                    completed
+                } else {
+                    results
+                        .into_iter()
+                        .flatten()
+                        .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
+                        .map_err(ErrorResponse::from)?
+                };
+                check_batch_completed(&results, &missing)?;
     
                 let mut embeddings = Vec::with_capacity(batch_size);
                 let mut total_tokenization_time = 0;
@@ -552,252 +1280,2897 @@ async fn rerank(
                     total_queue_time += r.queue.as_nanos() as u64;
                     total_inference_time += r.inference.as_nanos() as u64;
                     total_compute_tokens += r.prompt_tokens;
+                    if req.return_confidence {
+                        confidences.push(r.norm);
+                    }
+                    if req.return_raw_shape {
+                        raw_outputs.push(raw_output(&infer, &r));
+                    }
                     embeddings.push(r.results);
                 }
-                let batch_size = batch_size as u64;
+                let batch_size = embeddings.len() as u64;
     
                 metrics::increment_counter!("te_request_success", "method" => "batch");
     
                 (
-                    EmbedResponse(embeddings),
+                    embeddings,
                     ResponseMetadata::new(
                         compute_chars,
                         total_compute_tokens,
                         start_time,
-                        Duration::from_nanos(total_tokenization_time / batch_size),
-                        Duration::from_nanos(total_queue_time / batch_size),
-                        Duration::from_nanos(total_inference_time / batch_size),
+                        mean_duration(total_tokenization_time, batch_size),
+                        mean_duration(total_queue_time, batch_size),
+                        mean_duration(total_inference_time, batch_size),
                     ),
                 )
             }
         };
+
+        let embeddings = match req.target_norm {
+            // Embeddings are already normalized
+            Some(target_norm) => embeddings
+                .into_iter()
+                .map(|embedding| embedding.into_iter().map(|v| v * target_norm).collect())
+                .collect(),
+            None => embeddings,
+        };
+
+        let embeddings = match req.clamp {
+            Some([min, max]) => embeddings
+                .into_iter()
+                .map(|embedding| embedding.into_iter().map(|v| v.clamp(min, max)).collect())
+                .collect(),
+            None => embeddings,
+        };
     
+        if !missing.is_empty() {
+            // Only return the metadata of the inputs that completed
+            let completed = |i: &usize| missing.binary_search(i).is_err();
+            keys = keys.map(|keys| remove_indices(keys, completed));
+            segments = segments.map(|segments| remove_indices(segments, completed));
+            languages = remove_indices(languages, completed);
+            content_types = remove_indices(content_types, completed);
+            texts = remove_indices(texts, completed);
+            hashes = remove_indices(hashes, completed);
+            explained = remove_indices(explained, completed);
+        }
+        let attributions = explain_inputs(
+            &infer,
+            &config,
+            explained,
+            req.truncate,
+            req.add_special_tokens,
+        )
+        .await?;
+
+        let response = if req.partial_failure {
+            aligned_embeddings(embeddings, &missing, failed)
+        } else if let Some(segments) = segments {
+            EmbedResponse::Segmented(
+                segments
+                    .into_iter()
+                    .zip(embeddings)
+                    .map(|(text, embedding)| Segment { text, embedding })
+                    .collect(),
+            )
+        } else if let Some(poolings) = &req.poolings {
+            // All the requested poolings are the pooling of the model
+            let pooled = embeddings.into_iter().map(|embedding| {
+                poolings
+                    .iter()
+                    .map(|pooling| (pooling.clone(), embedding.clone()))
+                    .collect::<BTreeMap<_, _>>()
+            });
+            match keys {
+                Some(keys) => EmbedResponse::KeyedPooled(keys.into_iter().zip(pooled).collect()),
+                None => EmbedResponse::Pooled(pooled.collect()),
+            }
+        } else if let Some(shards) = req.shards {
+            // Already checked before inference if the model embedding dimension is known
+            if let Some(embedding) = embeddings.first() {
+                check_shards(shards, embedding.len())?;
+            }
+            let sharded = embeddings
+                .into_iter()
+                .map(|embedding| shard(&embedding, shards));
+            match keys {
+                Some(keys) => EmbedResponse::KeyedSharded(keys.into_iter().zip(sharded).collect()),
+                None => EmbedResponse::Sharded(sharded.collect()),
+            }
+        } else if req.detect_language
+            || req.classify_content
+            || req.top_dims.is_some()
+            || req.return_text
+            || req.return_hash
+            || req.return_confidence
+            || req.return_raw_shape
+            || req.explain
+        {
+            // Languages and content types are only detected, texts only kept, inputs only hashed
+            // and explained and confidences and raw outputs only returned if requested
+            let mut languages = languages.into_iter();
+            let mut content_types = content_types.into_iter();
+            let mut texts = texts.into_iter();
+            let mut hashes = hashes.into_iter();
+            let mut attributions = attributions.into_iter();
+            let mut confidences = confidences.into_iter();
+            let mut raw_outputs = raw_outputs.into_iter();
+            let embeddings = embeddings.into_iter().map(|embedding| Embedding {
+                text: texts.next(),
+                hash: hashes.next(),
+                language: languages.next(),
+                content_type: content_types.next(),
+                top_dims: req.top_dims.map(|k| top_dims(&embedding, k)),
+                attribution: attributions.next(),
+                confidence: confidences.next(),
+                raw_output: raw_outputs.next(),
+                embedding,
+            });
+            match keys {
+                Some(keys) => {
+                    EmbedResponse::KeyedDetailed(keys.into_iter().zip(embeddings).collect())
+                }
+                None => EmbedResponse::Detailed(embeddings.collect()),
+            }
+        } else {
+            match keys {
+                Some(keys) => EmbedResponse::Keyed(keys.into_iter().zip(embeddings).collect()),
+                None => EmbedResponse::Positional(embeddings),
+            }
+        };
+
         metadata.record_span(&span);
         metadata.record_metrics();
     
-        let headers = HeaderMap::from(metadata);
+        let used_tokens = metadata.compute_tokens;
+        let mut headers = HeaderMap::from(metadata);
+        if config.report_truncation {
+            truncation_headers(&mut headers, used_tokens);
+        }
+        let status = partial_status(&missing, &mut headers);
     
         tracing::info!("Success");
-    
-        Ok((headers, Json(response)))
+
+        match response {
+            // Keyed and detailed responses are always returned as JSON
+            EmbedResponse::Positional(embeddings) if accepts_octet_stream(&request_headers) => {
+                let dim = embeddings.first().map(|e| e.len()).unwrap_or(0);
+                headers.insert(
+                    "x-shape",
+                    format!("[{}, {dim}]", embeddings.len()).parse().unwrap(),
+                );
+                headers.insert("x-dtype", HeaderValue::from_static("float32"));
+                Ok((status, headers, embeddings_to_bytes(&embeddings)).into_response())
+            }
+            response => Ok((status, headers, Json(response)).into_response()),
+        }
     }
-    
-/// Get Embeddings in weaviate format. Returns a 424 status code if the model is not an embedding model.
-#[utoipa::path(
-post,
-tag = "Text Embeddings Inference",
-path = "/vectors",
-request_body = EmbedRequest,
-responses(
-(status = 200, description = "Embeddings", body = EmbedResponse),
-(status = 424, description = "Embedding Error", body = ErrorResponse,
-example = json ! ({"error": "Inference failed", "error_type": "backend"})),
-(status = 429, description = "Model is overloaded", body = ErrorResponse,
-example = json ! ({"error": "Model is overloaded", "error_type": "overloaded"})),
-(status = 422, description = "Tokenization error", body = ErrorResponse,
-example = json ! ({"error": "Tokenization error", "error_type": "tokenizer"})),
-(status = 413, description = "Batch size error", body = ErrorResponse,
-example = json ! ({"error": "Batch size error", "error_type": "validation"})),
-)
-)]
-#[instrument(
+
+/// Realign the embeddings of a batch with its inputs, with `null` and an error for the inputs
+/// that timed out or failed
+fn aligned_embeddings(
+    embeddings: Vec<Vec<f32>>,
+    missing: &[usize],
+    failed: Vec<(usize, ErrorResponse)>,
+) -> EmbedResponse {
+    let len = embeddings.len() + missing.len() + failed.len();
+    let mut embeddings = embeddings.into_iter();
+    let mut failed = failed.into_iter().peekable();
+    let mut aligned = Vec::with_capacity(len);
+    let mut errors = Vec::with_capacity(len);
+    for i in 0..len {
+        if missing.binary_search(&i).is_ok() {
+            aligned.push(None);
+            errors.push(Some(ErrorResponse {
+                error: "Request timed out before the input completed".to_string(),
+                error_type: ErrorType::Overloaded,
+                code: "request_timeout".to_string(),
+            }));
+        } else if let Some((_, err)) = failed.next_if(|(index, _)| *index == i) {
+            aligned.push(None);
+            errors.push(Some(err));
+        } else {
+            aligned.push(embeddings.next());
+            errors.push(None);
+        }
+    }
+    EmbedResponse::PartialFailure {
+        embeddings: aligned,
+        errors,
+    }
+}
+
+/// Reject empty batches when `REJECT_EMPTY_BATCH` is set
+///
+/// Otherwise they return an empty result with zeroed metadata.
+fn check_empty_batch(config: &ServerConfig, batch_size: usize) -> Result<(), ErrorResponse> {
+    if batch_size == 0 && config.reject_empty_batch {
+        let message = "batch is empty".to_string();
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        return Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::InvalidParameter,
+            code: "empty_batch".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Pooling strategies of embedding models. The ones the model does not use are reported as
+/// unavailable rather than unknown
+const POOLINGS: [&str; 4] = ["cls", "mean", "splade", "last_token"];
+
+/// Check that the backend can produce all the requested poolings from one forward pass
+///
+/// Backends only return the pooled output of the model, so the only pooling they can produce
+/// is the one the model was loaded with.
+fn check_poolings(info: &Info, poolings: &[String]) -> Result<(), ErrorResponse> {
+    let model_pooling = match &info.model_type {
+        ModelType::Embedding(model) => &model.pooling,
+        // Non-embedding models fail when embedding
+        _ => return Ok(()),
+    };
+
+    for pooling in poolings {
+        if !POOLINGS.contains(&pooling.as_str()) {
+            let message = format!("Unknown pooling `{pooling}`, expected one of {POOLINGS:?}");
+            tracing::error!("{message}");
+            metrics::increment_counter!("te_request_failure", "err" => "validation");
+            return Err(ErrorResponse {
+                error: message,
+                error_type: ErrorType::InvalidParameter,
+                code: "invalid_poolings".to_string(),
+            });
+        }
+        if pooling != model_pooling {
+            let message = format!(
+                "The backend only produces `{model_pooling}` pooling and cannot compute `{pooling}` \
+                from the same forward pass"
+            );
+            tracing::error!("{message}");
+            metrics::increment_counter!("te_request_failure", "err" => "pooling");
+            return Err(ErrorResponse {
+                error: message,
+                error_type: ErrorType::Backend,
+                code: "pooling_unavailable".to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Embed the chunks of each input, grouped by input
+///
+/// Inputs are split on token boundaries into windows of `chunk_size` tokens overlapping by
+/// `chunk_overlap` tokens, and each window is embedded as the text it spans. Windows are
+/// shortened to fit in the model input length with the instruction and the special tokens.
+#[allow(clippy::too_many_arguments)]
+async fn embed_chunked(
+    infer: &Infer,
+    info: &Info,
+    config: &ServerConfig,
+    req: &EmbedRequest,
+    instruction: Option<&str>,
+    chunk_size: usize,
+    start_time: Instant,
+) -> Result<(HeaderMap, EmbedResponse), ErrorResponse> {
+    metrics::increment_counter!("te_request_count", "method" => "chunked");
+
+    let inputs: Vec<&String> = match &req.inputs {
+        EmbedInput::Positional(Input::Single(input)) => vec![input],
+        EmbedInput::Positional(Input::Batch(inputs)) => inputs.iter().collect(),
+        EmbedInput::Keyed(_) => {
+            let message = "`chunk_size` cannot be used with keyed inputs".to_string();
+            tracing::error!("{message}");
+            metrics::increment_counter!("te_request_failure", "err" => "validation");
+            return Err(ErrorResponse {
+                error: message,
+                error_type: ErrorType::InvalidParameter,
+                code: "invalid_chunk_size".to_string(),
+            });
+        }
+    };
+    let inputs: Vec<String> = inputs
+        .into_iter()
+        .map(|input| {
+            if req.strip_html {
+                strip_html(input)
+            } else {
+                input.clone()
+            }
+        })
+        .collect();
+
+    // Every chunk is embedded with the instruction and the special tokens, so only the rest of
+    // the model input length is left for its own tokens
+    let reserved = infer
+        .count_tokens(
+            instruction.unwrap_or_default().to_string(),
+            req.add_special_tokens,
+        )
+        .await
+        .map_err(ErrorResponse::from)?;
+    let chunk_size = chunk_size.min(info.max_input_length.saturating_sub(reserved));
+    if req.chunk_overlap >= chunk_size {
+        let message = format!(
+            "`chunk_overlap` ({}) must be lower than the {chunk_size} tokens left for each chunk \
+            by the instruction and the special tokens",
+            req.chunk_overlap
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "validation");
+        return Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::InvalidParameter,
+            code: "invalid_chunk_size".to_string(),
+        });
+    }
+
+    let offsets = inputs
+        .iter()
+        .map(|input| infer.token_offsets(input.clone()));
+    let offsets = join_all(offsets)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, TextEmbeddingsError>>()
+        .map_err(ErrorResponse::from)?;
+
+    // Input index, token span and byte span of each chunk
+    let step = chunk_size - req.chunk_overlap;
+    let mut spans = Vec::new();
+    for (index, offsets) in offsets.iter().enumerate() {
+        let mut token_start = 0;
+        while token_start < offsets.len() {
+            let token_end = (token_start + chunk_size).min(offsets.len());
+            let bytes = (offsets[token_start].0, offsets[token_end - 1].1);
+            spans.push((index, token_start, token_end, bytes));
+            if token_end == offsets.len() {
+                break;
+            }
+            token_start += step;
+        }
+    }
+
+    let batch_size = spans.len();
+    if batch_size > info.max_client_batch_size {
+        let message = format!(
+            "number of chunks {batch_size} > maximum allowed batch size {}",
+            info.max_client_batch_size
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        return Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Validation,
+            code: "batch_too_large".to_string(),
+        });
+    }
+
+    let mut compute_chars = 0;
+    let futures: Vec<_> = spans
+        .iter()
+        .map(|(index, _, _, (start, end))| {
+            let input = inputs[*index][*start..*end].to_string();
+            compute_chars += with_instruction(instruction, &input).chars().count();
+            let input = instructed(instruction, input);
+            async move {
+                let permit = infer
+                    .acquire_permit_timeout(config.permit_wait_timeout)
+                    .await?;
+                infer
+                    .embed(input, true, req.add_special_tokens, req.normalize, permit)
+                    .await
+            }
+        })
+        .collect();
+    let results = join_all(futures)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
+        .map_err(ErrorResponse::from)?;
+
+    let mut groups: Vec<ChunkedEmbeddings> = (0..inputs.len())
+        .map(|index| ChunkedEmbeddings {
+            index,
+            chunks: Vec::new(),
+        })
+        .collect();
+    let mut total_tokenization_time = 0;
+    let mut total_queue_time = 0;
+    let mut total_inference_time = 0;
+    let mut total_compute_tokens = 0;
+
+    for ((index, token_start, token_end, (start, end)), r) in spans.into_iter().zip(results) {
+        total_tokenization_time += r.tokenization.as_nanos() as u64;
+        total_queue_time += r.queue.as_nanos() as u64;
+        total_inference_time += r.inference.as_nanos() as u64;
+        total_compute_tokens += r.prompt_tokens;
+
+        let input = &inputs[index];
+        groups[index].chunks.push(EmbeddingChunk {
+            embedding: r.results,
+            token_start,
+            token_end,
+            char_start: input[..start].chars().count(),
+            char_end: input[..end].chars().count(),
+        });
+    }
+
+    metrics::increment_counter!("te_request_success", "method" => "chunked");
+
+    // Inputs without tokens have no chunks
+    let batch_size = batch_size.max(1) as u64;
+    let metadata = ResponseMetadata::new(
+        compute_chars,
+        total_compute_tokens,
+        start_time,
+        mean_duration(total_tokenization_time, batch_size),
+        mean_duration(total_queue_time, batch_size),
+        mean_duration(total_inference_time, batch_size),
+    );
+    metadata.record_span(&tracing::Span::current());
+    metadata.record_metrics();
+
+    Ok((HeaderMap::from(metadata), EmbedResponse::Chunked(groups)))
+}
+
+/// Stream the embeddings of an `/embed` request as newline-delimited JSON
+///
+/// Embeddings are written whole, one per line, in completion order and are matched to their
+/// input with `index`. If an input fails, the last line is the error. The batch slot is held
+/// until the stream ends.
+fn embed_stream(
+    infer: Infer,
+    req: EmbedRequest,
+    instruction: Option<String>,
+    permit_wait_timeout: Option<Duration>,
+    batch_slot: Option<OwnedSemaphorePermit>,
+) -> Response {
+    let inputs = match req.inputs {
+        EmbedInput::Positional(Input::Single(input)) => vec![input],
+        EmbedInput::Positional(Input::Batch(inputs)) => inputs,
+        EmbedInput::Keyed(_) => unreachable!("keyed inputs cannot be streamed"),
+    };
+    let normalize = req.normalize || req.target_norm.is_some();
+    let mut embeddings: FuturesUnordered<_> = inputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let infer = infer.clone();
+            let input = if req.strip_html {
+                strip_html(&input)
+            } else {
+                input
+            };
+            let input = instructed(instruction.as_deref(), input);
+            async move {
+                let permit = infer.acquire_permit_timeout(permit_wait_timeout).await?;
+                let response = infer
+                    .embed(
+                        input,
+                        req.truncate,
+                        req.add_special_tokens,
+                        normalize,
+                        permit,
+                    )
+                    .await?;
+                Ok::<_, TextEmbeddingsError>((index, response.results))
+            }
+        })
+        .collect();
+
+    let (sender, receiver) = futures::channel::mpsc::unbounded::<Result<Bytes, Infallible>>();
+    // Spawned tasks do not inherit the redaction of the request
+    tokio::spawn(redact_inputs(inputs_redacted(), async move {
+        let _batch_slot = batch_slot;
+        let send = |line: String| sender.unbounded_send(Ok(Bytes::from(format!("{line}\n"))));
+
+        while let Some(result) = embeddings.next().await {
+            let (index, mut values) = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    let error = serde_json::to_string(&ErrorResponse::from(err)).unwrap();
+                    let _ = send(error);
+                    return;
+                }
+            };
+            if let Some(target_norm) = req.target_norm {
+                values.iter_mut().for_each(|v| *v *= target_norm);
+            }
+            if let Some([min, max]) = req.clamp {
+                values.iter_mut().for_each(|v| *v = v.clamp(min, max));
+            }
+            let embedding = StreamedEmbedding { index, values };
+            // The client is gone: dropping the futures cancels the remaining inputs
+            if send(serde_json::to_string(&embedding).unwrap()).is_err() {
+                return;
+            }
+        }
+        metrics::increment_counter!("te_request_success", "method" => "stream");
+    }));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    (headers, StreamBody::new(receiver)).into_response()
+}
+
+/// Get the instruction of an `/embed` request, either sent as is or selected from the registry
+fn resolve_instruction<'a>(
+    config: &'a ServerConfig,
+    instruction: Option<&'a str>,
+    key: Option<&str>,
+) -> Result<Option<&'a str>, ErrorResponse> {
+    let (message, code) = match (instruction, key) {
+        (instruction, None) => return Ok(instruction),
+        (Some(_), Some(_)) => (
+            "`instruction` and `instruction_key` cannot be set together".to_string(),
+            "conflicting_instruction",
+        ),
+        (None, Some(key)) => match config.instructions.get(key) {
+            Some(instruction) => return Ok(Some(instruction)),
+            None => {
+                let mut keys: Vec<&str> = config.instructions.keys().map(String::as_str).collect();
+                keys.sort_unstable();
+                (
+                    format!(
+                        "Unknown `instruction_key` `{key}`. Available instructions: {}",
+                        keys.join(", ")
+                    ),
+                    "unknown_instruction",
+                )
+            }
+        },
+    };
+    tracing::error!("{message}");
+    metrics::increment_counter!("te_request_failure", "err" => "validation");
+    Err(ErrorResponse {
+        error: message,
+        error_type: ErrorType::InvalidParameter,
+        code: code.to_string(),
+    })
+}
+
+/// Text of an input with its instruction prepended
+fn with_instruction(instruction: Option<&str>, input: &str) -> String {
+    format!("{}{input}", instruction.unwrap_or_default())
+}
+
+/// Input to embed with its instruction prepended
+///
+/// Only the input is truncated, so that the instruction is always kept whole.
+fn instructed(instruction: Option<&str>, input: String) -> EncodingInput {
+    match instruction {
+        Some(instruction) => EncodingInput::Prefixed(instruction.to_string(), input),
+        None => EncodingInput::Single(input),
+    }
+}
+
+/// Run the futures of a batch concurrently until they are all done or `timeout` is reached
+///
+/// Results are returned in input order, with `None` for the futures that did not complete in
+/// time. They are dropped, which cancels them.
+async fn join_batch<F: Future>(
+    futures: Vec<F>,
+    timeout: Option<Duration>,
+) -> Vec<Option<F::Output>> {
+    let mut results: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    let mut pending: FuturesUnordered<_> = futures
+        .into_iter()
+        .enumerate()
+        .map(|(i, future)| async move { (i, future.await) })
+        .collect();
+
+    let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+    loop {
+        let next = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, pending.next()).await {
+                Ok(next) => next,
+                Err(_) => break,
+            },
+            None => pending.next().await,
+        };
+        match next {
+            Some((i, result)) => results[i] = Some(result),
+            None => break,
+        }
+    }
+    results
+}
+
+fn missing_indices<T>(results: &[Option<T>]) -> Vec<usize> {
+    results
+        .iter()
+        .enumerate()
+        .filter_map(|(i, result)| result.is_none().then_some(i))
+        .collect()
+}
+
+/// Fail the request if no input of a batch completed before the request timeout
+fn check_batch_completed<T>(results: &[T], missing: &[usize]) -> Result<(), ErrorResponse> {
+    if results.is_empty() && !missing.is_empty() {
+        let message = "Request timed out before any input completed".to_string();
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "timeout");
+        return Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Overloaded,
+            code: "request_timeout".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Keep the values whose index passes `keep`
+fn remove_indices<T>(values: Vec<T>, keep: impl Fn(&usize) -> bool) -> Vec<T> {
+    values
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, value)| keep(&i).then_some(value))
+        .collect()
+}
+
+/// Set the `X-Truncated`, `X-Input-Tokens` and `X-Used-Tokens` headers
+///
+/// The tokens dropped by truncation are counted by [`count_truncation`] from the encodings of the
+/// inputs. Only a prefix of large inputs is tokenized, so `X-Input-Tokens` only counts the tokens
+/// of that prefix.
+fn truncation_headers(headers: &mut HeaderMap, used_tokens: usize) {
+    let truncated_tokens = truncated_tokens();
+    let input_tokens = used_tokens + truncated_tokens;
+    let truncated = truncated_tokens > 0;
+    headers.insert("x-truncated", truncated.to_string().parse().unwrap());
+    headers.insert("x-input-tokens", input_tokens.to_string().parse().unwrap());
+    headers.insert("x-used-tokens", used_tokens.to_string().parse().unwrap());
+}
+
+/// `206 Partial Content` with the missing indices in `x-missing-indices` if a batch timed out
+fn partial_status(missing: &[usize], headers: &mut HeaderMap) -> StatusCode {
+    if missing.is_empty() {
+        return StatusCode::OK;
+    }
+    metrics::increment_counter!("te_request_partial");
+    let missing: Vec<String> = missing.iter().map(usize::to_string).collect();
+    headers.insert("x-missing-indices", missing.join(",").parse().unwrap());
+    StatusCode::PARTIAL_CONTENT
+}
+
+/// Get the `k` dimensions of an embedding with the highest absolute value
+fn top_dims(embedding: &[f32], k: usize) -> Vec<Dimension> {
+    let mut dims: Vec<Dimension> = embedding
+        .iter()
+        .enumerate()
+        .map(|(index, value)| Dimension {
+            index,
+            value: *value,
+        })
+        .collect();
+    dims.sort_by(|a, b| b.value.abs().total_cmp(&a.value.abs()));
+    dims.truncate(k);
+    dims
+}
+
+/// Check that embeddings of dimension `dim` can be split into `shards` non-empty shards
+fn check_shards(shards: usize, dim: usize) -> Result<(), ErrorResponse> {
+    if shards > dim {
+        return Err(validation::invalid(
+            "shards",
+            format!("must be at most the embedding dimension ({dim}), got {shards}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Split an embedding into `shards` contiguous sub-vectors whose lengths differ by at most one
+fn shard(embedding: &[f32], shards: usize) -> Vec<Vec<f32>> {
+    let (len, longer) = (embedding.len() / shards, embedding.len() % shards);
+    let mut start = 0;
+    (0..shards)
+        .map(|i| {
+            let end = start + len + usize::from(i < longer);
+            let shard = embedding[start..end].to_vec();
+            start = end;
+            shard
+        })
+        .collect()
+}
+
+/// Shape and data type of the backend output an embedding was taken from
+fn raw_output(infer: &Infer, response: &InferResponse) -> RawOutput {
+    RawOutput {
+        shape: response.raw_shape.to_vec(),
+        dtype: infer.dtype().to_string(),
+    }
+}
+
+/// Hex-encoded SHA-256 of an input
+fn content_hash(input: &str) -> String {
+    format!("{:x}", Sha256::digest(input))
+}
+
+/// Get the token contributing the most to the embedding of each input
+async fn explain_inputs(
+    infer: &Infer,
+    config: &ServerConfig,
+    inputs: Vec<String>,
+    truncate: bool,
+    add_special_tokens: bool,
+) -> Result<Vec<TokenAttribution>, ErrorResponse> {
+    let attributions = inputs.into_iter().map(|input| {
+        infer.explain(
+            input,
+            truncate,
+            add_special_tokens,
+            config.permit_wait_timeout,
+        )
+    });
+    join_all(attributions)
+        .await
+        .into_iter()
+        .map(|attribution| {
+            let attribution = attribution.map_err(ErrorResponse::from)?;
+            Ok(TokenAttribution {
+                index: attribution.index,
+                token: attribution.token,
+                saliency: attribution.saliency,
+            })
+        })
+        .collect()
+}
+
+/// Check if the client asked for a binary response
+fn accepts_octet_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("application/octet-stream"))
+}
+
+/// Concatenate embeddings as little-endian `f32` values
+fn embeddings_to_bytes(embeddings: &[Vec<f32>]) -> Vec<u8> {
+    let size = embeddings.iter().map(|e| e.len()).sum::<usize>();
+    let mut bytes = Vec::with_capacity(size * std::mem::size_of::<f32>());
+    for v in embeddings.iter().flatten() {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes
+}
+
+/// Concatenate embeddings as little-endian `f16` values
+fn embeddings_to_f16_bytes(embeddings: &[Vec<f32>]) -> Vec<u8> {
+    let size = embeddings.iter().map(|e| e.len()).sum::<usize>();
+    let mut bytes = Vec::with_capacity(size * std::mem::size_of::<f16>());
+    for v in embeddings.iter().flatten() {
+        bytes.extend_from_slice(&f16::from_f32(*v).to_le_bytes());
+    }
+    bytes
+}
+    
+/// Read and deserialize the body of a Weaviate request
+///
+/// With `STREAM_PARSE`, the body is deserialized on a blocking thread as its chunks are received
+/// instead of being buffered first. Both paths reject bodies larger than [`MAX_BODY_BYTES`].
+async fn read_weaviate_body<T: DeserializeOwned + Send + 'static>(
+    config: &ServerConfig,
+    headers: &HeaderMap,
+    request: Request<Body>,
+) -> Result<T, (StatusCode, Json<WeaviateErrorResponse>)> {
+    if config.stream_parse && !config.transcode_charset {
+        // `BodyStream` is not subject to the default body limit
+        let body = BodyStream::from_request(request, &()).await.unwrap();
+        let (result, too_large) = tokio::task::spawn_blocking(move || {
+            let mut reader = std::io::Read::take(
+                BodyReader {
+                    chunks: futures::executor::block_on_stream(body),
+                    chunk: Bytes::new(),
+                },
+                MAX_BODY_BYTES as u64 + 1,
+            );
+            let result = serde_json::from_reader(&mut reader);
+            (result, reader.limit() == 0)
+        })
+        .await
+        .expect("Body parsing task panicked. This is a bug.");
+
+        let error = |status: StatusCode, message: String| {
+            metrics::increment_counter!("te_request_failure", "err" => "validation");
+            (
+                status,
+                Json(WeaviateErrorResponse::new(
+                    message,
+                    config.weaviate_protocol,
+                )),
+            )
+        };
+        if too_large {
+            return Err(error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Request body is larger than {MAX_BODY_BYTES} bytes"),
+            ));
+        }
+        return result.map_err(|err| error(StatusCode::BAD_REQUEST, err.to_string()));
+    }
+
+    let body = Bytes::from_request(request, &())
+        .await
+        .map_err(|rejection| {
+            (
+                rejection.status(),
+                Json(WeaviateErrorResponse::new(
+                    rejection.body_text(),
+                    config.weaviate_protocol,
+                )),
+            )
+        })?;
+    parse_weaviate_body(config, headers, &body)
+}
+
+/// Maximum size of a request body, the default body limit of axum
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Blocking reader of the chunks of a request body
+struct BodyReader {
+    chunks: futures::executor::BlockingStream<BodyStream>,
+    /// Rest of the chunk being read
+    chunk: Bytes,
+}
+
+impl std::io::Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.chunks.next() {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(err)) => return Err(std::io::Error::new(std::io::ErrorKind::Other, err)),
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+/// Deserialize the body of a Weaviate request, answering `400` with the reason if it is invalid
+fn parse_weaviate_body<T: DeserializeOwned>(
+    config: &ServerConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<T, (StatusCode, Json<WeaviateErrorResponse>)> {
+    let bad_request = |message: String| {
+        metrics::increment_counter!("te_request_failure", "err" => "validation");
+        (
+            StatusCode::BAD_REQUEST,
+            Json(WeaviateErrorResponse::new(
+                message,
+                config.weaviate_protocol,
+            )),
+        )
+    };
+    let body = decode_body(headers, body, config.transcode_charset).map_err(bad_request)?;
+    serde_json::from_str(&body).map_err(|_| bad_request("Invalid request body".to_string()))
+}
+
+/// Decode a request body to text
+///
+/// With `TRANSCODE_CHARSET`, the body is decoded from the charset of the `Content-Type` header.
+/// Otherwise it must be UTF-8.
+fn decode_body(headers: &HeaderMap, body: &[u8], transcode: bool) -> Result<String, String> {
+    let charset = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|content_type| {
+            content_type.split(';').skip(1).find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let value = value.trim().trim_matches('"');
+                name.trim().eq_ignore_ascii_case("charset").then_some(value)
+            })
+        });
+
+    let encoding = match charset {
+        Some(charset) if transcode => Encoding::for_label(charset.as_bytes())
+            .ok_or_else(|| format!("Unsupported charset `{charset}`"))?,
+        _ => UTF_8,
+    };
+    if encoding == UTF_8 {
+        return std::str::from_utf8(body)
+            .map(str::to_string)
+            .map_err(|err| {
+                format!(
+                    "Request body is not valid UTF-8: invalid byte sequence at byte {}",
+                    err.valid_up_to()
+                )
+            });
+    }
+
+    let (text, malformed) = encoding.decode_without_bom_handling(body);
+    if malformed {
+        return Err(format!(
+            "Request body is not valid {}: it contains invalid byte sequences",
+            encoding.name()
+        ));
+    }
+    Ok(text.into_owned())
+}
+
+/// Get Embeddings in weaviate format. Returns a 424 status code if the model is not an embedding model.
+///
+/// With `probe`, only the dimension of the embeddings is returned.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/vectors",
+request_body = EmbedWeaviateRequest,
+responses(
+(status = 200, description = "Embeddings", body = EmbedWeaviateResponse),
+(status = 400, description = "Invalid request body", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Invalid request body"}]})),
+(status = 500, description = "Embedding Error", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Inference failed"}]})),
+(status = 429, description = "Model is overloaded", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Model is overloaded"}]})),
+(status = 422, description = "Tokenization error", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Tokenization error"}]})),
+)
+)]
+#[instrument(
     skip_all,
     fields(total_time, tokenization_time, queue_time, inference_time,)
 )]
 async fn weaviate_embed(
     infer: Extension<Infer>,
     info: Extension<Info>,
-    body: Bytes,
-) -> Result<(HeaderMap, Json<EmbedWeaviateResponse>), (StatusCode, Json<ErrorResponse>)> {
-    let req = match from_slice::<EmbedWeaviateRequest>(&body) {
-        Ok(req) => req,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Invalid request body".to_string(),
-                    error_type: ErrorType::Validation,
-                }),
-            ));
+    config: Extension<ServerConfig>,
+    dims: Extension<ProbedDimensions>,
+    request_headers: HeaderMap,
+    request: Request<Body>,
+) -> Result<(HeaderMap, Json<EmbedWeaviateResponse>), (StatusCode, Json<WeaviateErrorResponse>)> {
+    let protocol = config.weaviate_protocol;
+
+    let req: EmbedWeaviateRequest = read_weaviate_body(&config, &request_headers, request).await?;
+
+    if let (WeaviateProtocol::V2, Some(vectorize_config)) = (protocol, &req.config) {
+        if let (Some(pooling_strategy), ModelType::Embedding(model)) =
+            (&vectorize_config.pooling_strategy, &info.model_type)
+        {
+            if pooling_strategy != &model.pooling {
+                tracing::debug!(
+                    "Ignoring `pooling_strategy` {pooling_strategy}, the model uses {} pooling",
+                    model.pooling
+                );
+            }
         }
-    };
+    }
+
+    if req.probe {
+        let dim = probe_dimension(&infer, &info, &dims)
+            .await
+            .map_err(|err| weaviate_error(err, protocol))?;
+        let json_response = EmbedWeaviateResponse {
+            text: None,
+            vector: None,
+            dim,
+        };
+        return Ok((HeaderMap::new(), Json(json_response)));
+    }
 
     let span = tracing::Span::current();
     let start_time = Instant::now();
 
-    let permit = infer.try_acquire_permit().map_err(ErrorResponse::from)?;
+    let input = if req.strip_html {
+        strip_html(&req.text)
+    } else {
+        req.text.clone()
+    };
+    let prefix = config.weaviate_prefix(req.task);
+
+    let input = instructed(prefix, input);
+
+    let permit = infer
+        .try_acquire_permit()
+        .map_err(|e| weaviate_error(ErrorResponse::from(e), protocol))?;
+    let response = infer
+        .embed(input, req.truncate, true, req.normalize, permit)
+        .await
+        .map_err(|e| {
+            error!("Error during embedding: {:?}", e);
+            weaviate_error(ErrorResponse::from(e), protocol)
+        })?;
+
+    let vector = response.results; 
+    let dim = vector.len();
+
+    let json_response = EmbedWeaviateResponse {
+        text: Some(req.text),
+        vector: Some(vector),
+        dim,
+    };
+
+    let mut headers = HeaderMap::new();
+    if config.report_truncation {
+        truncation_headers(&mut headers, response.prompt_tokens);
+    }
+
+    Ok((headers, Json(json_response)))
+}
+
+/// Input embedded to learn the dimension of the embeddings of a model
+const PROBE_INPUT: &str = "dimension";
+
+/// Embedding dimension of each model, learned from the first probe request
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProbedDimensions(Arc<std::sync::RwLock<HashMap<String, usize>>>);
+
+impl ProbedDimensions {
+    fn get(&self, model_id: &str) -> Option<usize> {
+        self.0.read().unwrap().get(model_id).copied()
+    }
+
+    fn insert(&self, model_id: String, dim: usize) {
+        self.0.write().unwrap().insert(model_id, dim);
+    }
+
+    fn forget(&self, model_id: &str) {
+        self.0.write().unwrap().remove(model_id);
+    }
+}
+
+/// Get the embedding dimension of the served model, embedding a fixed input only the first time
+async fn probe_dimension(
+    infer: &Infer,
+    info: &Info,
+    dims: &ProbedDimensions,
+) -> Result<usize, ErrorResponse> {
+    if let Some(dim) = dims.get(&info.model_id) {
+        return Ok(dim);
+    }
+
+    let permit = infer.try_acquire_permit()?;
     let response = infer
-        .embed(req.text.clone(), req.truncate, req.normalize, permit)
+        .embed(PROBE_INPUT.to_string(), true, true, false, permit)
+        .await?;
+    let dim = response.results.len();
+    dims.insert(info.model_id.clone(), dim);
+    Ok(dim)
+}
+
+/// Get Embeddings of a batch of texts for Weaviate bulk imports
+///
+/// With `Accept: application/octet-stream`, vectors are returned as a row-major buffer of
+/// little-endian values described by the `X-Shape` and `X-Dtype` headers, float16 unless the
+/// request sets `dtype`.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/vectors/batch",
+request_body = EmbedWeaviateBatchRequest,
+responses(
+(status = 200, description = "Embeddings", content(
+("application/json" = EmbedWeaviateBatchResponse),
+("application/octet-stream" = Vec<u8>),
+)),
+(status = 400, description = "Invalid request body", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Invalid request body"}]})),
+(status = 500, description = "Embedding Error", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Inference failed"}]})),
+(status = 429, description = "Model is overloaded", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Model is overloaded"}]})),
+(status = 422, description = "Tokenization or batch size error", body = WeaviateErrorResponse,
+example = json ! ({"error": [{"message": "Tokenization error"}]})),
+)
+)]
+#[instrument(skip_all)]
+async fn weaviate_embed_batch(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    config: Extension<ServerConfig>,
+    limits: Extension<ConcurrencyLimits>,
+    request_headers: HeaderMap,
+    request: Request<Body>,
+) -> Result<Response, (StatusCode, Json<WeaviateErrorResponse>)> {
+    let protocol = config.weaviate_protocol;
+
+    let req: EmbedWeaviateBatchRequest =
+        read_weaviate_body(&config, &request_headers, request).await?;
+
+    metrics::increment_counter!("te_request_count", "method" => "batch");
+
+    let batch_size = req.texts.len();
+    if batch_size > info.max_client_batch_size {
+        let message = format!(
+            "batch size {batch_size} > maximum allowed batch size {}",
+            info.max_client_batch_size
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        let err = ErrorResponse {
+            error: message,
+            error_type: ErrorType::Validation,
+            code: "batch_too_large".to_string(),
+        };
+        return Err(weaviate_error(err, protocol));
+    }
+
+    check_empty_batch(&config, batch_size).map_err(|err| weaviate_error(err, protocol))?;
+    check_response_size(&config, &info, batch_size).map_err(|err| weaviate_error(err, protocol))?;
+    let _batch_slot = limits
+        .acquire_batch()
+        .map_err(|err| weaviate_error(err, protocol))?;
+
+    let prefix = config.weaviate_prefix(req.task);
+    let futures = req.texts.into_iter().map(|text| {
+        let input = if req.strip_html {
+            strip_html(&text)
+        } else {
+            text
+        };
+        let input = instructed(prefix, input);
+
+        let local_infer = infer.clone();
+        let permit_wait_timeout = config.permit_wait_timeout;
+        async move {
+            let permit = local_infer
+                .acquire_permit_timeout(permit_wait_timeout)
+                .await?;
+            local_infer
+                .embed(input, req.truncate, true, req.normalize, permit)
+                .await
+        }
+    });
+    let vectors = join_all(futures)
         .await
+        .into_iter()
+        .map(|result| result.map(|response| response.results))
+        .collect::<Result<Vec<Vec<f32>>, TextEmbeddingsError>>()
         .map_err(|e| {
             error!("Error during embedding: {:?}", e);
-            ErrorResponse::from(e)
+            weaviate_error(ErrorResponse::from(e), protocol)
         })?;
+    let dim = vectors.first().map(|v| v.len()).unwrap_or(0);
+
+    metrics::increment_counter!("te_request_success", "method" => "batch");
+
+    if accepts_octet_stream(&request_headers) {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-shape",
+            format!("[{}, {dim}]", vectors.len()).parse().unwrap(),
+        );
+        let body = match req.dtype.unwrap_or(VectorDtype::Float16) {
+            VectorDtype::Float32 => {
+                headers.insert("x-dtype", HeaderValue::from_static("float32"));
+                embeddings_to_bytes(&vectors)
+            }
+            VectorDtype::Float16 => {
+                headers.insert("x-dtype", HeaderValue::from_static("float16"));
+                embeddings_to_f16_bytes(&vectors)
+            }
+        };
+        return Ok((headers, body).into_response());
+    }
+
+    let dtype = req.dtype.unwrap_or(VectorDtype::Float32);
+    let vectors = match dtype {
+        VectorDtype::Float32 => vectors,
+        VectorDtype::Float16 => vectors
+            .into_iter()
+            .map(|vector| {
+                vector
+                    .into_iter()
+                    .map(|v| f16::from_f32(v).to_f32())
+                    .collect()
+            })
+            .collect(),
+    };
+    Ok(Json(EmbedWeaviateBatchResponse {
+        vectors,
+        dim,
+        dtype,
+    })
+    .into_response())
+}
+
+/// OpenAI compatible route. Returns a 500 status code if the model is not an embedding model.
+///
+/// Errors use the status codes and `type` values of the OpenAI API.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/embeddings",
+request_body = OpenAICompatRequest,
+responses(
+(status = 200, description = "Embeddings", body = OpenAICompatResponse),
+(status = 500, description = "Embedding Error", body = OpenAICompatErrorResponse,
+example = json ! ({"message": "Inference failed", "code": 500, "type": "server_error", "error_code": "backend_error"})),
+(status = 429, description = "Model is overloaded", body = OpenAICompatErrorResponse,
+example = json ! ({"message": "Model is overloaded", "code": 429, "type": "rate_limit_error", "error_code": "overloaded"})),
+(status = 400, description = "Invalid input", body = OpenAICompatErrorResponse,
+example = json ! ({"message": "Batch size error", "code": 400, "type": "invalid_request_error", "error_code": "batch_too_large"})),
+(status = 404, description = "The model is not loaded", body = OpenAICompatErrorResponse,
+example = json ! ({"message": "Model `unknown` is not loaded", "code": 404, "type": "invalid_request_error", "error_code": "model_not_found"})),
+)
+)]
+#[instrument(
+    skip_all,
+    fields(total_time, tokenization_time, queue_time, inference_time,)
+)]
+async fn openai_embed(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    config: Extension<ServerConfig>,
+    limits: Extension<ConcurrencyLimits>,
+    models: Extension<ModelRegistry>,
+    Json(req): Json<OpenAICompatRequest>,
+) -> Result<Response, (StatusCode, Json<OpenAICompatErrorResponse>)> {
+    let span = tracing::Span::current();
+    let start_time = Instant::now();
+
+    // The `model` field takes precedence over the `X-Model-Id` header. The public model name
+    // selects the model of the header, or the default one.
+    let selected = match req.model.as_deref().filter(|model| !model.is_empty()) {
+        Some(model)
+            if config.public_model_name.as_deref() != Some(model) && model != info.model_id =>
+        {
+            Some(models.get(model).map_err(|err| {
+                metrics::increment_counter!("te_request_failure", "err" => "model_not_found");
+                err
+            })?)
+        }
+        _ => None,
+    };
+    let (infer, info) = match selected {
+        Some((infer, info)) => (Extension(infer), Extension(info)),
+        None => (infer, info),
+    };
+    // Echo the requested model name, as some clients check that it matches what they sent
+    let model = req
+        .model
+        .clone()
+        .filter(|model| !model.is_empty())
+        .or_else(|| config.public_model_name.clone())
+        .unwrap_or_else(|| info.model_id.clone());
+    // Indices of the batch inputs that did not complete before the request timeout
+    let mut missing: Vec<usize> = Vec::new();
+
+    if req.stream {
+        metrics::increment_counter!("te_request_count", "method" => "stream");
+
+        let (inputs, batch) = match req.input {
+            Input::Single(input) => (vec![input], false),
+            Input::Batch(inputs) => (inputs, true),
+        };
+        let batch_size = inputs.len();
+        if batch_size > info.max_client_batch_size {
+            let message = format!(
+                "batch size {batch_size} > maximum allowed batch size {}",
+                info.max_client_batch_size
+            );
+            tracing::error!("{message}");
+            metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+            Err(ErrorResponse {
+                error: message,
+                error_type: ErrorType::Validation,
+                code: "batch_too_large".to_string(),
+            })?;
+        }
+
+        check_empty_batch(&config, batch_size)?;
+        let batch_slot = match batch {
+            true => limits.acquire_batch()?,
+            false => None,
+        };
+
+        // The body is not buffered, so `MAX_RESPONSE_BYTES` does not apply
+        return Ok(openai_embed_stream(
+            infer.0,
+            model,
+            inputs,
+            config.permit_wait_timeout,
+            req.encoding_format,
+            batch_slot,
+        ));
+    }
+
+    let encoding_format = req.encoding_format;
+    let _batch_slot = match &req.input {
+        Input::Single(_) => None,
+        Input::Batch(_) => limits.acquire_batch()?,
+    };
+    let (embeddings, metadata) = match req.input {
+        Input::Single(input) => {
+            metrics::increment_counter!("te_request_count", "method" => "single");
+
+            let compute_chars = input.chars().count();
+
+            let permit = infer.try_acquire_permit().map_err(ErrorResponse::from)?;
+            let response = infer
+                .embed(input, false, true, true, permit)
+                .await
+                .map_err(ErrorResponse::from)?;
+
+            metrics::increment_counter!("te_request_success", "method" => "single");
+
+            (
+                vec![OpenAICompatEmbedding {
+                    object: "embedding",
+                    embedding: encode_vector(response.results, encoding_format),
+                    index: 0,
+                }],
+                ResponseMetadata::new(
+                    compute_chars,
+                    response.prompt_tokens,
+                    start_time,
+                    response.tokenization,
+                    response.queue,
+                    response.inference,
+                ),
+            )
+        }
+        Input::Batch(inputs) => {
+            metrics::increment_counter!("te_request_count", "method" => "batch");
+
+            let batch_size = inputs.len();
+            metrics::histogram!("te_batch_size", batch_size as f64);
+            if batch_size > info.max_client_batch_size {
+                let message = format!(
+                    "batch size {batch_size} > maximum allowed batch size {}",
+                    info.max_client_batch_size
+                );
+                tracing::error!("{message}");
+                let err = ErrorResponse {
+                    error: message,
+                    error_type: ErrorType::Validation,
+                    code: "batch_too_large".to_string(),
+                };
+                metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+                Err(err)?;
+            }
+
+            check_empty_batch(&config, batch_size)?;
+            check_response_size(&config, &info, batch_size)?;
+
+            let mut futures = Vec::with_capacity(batch_size);
+            let mut compute_chars = 0;
+
+            for input in inputs {
+                compute_chars += input.chars().count();
+
+                let local_infer = infer.clone();
+                let permit_wait_timeout = config.permit_wait_timeout;
+                futures.push(async move {
+                    let permit = local_infer
+                        .acquire_permit_timeout(permit_wait_timeout)
+                        .await?;
+                    local_infer.embed(input, false, true, true, permit).await
+                })
+            }
+            let results = join_batch(futures, config.request_timeout).await;
+            missing = missing_indices(&results);
+            // Keep the input index of each result
+            let results = results
+                .into_iter()
+                .enumerate()
+                .filter_map(|(i, r)| r.map(|r| r.map(|r| (i, r))))
+                .collect::<Result<Vec<(usize, InferResponse)>, TextEmbeddingsError>>()
+                .map_err(ErrorResponse::from)?;
+            check_batch_completed(&results, &missing)?;
+
+            let mut embeddings = Vec::with_capacity(batch_size);
+            let mut total_tokenization_time = 0;
+            let mut total_queue_time = 0;
+            let mut total_inference_time = 0;
+            let mut total_compute_tokens = 0;
+
+            for (i, r) in results {
+                total_tokenization_time += r.tokenization.as_nanos() as u64;
+                total_queue_time += r.queue.as_nanos() as u64;
+                total_inference_time += r.inference.as_nanos() as u64;
+                total_compute_tokens += r.prompt_tokens;
+                embeddings.push(OpenAICompatEmbedding {
+                    object: "embedding",
+                    embedding: encode_vector(r.results, encoding_format),
+                    index: i,
+                });
+            }
+            let batch_size = embeddings.len() as u64;
+
+            metrics::increment_counter!("te_request_success", "method" => "batch");
+
+            (
+                embeddings,
+                ResponseMetadata::new(
+                    compute_chars,
+                    total_compute_tokens,
+                    start_time,
+                    mean_duration(total_tokenization_time, batch_size),
+                    mean_duration(total_queue_time, batch_size),
+                    mean_duration(total_inference_time, batch_size),
+                ),
+            )
+        }
+    };
+
+    metadata.record_span(&span);
+    metadata.record_metrics();
+
+    let compute_tokens = metadata.compute_tokens;
+    let mut headers = HeaderMap::from(metadata);
+    let status = partial_status(&missing, &mut headers);
+
+    tracing::info!("Success");
+
+    let response = OpenAICompatResponse {
+        object: "list",
+        data: embeddings,
+        model,
+        usage: OpenAICompatUsage {
+            prompt_tokens: compute_tokens,
+            total_tokens: compute_tokens,
+        },
+        encoding_format: (encoding_format == EncodingFormat::Base64).then_some(encoding_format),
+        byte_order: (encoding_format == EncodingFormat::Base64).then_some("little"),
+    };
+    Ok((status, headers, Json(response)).into_response())
+}
+
+/// Stream an OpenAI response, writing each embedding of the `data` array as soon as it completes
+///
+/// Embeddings are written in completion order and are matched to their input with `index`.
+/// The usage is written last, or an `error` object if an input failed. The batch slot is held
+/// until the stream ends.
+fn openai_embed_stream(
+    infer: Infer,
+    model: String,
+    inputs: Vec<String>,
+    permit_wait_timeout: Option<Duration>,
+    encoding_format: EncodingFormat,
+    batch_slot: Option<OwnedSemaphorePermit>,
+) -> Response {
+    let mut embeddings: FuturesUnordered<_> = inputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let infer = infer.clone();
+            async move {
+                let permit = infer.acquire_permit_timeout(permit_wait_timeout).await?;
+                let response = infer.embed(input, false, true, true, permit).await?;
+                Ok::<_, TextEmbeddingsError>((index, response))
+            }
+        })
+        .collect();
+
+    let (sender, receiver) = futures::channel::mpsc::unbounded::<Result<Bytes, Infallible>>();
+    // Spawned tasks do not inherit the redaction of the request
+    tokio::spawn(redact_inputs(inputs_redacted(), async move {
+        let _batch_slot = batch_slot;
+        let send = |chunk: String| sender.unbounded_send(Ok(Bytes::from(chunk)));
+
+        // `model` is serialized to escape it
+        let model = serde_json::to_string(&model).unwrap();
+        let encoding = match encoding_format {
+            EncodingFormat::Float => "",
+            EncodingFormat::Base64 => r#","encoding_format":"base64","byte_order":"little""#,
+        };
+        let head = format!(r#"{{"object":"list","model":{model}{encoding},"data":["#);
+        if send(head).is_err() {
+            return;
+        }
+
+        let mut compute_tokens = 0;
+        let mut error = None;
+        let mut first = true;
+        while let Some(result) = embeddings.next().await {
+            match result {
+                Ok((index, response)) => {
+                    compute_tokens += response.prompt_tokens;
+                    let embedding = OpenAICompatEmbedding {
+                        object: "embedding",
+                        embedding: encode_vector(response.results, encoding_format),
+                        index,
+                    };
+                    let separator = if first { "" } else { "," };
+                    first = false;
+                    let embedding = serde_json::to_string(&embedding).unwrap();
+                    // The client is gone: dropping the futures cancels the remaining inputs
+                    if send(format!("{separator}{embedding}")).is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            }
+        }
+
+        let end = match error {
+            None => {
+                metrics::increment_counter!("te_request_success", "method" => "stream");
+                let usage = serde_json::to_string(&OpenAICompatUsage {
+                    prompt_tokens: compute_tokens,
+                    total_tokens: compute_tokens,
+                })
+                .unwrap();
+                format!(r#"],"usage":{usage}}}"#)
+            }
+            Some(err) => {
+                let error = OpenAICompatErrorResponse::from(ErrorResponse::from(err));
+                let error = serde_json::to_string(&error).unwrap();
+                format!(r#"],"error":{error}}}"#)
+            }
+        };
+        let _ = send(end);
+    }));
+
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        StreamBody::new(receiver),
+    )
+        .into_response()
+}
+
+/// Approximate size of a float serialized in a JSON array
+const JSON_BYTES_PER_FLOAT: usize = 12;
+
+/// Encode an embedding of an OpenAI response
+///
+/// `base64` embeddings are the little-endian bytes of the `f32` values, whatever the byte order of
+/// the host, so that clients can always decode them as `<f4`.
+fn encode_vector(values: Vec<f32>, encoding_format: EncodingFormat) -> OpenAICompatVector {
+    match encoding_format {
+        EncodingFormat::Float => OpenAICompatVector::Float(values),
+        EncodingFormat::Base64 => {
+            let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            OpenAICompatVector::Base64(BASE64_STANDARD.encode(bytes))
+        }
+    }
+}
+
+/// Reject embedding requests whose response would be larger than `MAX_RESPONSE_BYTES` once
+/// serialized
+///
+/// The size is estimated from the embedding dimension of the model, so that large requests are
+/// rejected before running inference.
+fn check_response_size(
+    config: &ServerConfig,
+    info: &Info,
+    batch_size: usize,
+) -> Result<(), ErrorResponse> {
+    if let (Some(max_response_bytes), Some(dim)) = (config.max_response_bytes, info.embedding_dim) {
+        let response_bytes = batch_size * dim * JSON_BYTES_PER_FLOAT;
+        if response_bytes > max_response_bytes {
+            let message = format!(
+                "estimated response size {response_bytes} bytes > maximum allowed response size \
+                {max_response_bytes} bytes. Reduce the batch size"
+            );
+            tracing::error!("{message}");
+            metrics::increment_counter!("te_request_failure", "err" => "response_size");
+            return Err(ErrorResponse {
+                error: message,
+                error_type: ErrorType::Validation,
+                code: "response_too_large".to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Get the similarity of sentences with a source sentence. Returns a 424 status code if the model
+/// is not an embedding model.
+///
+/// The `cosine` metric always normalizes the embeddings, the `dot` metric only if `normalize` is
+/// set.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/similarity",
+request_body = SimilarityRequest,
+responses(
+(status = 200, description = "Similarity scores", body = SimilarityResponse),
+(status = 424, description = "Embedding Error", body = ErrorResponse,
+example = json ! ({"error": "Inference failed", "error_type": "backend", "code": "backend_error"})),
+(status = 429, description = "Model is overloaded", body = ErrorResponse,
+example = json ! ({"error": "Model is overloaded", "error_type": "overloaded", "code": "overloaded"})),
+(status = 422, description = "Tokenization error", body = ErrorResponse,
+example = json ! ({"error": "Tokenization error", "error_type": "tokenizer", "code": "tokenizer_error"})),
+(status = 413, description = "Batch size error", body = ErrorResponse,
+example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
+)
+)]
+#[instrument(
+    skip_all,
+    fields(total_time, tokenization_time, queue_time, inference_time,)
+)]
+async fn similarity(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    config: Extension<ServerConfig>,
+    limits: Extension<ConcurrencyLimits>,
+    Json(req): Json<SimilarityRequest>,
+) -> Result<(HeaderMap, Json<SimilarityResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let span = tracing::Span::current();
+    let start_time = Instant::now();
+
+    metrics::increment_counter!("te_request_count", "method" => "similarity");
+
+    req.validate()?;
+
+    // The source sentence is embedded in the same batch
+    let batch_size = req.sentences.len() + 1;
+    if batch_size > info.max_client_batch_size {
+        let message = format!(
+            "batch size {batch_size} > maximum allowed batch size {}",
+            info.max_client_batch_size
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Validation,
+            code: "batch_too_large".to_string(),
+        })?;
+    }
+    let _batch_slot = limits.acquire_batch()?;
+
+    let mut futures = Vec::with_capacity(batch_size);
+    let mut compute_chars = 0;
+    for input in std::iter::once(req.source_sentence).chain(req.sentences) {
+        compute_chars += input.chars().count();
+        let local_infer = infer.clone();
+        let permit_wait_timeout = config.permit_wait_timeout;
+        futures.push(async move {
+            let permit = local_infer
+                .acquire_permit_timeout(permit_wait_timeout)
+                .await?;
+            // Normalized in the router so that raw dot products do not need another inference
+            local_infer
+                .embed(input, req.truncate, true, false, permit)
+                .await
+        })
+    }
+    let mut results = join_all(futures)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
+        .map_err(ErrorResponse::from)?;
+
+    // Always set for the `cosine` metric
+    if req.normalize {
+        for r in &mut results {
+            infer.normalize(&mut r.results);
+        }
+    }
+
+    let mut total_tokenization_time = 0;
+    let mut total_queue_time = 0;
+    let mut total_inference_time = 0;
+    let mut total_compute_tokens = 0;
+    for r in &results {
+        total_tokenization_time += r.tokenization.as_nanos() as u64;
+        total_queue_time += r.queue.as_nanos() as u64;
+        total_inference_time += r.inference.as_nanos() as u64;
+        total_compute_tokens += r.prompt_tokens;
+    }
+
+    // The dot product of normalized embeddings is their cosine similarity
+    let (source, sentences) = results.split_first().unwrap();
+    let scores = sentences.iter().map(|sentence| {
+        source
+            .results
+            .iter()
+            .zip(&sentence.results)
+            .map(|(a, b)| a * b)
+            .sum::<f32>()
+    });
+
+    let response = match req.threshold {
+        Some(threshold) => {
+            let mut matches: Vec<SimilarityScore> = scores
+                .enumerate()
+                .filter(|(_, score)| *score >= threshold)
+                .map(|(index, score)| SimilarityScore { index, score })
+                .collect();
+            matches.sort_by(|x, y| y.score.total_cmp(&x.score));
+            SimilarityResponse::Matches(matches)
+        }
+        None => SimilarityResponse::Scores(scores.collect()),
+    };
+
+    metrics::increment_counter!("te_request_success", "method" => "similarity");
+
+    let batch_size = batch_size as u64;
+    let metadata = ResponseMetadata::new(
+        compute_chars,
+        total_compute_tokens,
+        start_time,
+        mean_duration(total_tokenization_time, batch_size),
+        mean_duration(total_queue_time, batch_size),
+        mean_duration(total_inference_time, batch_size),
+    );
+    metadata.record_span(&span);
+    metadata.record_metrics();
+
+    let headers = HeaderMap::from(metadata);
+
+    tracing::info!("Success");
+
+    Ok((headers, Json(response)))
+}
+
+/// Classify inputs by the labels of their most similar embeddings in the reference set loaded
+/// from `REFERENCE_SET_PATH`. Returns a 424 status code if the model is not an embedding model.
+///
+/// The score of a label is the cosine similarity with its closest reference embedding.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/classify_by_similarity",
+request_body = ClassifyBySimilarityRequest,
+responses(
+(status = 200, description = "Nearest labels", body = ClassifyBySimilarityResponse),
+(status = 424, description = "Embedding Error", body = ErrorResponse,
+example = json ! ({"error": "Inference failed", "error_type": "backend", "code": "backend_error"})),
+(status = 429, description = "Model is overloaded", body = ErrorResponse,
+example = json ! ({"error": "Model is overloaded", "error_type": "overloaded", "code": "overloaded"})),
+(status = 422, description = "Tokenization error", body = ErrorResponse,
+example = json ! ({"error": "Tokenization error", "error_type": "tokenizer", "code": "tokenizer_error"})),
+(status = 413, description = "Batch size error", body = ErrorResponse,
+example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
+)
+)]
+#[instrument(
+    skip_all,
+    fields(total_time, tokenization_time, queue_time, inference_time,)
+)]
+async fn classify_by_similarity(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    config: Extension<ServerConfig>,
+    limits: Extension<ConcurrencyLimits>,
+    Json(req): Json<ClassifyBySimilarityRequest>,
+) -> Result<(HeaderMap, Json<ClassifyBySimilarityResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let span = tracing::Span::current();
+    let start_time = Instant::now();
+
+    metrics::increment_counter!("te_request_count", "method" => "classify_by_similarity");
+
+    validation::non_zero("top_k", req.top_k)?;
+    // The route is only served with a reference set
+    let references = config
+        .reference_set
+        .clone()
+        .expect("`/classify_by_similarity` is served without a reference set. This is a bug.");
+
+    let (inputs, single) = match req.inputs {
+        Input::Single(input) => (vec![input], true),
+        Input::Batch(inputs) => (inputs, false),
+    };
+    let batch_size = inputs.len();
+    if batch_size > info.max_client_batch_size {
+        let message = format!(
+            "batch size {batch_size} > maximum allowed batch size {}",
+            info.max_client_batch_size
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Validation,
+            code: "batch_too_large".to_string(),
+        })?;
+    }
+    check_empty_batch(&config, batch_size)?;
+    let _batch_slot = if single {
+        None
+    } else {
+        limits.acquire_batch()?
+    };
+
+    let mut futures = Vec::with_capacity(batch_size);
+    let mut compute_chars = 0;
+    for input in inputs {
+        compute_chars += input.chars().count();
+        let local_infer = infer.clone();
+        let permit_wait_timeout = config.permit_wait_timeout;
+        futures.push(async move {
+            let permit = local_infer
+                .acquire_permit_timeout(permit_wait_timeout)
+                .await?;
+            local_infer
+                .embed(input, req.truncate, true, true, permit)
+                .await
+        })
+    }
+    let results = join_all(futures)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
+        .map_err(ErrorResponse::from)?;
+
+    let dim = references[0].embedding.len();
+    if let Some(r) = results.iter().find(|r| r.results.len() != dim) {
+        let message = format!(
+            "Reference embeddings have dimension {dim}, but the model embeddings have dimension {}",
+            r.results.len()
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "reference_set");
+        Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Backend,
+            code: "reference_dimension_mismatch".to_string(),
+        })?;
+    }
+
+    let mut total_tokenization_time = 0;
+    let mut total_queue_time = 0;
+    let mut total_inference_time = 0;
+    let mut total_compute_tokens = 0;
+    let mut labels = Vec::with_capacity(batch_size);
+    for r in results {
+        total_tokenization_time += r.tokenization.as_nanos() as u64;
+        total_queue_time += r.queue.as_nanos() as u64;
+        total_inference_time += r.inference.as_nanos() as u64;
+        total_compute_tokens += r.prompt_tokens;
+        labels.push(nearest_labels(&r.results, &references, req.top_k));
+    }
+
+    let response = if single {
+        ClassifyBySimilarityResponse::Single(labels.pop().unwrap())
+    } else {
+        ClassifyBySimilarityResponse::Batch(labels)
+    };
+
+    metrics::increment_counter!("te_request_success", "method" => "classify_by_similarity");
+
+    let batch_size = batch_size as u64;
+    let metadata = ResponseMetadata::new(
+        compute_chars,
+        total_compute_tokens,
+        start_time,
+        mean_duration(total_tokenization_time, batch_size),
+        mean_duration(total_queue_time, batch_size),
+        mean_duration(total_inference_time, batch_size),
+    );
+    metadata.record_span(&span);
+    metadata.record_metrics();
+
+    let headers = HeaderMap::from(metadata);
+
+    tracing::info!("Success");
+
+    Ok((headers, Json(response)))
+}
+
+/// Labels of the reference embeddings closest to a normalized embedding, by decreasing score
+///
+/// Each label is scored by its closest reference embedding.
+fn nearest_labels(
+    embedding: &[f32],
+    references: &[Reference],
+    top_k: usize,
+) -> Vec<LabelSimilarity> {
+    let mut scores: Vec<(&str, f32)> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for reference in references {
+        // Both embeddings are normalized
+        let score = embedding
+            .iter()
+            .zip(&reference.embedding)
+            .map(|(a, b)| a * b)
+            .sum::<f32>();
+        match positions.get(reference.label.as_str()) {
+            Some(&i) => scores[i].1 = scores[i].1.max(score),
+            None => {
+                positions.insert(&reference.label, scores.len());
+                scores.push((&reference.label, score));
+            }
+        }
+    }
+    scores.sort_by(|x, y| y.1.total_cmp(&x.1));
+    scores
+        .into_iter()
+        .take(top_k)
+        .map(|(label, score)| LabelSimilarity {
+            label: label.to_string(),
+            score,
+        })
+        .collect()
+}
+
+/// Count the tokens of inputs without running the model
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/count_tokens",
+request_body = CountTokensRequest,
+responses(
+(status = 200, description = "Token counts", body = CountTokensResponse),
+(status = 422, description = "Tokenization error", body = ErrorResponse,
+example = json ! ({"error": "Tokenization error", "error_type": "tokenizer", "code": "tokenizer_error"})),
+(status = 413, description = "Batch size error", body = ErrorResponse,
+example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
+)
+)]
+#[instrument(skip_all)]
+async fn count_tokens(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    Json(req): Json<CountTokensRequest>,
+) -> Result<Json<CountTokensResponse>, (StatusCode, Json<ErrorResponse>)> {
+    metrics::increment_counter!("te_request_count", "method" => "count_tokens");
+
+    let inputs = match req.inputs {
+        Input::Single(input) => vec![input],
+        Input::Batch(inputs) => inputs,
+    };
+
+    let batch_size = inputs.len();
+    if batch_size > info.max_client_batch_size {
+        let message = format!(
+            "batch size {batch_size} > maximum allowed batch size {}",
+            info.max_client_batch_size
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Validation,
+            code: "batch_too_large".to_string(),
+        })?;
+    }
+
+    let futures = inputs
+        .into_iter()
+        .map(|input| infer.count_tokens(input, req.add_special_tokens));
+    let tokens = join_all(futures)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<usize>, TextEmbeddingsError>>()
+        .map_err(ErrorResponse::from)?;
+
+    let counts: Vec<TokenCount> = tokens
+        .into_iter()
+        .map(|tokens| TokenCount {
+            tokens,
+            truncated: tokens > info.max_input_length,
+        })
+        .collect();
+    let total = counts.iter().map(|count| count.tokens).sum();
+
+    metrics::increment_counter!("te_request_success", "method" => "count_tokens");
+    Ok(Json(CountTokensResponse { counts, total }))
+}
+
+/// Submit an asynchronous embedding job. Returns a 424 status code if the model is not an embedding model.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/jobs",
+request_body = JobRequest,
+responses(
+(status = 202, description = "Job accepted", body = JobResponse),
+(status = 424, description = "Embedding Error", body = ErrorResponse,
+example = json ! ({"error": "Model is not an embedding model", "error_type": "backend", "code": "model_type_mismatch"})),
+(status = 413, description = "Batch size error", body = ErrorResponse,
+example = json ! ({"error": "Batch size error", "error_type": "validation", "code": "batch_too_large"})),
+(status = 429, description = "Too many jobs in flight", body = ErrorResponse,
+example = json ! ({"error": "Too many jobs in flight", "error_type": "overloaded", "code": "too_many_jobs"})),
+)
+)]
+#[instrument(skip_all)]
+async fn submit_job(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    config: Extension<ServerConfig>,
+    jobs: Extension<JobStore>,
+    Json(req): Json<JobRequest>,
+) -> Result<(StatusCode, Json<JobResponse>), (StatusCode, Json<ErrorResponse>)> {
+    metrics::increment_counter!("te_request_count", "method" => "job");
+
+    if infer.is_classifier() {
+        metrics::increment_counter!("te_request_failure", "err" => "model_type");
+        Err(ErrorResponse {
+            error: "Model is not an embedding model".to_string(),
+            error_type: ErrorType::Backend,
+            code: "model_type_mismatch".to_string(),
+        })?;
+    }
+
+    let batch_size = req.inputs.len();
+    check_empty_batch(&config, batch_size)?;
+    if batch_size > config.max_job_inputs {
+        let message = format!(
+            "job size {batch_size} must be at most {}",
+            config.max_job_inputs
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "batch_size");
+        Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::Validation,
+            code: "batch_too_large".to_string(),
+        })?;
+    }
+
+    let id = jobs.create(batch_size).ok_or_else(|| {
+        metrics::increment_counter!("te_request_failure", "err" => "too_many_jobs");
+        let message = "Too many jobs in flight".to_string();
+        tracing::error!("{message}");
+        ErrorResponse {
+            error: message,
+            error_type: ErrorType::Overloaded,
+            code: "too_many_jobs".to_string(),
+        }
+    })?;
+    let job = run_job(
+        infer.0,
+        jobs.0.clone(),
+        id.clone(),
+        req,
+        info.max_client_batch_size,
+    );
+    tokio::spawn(redact_inputs(inputs_redacted(), job));
+
+    // The job has just been created
+    let job = jobs.get(&id).unwrap();
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Embed the inputs of a job in chunks, under the permit pool
+async fn run_job(infer: Infer, jobs: JobStore, id: String, req: JobRequest, chunk_size: usize) {
+    jobs.update(&id, |job| job.status = JobStatus::Running);
+
+    for chunk in req.inputs.chunks(chunk_size) {
+        let futures = chunk.iter().map(|input| {
+            let infer = infer.clone();
+            let input = input.clone();
+            async move {
+                // Fails if the model is reloaded while the job is running
+                let permit = infer.acquire_permit_timeout(None).await?;
+                infer
+                    .embed(input, req.truncate, true, req.normalize, permit)
+                    .await
+            }
+        });
+
+        match join_all(futures)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
+        {
+            Ok(results) => jobs.update(&id, |job| {
+                job.embeddings
+                    .extend(results.into_iter().map(|r| r.results))
+            }),
+            Err(err) => {
+                tracing::error!("Job {id} failed: {err}");
+                metrics::increment_counter!("te_request_failure", "err" => "job");
+                jobs.update(&id, |job| job.fail(err.to_string()));
+                return;
+            }
+        }
+    }
+
+    metrics::increment_counter!("te_request_success", "method" => "job");
+    jobs.update(&id, |job| job.complete());
+}
+
+/// Get the status of an asynchronous job, and its embeddings once completed
+#[utoipa::path(
+get,
+tag = "Text Embeddings Inference",
+path = "/jobs/{id}",
+params(("id" = String, Path, description = "Job id")),
+responses(
+(status = 200, description = "Job status", body = JobResponse),
+(status = 404, description = "Unknown or expired job", body = ErrorResponse,
+example = json ! ({"error": "Job not found", "error_type": "not_found", "code": "job_not_found"})),
+)
+)]
+#[instrument(skip(jobs))]
+async fn get_job(
+    jobs: Extension<JobStore>,
+    Path(id): Path<String>,
+) -> Result<Json<JobResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match jobs.get(&id) {
+        Some(job) => Ok(Json(job)),
+        None => Err(ErrorResponse {
+            error: "Job not found".to_string(),
+            error_type: ErrorType::NotFound,
+            code: "job_not_found".to_string(),
+        })?,
+    }
+}
+
+/// Maximum number of synthetic inferences of a single warmup
+const MAX_WARMUP_ITERATIONS: usize = 1024;
+
+/// Maximum number of inputs embedded by a single benchmark
+const MAX_BENCH_INPUTS: usize = 10_000;
+
+/// Locks held while an admin task is running, to refuse concurrent runs
+#[derive(Clone, Default)]
+struct AdminLocks {
+    warmup: Arc<Mutex<()>>,
+    bench: Arc<Mutex<()>>,
+    /// Number of reloads, so that each backend gets its own socket
+    reload: Arc<Mutex<usize>>,
+}
+
+/// Load a model and swap it with the served model. Requires an API key with the `admin` scope
+///
+/// The new model serves the next requests while requests in flight finish on the previous one,
+/// whose backend is then stopped. If the new model cannot be loaded, the previous one keeps
+/// serving.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/admin/reload",
+request_body = ReloadRequest,
+responses(
+(status = 200, description = "Info of the new model", body = Info),
+(status = 401, description = "Missing or invalid API key", body = ErrorResponse,
+example = json ! ({"error": "Invalid API key", "error_type": "unauthorized", "code": "unauthorized"})),
+(status = 424, description = "The model could not be loaded", body = ErrorResponse,
+example = json ! ({"error": "Could not load model", "error_type": "backend", "code": "reload_failed"})),
+(status = 429, description = "A reload is already running", body = ErrorResponse,
+example = json ! ({"error": "A reload is already running", "error_type": "overloaded", "code": "reload_in_progress"})),
+)
+)]
+#[instrument(skip_all)]
+async fn reload(
+    models: Extension<ModelRegistry>,
+    dims: Extension<ProbedDimensions>,
+    options: Extension<ModelOptions>,
+    config: Extension<ServerConfig>,
+    locks: Extension<AdminLocks>,
+    req: Option<Json<ReloadRequest>>,
+) -> Result<Json<Info>, (StatusCode, Json<ErrorResponse>)> {
+    let mut reloads = locks.reload.try_lock().map_err(|_| ErrorResponse {
+        error: "A reload is already running".to_string(),
+        error_type: ErrorType::Overloaded,
+        code: "reload_in_progress".to_string(),
+    })?;
+
+    let current = models.default_info();
+    // The body is optional
+    let model_id = req
+        .and_then(|req| req.0.model_id)
+        .or_else(|| config.reload_model_path.clone())
+        .unwrap_or_else(|| current.model_id.clone());
+
+    tracing::info!("Reloading model {model_id}");
+    // The previous backend may still be listening on its socket
+    *reloads += 1;
+    let options = options.with_uds_suffix(&format!("reload-{reloads}"));
+    let (infer, info) = load_model(model_id, options).await.map_err(|err| {
+        let message = format!("Could not load model: {err:#}");
+        tracing::error!("{message}");
+        ErrorResponse {
+            error: message,
+            error_type: ErrorType::Backend,
+            code: "reload_failed".to_string(),
+        }
+    })?;
+
+    // Default routes are selected from the model type when the server starts
+    if std::mem::discriminant(&info.model_type) != std::mem::discriminant(&current.model_type) {
+        infer.stop().await;
+        Err(ErrorResponse {
+            error: "The new model does not have the same type as the served model".to_string(),
+            error_type: ErrorType::Backend,
+            code: "model_type_mismatch".to_string(),
+        })?;
+    }
+
+    if let Err(err) = config.check_reference_set(&info) {
+        infer.stop().await;
+        Err(ErrorResponse {
+            error: err.to_string(),
+            error_type: ErrorType::Backend,
+            code: "reference_dimension_mismatch".to_string(),
+        })?;
+    }
+
+    // The new model may be another revision with another dimension
+    dims.forget(&info.model_id);
+    if let Some((previous, _)) = models.replace_default(infer, info.clone()) {
+        previous.stop().await;
+    }
+    tracing::info!("Model {} reloaded", info.model_id);
+
+    Ok(Json(info))
+}
+
+/// Replace the log filter without restarting. Requires an API key with the `admin` scope
+#[utoipa::path(
+put,
+tag = "Text Embeddings Inference",
+path = "/admin/log_level",
+request_body = LogLevel,
+responses(
+(status = 200, description = "The filter now in use", body = LogLevel),
+(status = 401, description = "Missing or invalid API key", body = ErrorResponse,
+example = json ! ({"error": "Invalid API key", "error_type": "unauthorized", "code": "unauthorized"})),
+(status = 422, description = "Invalid filter", body = ErrorResponse,
+example = json ! ({"error": "invalid filter directive", "error_type": "invalid_parameter", "code": "invalid_filter"})),
+(status = 503, description = "Logging cannot be reconfigured", body = ErrorResponse,
+example = json ! ({"error": "The log filter cannot be changed at runtime", "error_type": "unavailable", "code": "log_reload_unavailable"})),
+)
+)]
+#[instrument(skip_all)]
+async fn set_log_level(
+    Json(req): Json<LogLevel>,
+) -> Result<Json<LogLevel>, (StatusCode, Json<ErrorResponse>)> {
+    match set_log_filter(&req.filter) {
+        Ok(()) => {
+            tracing::info!("Log filter set to `{}`", req.filter);
+            Ok(Json(req))
+        }
+        Err(LogFilterError::Invalid(message)) => Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::InvalidParameter,
+            code: "invalid_filter".to_string(),
+        })?,
+        Err(LogFilterError::Unavailable) => Err(ErrorResponse {
+            error: "The log filter cannot be changed at runtime".to_string(),
+            error_type: ErrorType::Unavailable,
+            code: "log_reload_unavailable".to_string(),
+        })?,
+    }
+}
+
+/// Run synthetic inferences to warm up the model. Requires an API key with the `admin` scope
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/admin/warmup",
+request_body = WarmupRequest,
+responses(
+(status = 200, description = "Warmup timings", body = WarmupResponse),
+(status = 401, description = "Missing or invalid API key", body = ErrorResponse,
+example = json ! ({"error": "Invalid API key", "error_type": "unauthorized", "code": "unauthorized"})),
+(status = 429, description = "A warmup is already running", body = ErrorResponse,
+example = json ! ({"error": "A warmup is already running", "error_type": "overloaded", "code": "warmup_in_progress"})),
+(status = 503, description = "Text embeddings Inference is down", body = ErrorResponse,
+example = json ! ({"error": "unhealthy", "error_type": "unhealthy", "code": "unhealthy"})),
+)
+)]
+#[instrument(skip_all)]
+async fn warmup(
+    infer: Extension<Infer>,
+    locks: Extension<AdminLocks>,
+    req: Option<Json<WarmupRequest>>,
+) -> Result<Json<WarmupResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // The body is optional
+    let iterations = req
+        .map(|req| req.iterations)
+        .unwrap_or_else(default_warmup_iterations);
+    if iterations == 0 || iterations > MAX_WARMUP_ITERATIONS {
+        Err(ErrorResponse {
+            error: format!("`iterations` must be between 1 and {MAX_WARMUP_ITERATIONS}"),
+            error_type: ErrorType::Validation,
+            code: "invalid_iterations".to_string(),
+        })?;
+    }
+
+    let _guard = locks.warmup.try_lock().map_err(|_| ErrorResponse {
+        error: "A warmup is already running".to_string(),
+        error_type: ErrorType::Overloaded,
+        code: "warmup_in_progress".to_string(),
+    })?;
+
+    if !infer.health().await {
+        Err(ErrorResponse {
+            error: "unhealthy".to_string(),
+            error_type: ErrorType::Unhealthy,
+            code: "unhealthy".to_string(),
+        })?;
+    }
+
+    let input = "This is a warmup request".to_string();
+    let mut durations = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start_time = Instant::now();
+        let permit = infer
+            .acquire_permit_timeout(None)
+            .await
+            .map_err(ErrorResponse::from)?;
+        if infer.is_classifier() {
+            infer.predict(input.clone(), true, true, true, permit).await
+        } else {
+            infer.embed(input.clone(), true, true, true, permit).await
+        }
+        .map_err(ErrorResponse::from)?;
+        durations.push(start_time.elapsed().as_secs_f64() * 1000.0);
+    }
+
+    let total_ms: f64 = durations.iter().sum();
+    let response = WarmupResponse {
+        iterations,
+        total_ms,
+        mean_ms: total_ms / iterations as f64,
+        min_ms: durations.iter().copied().fold(f64::INFINITY, f64::min),
+        max_ms: durations.iter().copied().fold(0.0, f64::max),
+    };
+    tracing::info!("Warmup finished in {total_ms:.2}ms");
+
+    Ok(Json(response))
+}
+
+/// Benchmark the embedding path with synthetic requests. Requires an API key with the `admin` scope
+///
+/// Requests go through the permit pool like client requests, so the numbers reflect production
+/// behavior, including the impact on live traffic.
+#[utoipa::path(
+post,
+tag = "Text Embeddings Inference",
+path = "/admin/bench",
+request_body = BenchRequest,
+responses(
+(status = 200, description = "Benchmark results", body = BenchResponse),
+(status = 401, description = "Missing or invalid API key", body = ErrorResponse,
+example = json ! ({"error": "Invalid API key", "error_type": "unauthorized", "code": "unauthorized"})),
+(status = 413, description = "Benchmark too large", body = ErrorResponse,
+example = json ! ({"error": "`requests * batch_size` must be between 1 and 10000", "error_type": "validation", "code": "invalid_bench"})),
+(status = 424, description = "Embedding Error", body = ErrorResponse,
+example = json ! ({"error": "Model is not an embedding model", "error_type": "backend", "code": "model_type_mismatch"})),
+(status = 429, description = "A benchmark is already running", body = ErrorResponse,
+example = json ! ({"error": "A benchmark is already running", "error_type": "overloaded", "code": "bench_in_progress"})),
+)
+)]
+#[instrument(skip_all)]
+async fn bench(
+    infer: Extension<Infer>,
+    info: Extension<Info>,
+    locks: Extension<AdminLocks>,
+    Json(req): Json<BenchRequest>,
+) -> Result<Json<BenchResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if infer.is_classifier() {
+        Err(ErrorResponse {
+            error: "Model is not an embedding model".to_string(),
+            error_type: ErrorType::Backend,
+            code: "model_type_mismatch".to_string(),
+        })?;
+    }
+
+    // Cap the total amount of work
+    let total_inputs = req.requests.saturating_mul(req.batch_size);
+    let error = if total_inputs == 0 || total_inputs > MAX_BENCH_INPUTS {
+        Some(format!(
+            "`requests * batch_size` must be between 1 and {MAX_BENCH_INPUTS}"
+        ))
+    } else if req.batch_size > info.max_client_batch_size {
+        Some(format!(
+            "`batch_size` must be at most {}",
+            info.max_client_batch_size
+        ))
+    } else if req.input_length == 0 || req.input_length > info.max_input_length {
+        Some(format!(
+            "`input_length` must be between 1 and {}",
+            info.max_input_length
+        ))
+    } else if req.concurrency == 0 || req.concurrency > info.max_concurrent_requests {
+        Some(format!(
+            "`concurrency` must be between 1 and {}",
+            info.max_concurrent_requests
+        ))
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        Err(ErrorResponse {
+            error,
+            error_type: ErrorType::Validation,
+            code: "invalid_bench".to_string(),
+        })?;
+    }
+
+    let _guard = locks.bench.try_lock().map_err(|_| ErrorResponse {
+        error: "A benchmark is already running".to_string(),
+        error_type: ErrorType::Overloaded,
+        code: "bench_in_progress".to_string(),
+    })?;
+
+    let input = vec!["benchmark"; req.input_length].join(" ");
+    let batch_size = req.batch_size;
+
+    let start_time = Instant::now();
+    let results = stream::iter(0..req.requests)
+        .map(|_| {
+            let infer = infer.clone();
+            let input = input.clone();
+            async move {
+                let request_start_time = Instant::now();
+                let futures = (0..batch_size).map(|_| {
+                    let infer = infer.clone();
+                    let input = input.clone();
+                    async move {
+                        let permit = infer.acquire_permit_timeout(None).await?;
+                        infer.embed(input, true, true, true, permit).await
+                    }
+                });
+                let responses = join_all(futures)
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()?;
+                let tokens: usize = responses.iter().map(|r| r.prompt_tokens).sum();
+                Ok::<(f64, usize), TextEmbeddingsError>((
+                    request_start_time.elapsed().as_secs_f64() * 1000.0,
+                    tokens,
+                ))
+            }
+        })
+        .buffer_unordered(req.concurrency)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<(f64, usize)>, TextEmbeddingsError>>()
+        .map_err(ErrorResponse::from)?;
+    let total_time = start_time.elapsed().as_secs_f64();
+
+    let (mut latencies, tokens): (Vec<f64>, Vec<usize>) = results.into_iter().unzip();
+    latencies.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    let total_tokens: usize = tokens.into_iter().sum();
+
+    let response = BenchResponse {
+        requests: req.requests,
+        batch_size,
+        total_ms: total_time * 1000.0,
+        throughput: req.requests as f64 / total_time,
+        tokens_per_sec: total_tokens as f64 / total_time,
+        p50_ms: percentile(&latencies, 0.50),
+        p95_ms: percentile(&latencies, 0.95),
+        p99_ms: percentile(&latencies, 0.99),
+    };
+    tracing::info!(
+        "Benchmark finished: {:.2} req/s, {:.2} tokens/s",
+        response.throughput,
+        response.tokens_per_sec
+    );
+
+    Ok(Json(response))
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Attach the `Principal` of the API key sent as a bearer token, if the key is valid
+///
+/// The `max_batch_size` of the principal replaces the client batch size limit.
+async fn attach_principal<B>(
+    config: &ServerConfig,
+    request: &mut Request<B>,
+) -> Result<(), ErrorResponse> {
+    let token = bearer_token(request.headers()).map(String::from);
+    let principal = match (&config.authenticator, token) {
+        (Some(authenticator), Some(token)) => authenticator.authenticate(&token).await?,
+        _ => None,
+    };
+    if let Some(principal) = principal {
+        if let (Some(max_batch_size), Some(info)) = (
+            principal.max_batch_size,
+            request.extensions_mut().get_mut::<Info>(),
+        ) {
+            info.max_client_batch_size = max_batch_size;
+        }
+        request.extensions_mut().insert(principal);
+    }
+    Ok(())
+}
 
-    let vector = response.results; 
-    let dim = vector.len();
+/// Authenticate inference requests, rejecting those without a valid API key if `REQUIRE_AUTH`
+/// is set
+///
+/// Only layered on the routes using the principal, so that the other routes, such as the health
+/// checks, neither wait for nor fail with the validator of API keys.
+async fn authenticate<B>(
+    config: Extension<ServerConfig>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    attach_principal(&config, &mut request).await?;
+    let tenant = match request.extensions().get::<Principal>() {
+        Some(principal) => Some(principal.tenant.clone()),
+        None if config.require_auth => Err(unauthorized("Invalid API key".to_string()))?,
+        None => None,
+    };
 
-    let json_response = EmbedWeaviateResponse {
-        text: req.text,
-        vector,
-        dim,
+    let tenants = match &config.tenant_metrics {
+        Some(tenants) => tenants,
+        None => return Ok(next.run(request).await),
     };
+    // Other tenants are bucketed to bound the cardinality of the label
+    let tenant = tenant
+        .filter(|tenant| tenants.contains(tenant))
+        .unwrap_or_else(|| "other".to_string());
+    Ok(with_tenant(tenant, next.run(request)).await)
+}
 
-    let headers = HeaderMap::new(); 
+/// Reject requests whose API key does not have the `admin` scope
+async fn require_api_key<B>(
+    config: Extension<ServerConfig>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    attach_principal(&config, &mut request).await?;
+    match request.extensions().get::<Principal>() {
+        Some(principal) if principal.has_scope(ADMIN_SCOPE) => Ok(next.run(request).await),
+        Some(_) => Err(unauthorized(format!(
+            "API key does not have the `{ADMIN_SCOPE}` scope"
+        )))?,
+        None => Err(unauthorized("Invalid API key".to_string()))?,
+    }
+}
 
-    Ok((headers, Json(json_response)))
+fn unauthorized(message: String) -> ErrorResponse {
+    metrics::increment_counter!("te_request_failure", "err" => "unauthorized");
+    ErrorResponse {
+        error: message,
+        error_type: ErrorType::Unauthorized,
+        code: "unauthorized".to_string(),
+    }
 }
 
-/// OpenAI compatible route. Returns a 424 status code if the model is not an embedding model.
-#[utoipa::path(
-post,
-tag = "Text Embeddings Inference",
-path = "/embeddings",
-request_body = OpenAICompatRequest,
-responses(
-(status = 200, description = "Embeddings", body = OpenAICompatResponse),
-(status = 424, description = "Embedding Error", body = OpenAICompatErrorResponse,
-example = json ! ({"message": "Inference failed", "type": "backend"})),
-(status = 429, description = "Model is overloaded", body = OpenAICompatErrorResponse,
-example = json ! ({"message": "Model is overloaded", "type": "overloaded"})),
-(status = 422, description = "Tokenization error", body = OpenAICompatErrorResponse,
-example = json ! ({"message": "Tokenization error", "type": "tokenizer"})),
-(status = 413, description = "Batch size error", body = OpenAICompatErrorResponse,
-example = json ! ({"message": "Batch size error", "type": "validation"})),
-)
-)]
-#[instrument(
-    skip_all,
-    fields(total_time, tokenization_time, queue_time, inference_time,)
-)]
-async fn openai_embed(
-    infer: Extension<Infer>,
-    info: Extension<Info>,
-    Json(req): Json<OpenAICompatRequest>,
-) -> Result<(HeaderMap, Json<OpenAICompatResponse>), (StatusCode, Json<OpenAICompatErrorResponse>)>
-{
-    let span = tracing::Span::current();
-    let start_time = Instant::now();
+/// Set the `X-Backend-Device` and `X-Batch-Composition` headers if `DEBUG_HEADERS` is set
+///
+/// `X-Batch-Composition` lists the number of requests in the backend batch that computed each
+/// inference of the request, in completion order.
+async fn debug_headers<B>(
+    config: Extension<ServerConfig>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !config.debug_headers {
+        return next.run(request).await;
+    }
 
-    let (embeddings, metadata) = match req.input {
-        Input::Single(input) => {
-            metrics::increment_counter!("te_request_count", "method" => "single");
+    let device = request
+        .extensions()
+        .get::<Infer>()
+        .and_then(Infer::device)
+        .and_then(|device| HeaderValue::from_str(device).ok());
+    let (mut response, batch_sizes) = collect_batch_sizes(next.run(request)).await;
 
-            let compute_chars = input.chars().count();
+    let headers = response.headers_mut();
+    if let Some(device) = device {
+        headers.insert("x-backend-device", device);
+    }
+    if !batch_sizes.is_empty() {
+        let batch_sizes: Vec<String> = batch_sizes.iter().map(usize::to_string).collect();
+        headers.insert(
+            "x-batch-composition",
+            batch_sizes.join(",").parse().unwrap(),
+        );
+    }
+    response
+}
 
-            let permit = infer.try_acquire_permit().map_err(ErrorResponse::from)?;
-            let response = infer
-                .embed(input, false, true, permit)
-                .await
-                .map_err(ErrorResponse::from)?;
+/// Raise the client batch size limit of requests carrying one of the `TRUSTED_API_KEYS`
+///
+/// The limit is never lowered below `max_client_batch_size`, unless the principal of the API key
+/// sets its own `max_batch_size`, which replaces it once the request is authenticated.
+async fn trusted_batch_limit<B>(
+    config: Extension<ServerConfig>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    // Every key is compared, so that the time does not depend on which one matches
+    let trusted = bearer_token(request.headers()).is_some_and(|token| {
+        config
+            .trusted_api_keys
+            .iter()
+            .fold(false, |trusted, key| constant_time_eq(key, token) | trusted)
+    });
+    if let (true, Some(trusted_max_batch)) = (trusted, config.trusted_max_batch) {
+        if let Some(info) = request.extensions_mut().get_mut::<Info>() {
+            info.max_client_batch_size = info.max_client_batch_size.max(trusted_max_batch);
+        }
+    }
+    next.run(request).await
+}
 
-            metrics::increment_counter!("te_request_success", "method" => "single");
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
 
-            (
-                vec![OpenAICompatEmbedding {
-                    object: "embedding",
-                    embedding: response.results,
-                    index: 0,
-                }],
-                ResponseMetadata::new(
-                    compute_chars,
-                    response.prompt_tokens,
-                    start_time,
-                    response.tokenization,
-                    response.queue,
-                    response.inference,
-                ),
-            )
-        }
-        Input::Batch(inputs) => {
-            metrics::increment_counter!("te_request_count", "method" => "batch");
+#[derive(Clone, Default)]
+struct ConcurrencyLimits {
+    /// Caps of the endpoints configured with `ENDPOINT_MAX_CONCURRENCY`, by path
+    endpoints: Arc<HashMap<String, Arc<Semaphore>>>,
+    /// Cap of the batch requests in flight configured with `MAX_INFLIGHT_BATCHES`
+    batches: Option<Arc<Semaphore>>,
+}
 
-            let batch_size = inputs.len();
-            if batch_size > info.max_client_batch_size {
-                let message = format!(
-                    "batch size {batch_size} > maximum allowed batch size {}",
-                    info.max_client_batch_size
-                );
-                tracing::error!("{message}");
-                let err = ErrorResponse {
-                    error: message,
-                    error_type: ErrorType::Validation,
-                };
-                metrics::increment_counter!("te_request_failure", "err" => "batch_size");
-                Err(err)?;
+impl ConcurrencyLimits {
+    /// Reserve a slot for a batch request, released when the permit is dropped
+    ///
+    /// Batch requests hold their results until they are serialized, so the slot is kept until
+    /// the response is built. Requests above the cap are rejected with a 429 instead of queueing.
+    fn acquire_batch(&self) -> Result<Option<OwnedSemaphorePermit>, ErrorResponse> {
+        let batches = match &self.batches {
+            Some(batches) => batches.clone(),
+            None => return Ok(None),
+        };
+        batches.try_acquire_owned().map(Some).map_err(|_| {
+            metrics::increment_counter!("te_request_failure", "err" => "inflight_batches");
+            let message = "Too many batch requests in flight".to_string();
+            tracing::error!("{message}");
+            ErrorResponse {
+                error: message,
+                error_type: ErrorType::Overloaded,
+                code: "too_many_batches".to_string(),
             }
+        })
+    }
+}
 
-            let mut futures = Vec::with_capacity(batch_size);
-            let mut compute_chars = 0;
+/// Reject requests with a 503 when their endpoint is at its concurrency cap instead of queueing
+async fn shed_load<B>(
+    limits: Extension<ConcurrencyLimits>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let path = request.uri().path().to_string();
+    let semaphore = match limits.endpoints.get(&path) {
+        Some(semaphore) => semaphore.clone(),
+        None => return Ok(next.run(request).await),
+    };
 
-            for input in inputs {
-                compute_chars += input.chars().count();
+    let _permit = semaphore.try_acquire_owned().map_err(|_| {
+        metrics::increment_counter!("te_load_shed", "path" => path.clone());
+        ErrorResponse {
+            error: format!("Too many concurrent requests on {path}"),
+            error_type: ErrorType::Unavailable,
+            code: "load_shed".to_string(),
+        }
+    })?;
+    Ok(next.run(request).await)
+}
 
-                let local_infer = infer.clone();
-                futures.push(async move {
-                    let permit = local_infer.acquire_permit().await;
-                    local_infer.embed(input, false, true, permit).await
-                })
-            }
-            let results = join_all(futures)
-                .await
-                .into_iter()
-                .collect::<Result<Vec<InferResponse>, TextEmbeddingsError>>()
-                .map_err(ErrorResponse::from)?;
+/// Count the request as in flight on its connection until its response body is sent, so that the
+/// connection is not closed for being idle while the request is served
+async fn track_in_flight<B>(
+    ConnectInfo(connection): ConnectInfo<Connection>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let in_flight = connection.start();
+    let response = next.run(request).await;
+    // The body may be streamed long after the handler returned
+    response.map(|body| {
+        axum::body::boxed(body.map_data(move |data| {
+            let _ = &in_flight;
+            data
+        }))
+    })
+}
 
-            let mut embeddings = Vec::with_capacity(batch_size);
-            let mut total_tokenization_time = 0;
-            let mut total_queue_time = 0;
-            let mut total_inference_time = 0;
-            let mut total_compute_tokens = 0;
+/// Count the input tokens dropped by truncation in the request, for [`truncation_headers`]
+async fn count_truncation<B>(request: Request<B>, next: Next<B>) -> Response {
+    count_truncated_tokens(next.run(request)).await
+}
 
-            for (i, r) in results.into_iter().enumerate() {
-                total_tokenization_time += r.tokenization.as_nanos() as u64;
-                total_queue_time += r.queue.as_nanos() as u64;
-                total_inference_time += r.inference.as_nanos() as u64;
-                total_compute_tokens += r.prompt_tokens;
-                embeddings.push(OpenAICompatEmbedding {
-                    object: "embedding",
-                    embedding: r.results,
-                    index: i,
-                });
+/// Keep the inputs of requests sent with `X-No-Log` out of logs and traces
+///
+/// Timings and token counts are still recorded.
+async fn no_log<B>(request: Request<B>, next: Next<B>) -> Response {
+    let redact = no_log_requested(request.headers());
+    redact_inputs(redact, next.run(request)).await
+}
+
+/// Check if the client sent `X-No-Log` with any value but `0` or `false`
+fn no_log_requested(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-no-log")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| !matches!(value.trim(), "0" | "false"))
+        .unwrap_or(false)
+}
+
+/// Select the model that serves the request from the `X-Model-Id` header
+///
+/// The selected model, or the default one, is stored as `Infer` and `Info` in the request
+/// extensions. It is resolved per request so that reloads apply to the next requests.
+async fn select_model<B>(
+    models: Extension<ModelRegistry>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let model_id = request
+        .headers()
+        .get("x-model-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    let (infer, info) = match model_id {
+        Some(model_id) => models.get(model_id).map_err(|err| {
+            metrics::increment_counter!("te_request_failure", "err" => "model_not_found");
+            err
+        })?,
+        None => match models.default_model() {
+            Some(model) => model,
+            // The backend of a lazily loaded model is started by `load_lazy_model`
+            None => {
+                request.extensions_mut().insert(models.default_info());
+                return Ok(next.run(request).await);
             }
-            let batch_size = batch_size as u64;
+        },
+    };
+    request.extensions_mut().insert(infer);
+    request.extensions_mut().insert(info);
+    Ok(next.run(request).await)
+}
 
-            metrics::increment_counter!("te_request_success", "method" => "batch");
+/// Start the backend of a lazily loaded model on the first inference request
+///
+/// Only layered on the routes running the model, inside their authentication, so that
+/// unauthenticated requests and unknown routes do not start it.
+async fn load_lazy_model<B>(
+    models: Extension<ModelRegistry>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    if request.method() == Method::POST && request.extensions().get::<Infer>().is_none() {
+        let (infer, mut info) = models.load_default().await?;
+        // Keep the client batch size limit set for the API key
+        if let Some(current) = request.extensions().get::<Info>() {
+            info.max_client_batch_size = current.max_client_batch_size;
+        }
+        request.extensions_mut().insert(infer);
+        request.extensions_mut().insert(info);
+    }
+    Ok(next.run(request).await)
+}
 
-            (
-                embeddings,
-                ResponseMetadata::new(
-                    compute_chars,
-                    total_compute_tokens,
-                    start_time,
-                    Duration::from_nanos(total_tokenization_time / batch_size),
-                    Duration::from_nanos(total_queue_time / batch_size),
-                    Duration::from_nanos(total_inference_time / batch_size),
-                ),
-            )
+/// Conditional request handling for CDN caching of embeddings
+///
+/// The `ETag` is a hash of the served model, the headers that change the response and the
+/// request body with sorted keys, so that identical requests get the same tag. Only complete
+/// `200 OK` responses are tagged.
+///
+/// With API keys, the credential is part of the tag and responses are only cacheable by the
+/// client, so that shared caches do not serve the embeddings of a tenant to another one.
+async fn http_cache(
+    config: Extension<ServerConfig>,
+    info: Extension<Info>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if !config.http_cache {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match Bytes::from_request(Request::new(body), &()).await {
+        Ok(body) => body,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    // Invalid bodies are rejected by the handler
+    let etag = match from_slice::<serde_json::Value>(&body) {
+        Ok(value) => {
+            let mut hasher = Sha256::new();
+            hasher.update(parts.uri.path());
+            hasher.update([0]);
+            hasher.update(&info.model_id);
+            hasher.update([0]);
+            hasher.update(info.model_sha.as_deref().unwrap_or_default());
+            for name in [
+                header::ACCEPT.as_str(),
+                "x-model-id",
+                header::AUTHORIZATION.as_str(),
+            ] {
+                hasher.update([0]);
+                let value = parts.headers.get(name).map(HeaderValue::as_bytes);
+                hasher.update(value.unwrap_or_default());
+            }
+            hasher.update([0]);
+            hasher.update(value.to_string());
+            format!("\"{:x}\"", hasher.finalize())
         }
+        Err(_) => return next.run(Request::from_parts(parts, Body::from(body))).await,
+    };
+    let visibility = match config.authenticator {
+        Some(_) => "private",
+        None => "public",
     };
+    let cache_control = format!(
+        "{visibility}, max-age={}",
+        config.http_cache_max_age.as_secs()
+    );
 
-    metadata.record_span(&span);
-    metadata.record_metrics();
+    let not_modified = parts
+        .headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag);
 
-    let compute_tokens = metadata.compute_tokens;
-    let headers = HeaderMap::from(metadata);
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        next.run(Request::from_parts(parts, Body::from(body))).await
+    };
 
-    tracing::info!("Success");
+    // Partial responses of timed out batches, and any other status, are not cached
+    if response.status() == StatusCode::OK || response.status() == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        // Both values are ASCII
+        headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&cache_control).unwrap(),
+        );
+        if config.authenticator.is_some() {
+            headers.insert(header::VARY, HeaderValue::from_static("authorization"));
+        }
+    }
+    response
+}
 
-    let response = OpenAICompatResponse {
-        object: "list",
-        data: embeddings,
-        model: info.model_id.clone(),
-        usage: OpenAICompatUsage {
-            prompt_tokens: compute_tokens,
-            total_tokens: compute_tokens,
-        },
-    };
-    Ok((headers, Json(response)))
+/// IP of the client that sent the request
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ClientIp(pub IpAddr);
+
+/// Resolve the client IP, store it in the request extensions and record it in the request span
+///
+/// `X-Forwarded-For` is only used if the peer is a trusted proxy, otherwise clients could spoof it.
+async fn resolve_client_ip<B>(
+    config: Extension<ServerConfig>,
+    ConnectInfo(connection): ConnectInfo<Connection>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let peer = connection.remote_addr;
+    let ip = client_ip(peer.ip(), request.headers(), &config.trusted_proxies);
+    // Field of the span created by `OtelAxumLayer`
+    tracing::Span::current().record("http.client.address", ip.to_string());
+    request.extensions_mut().insert(ClientIp(ip));
+    next.run(request).await
+}
+
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    // Walk the chain from the closest hop and stop at the first address not set by a trusted proxy
+    let mut ip = peer;
+    for hop in forwarded.into_iter().rev() {
+        match hop.parse::<IpAddr>() {
+            Ok(hop) => {
+                ip = hop;
+                if !is_trusted(&hop) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    ip
 }
 
 /// Prometheus metrics scrape endpoint
@@ -813,8 +4186,8 @@ async fn metrics(prom_handle: Extension<PrometheusHandle>) -> String {
 
 /// Serving method
 pub async fn run(
-    infer: Infer,
-    info: Info,
+    model: PreparedModel,
+    options: ModelOptions,
     addr: SocketAddr,
     prom_builder: PrometheusBuilder,
 ) -> Result<(), anyhow::Error> {
@@ -824,34 +4197,95 @@ pub async fn run(
     paths(
     get_model_info,
     health,
+    health_detail,
+    live,
+    ready,
     predict,
     rerank,
     embed,
     openai_embed,
+    weaviate_embed,
+    weaviate_embed_batch,
     metrics,
+    similarity,
+    classify_by_similarity,
+    count_tokens,
+    submit_job,
+    get_job,
+    warmup,
+    bench,
+    reload,
+    set_log_level,
     ),
     components(
     schemas(
     PredictInput,
     Input,
     Info,
+    MetaResponse,
     ModelType,
     ClassifierModel,
     EmbeddingModel,
     PredictRequest,
+    PredictFormat,
     Prediction,
+    Predictions,
     PredictResponse,
     OpenAICompatRequest,
     OpenAICompatEmbedding,
+    OpenAICompatVector,
+    EncodingFormat,
     OpenAICompatUsage,
     OpenAICompatResponse,
     RerankRequest,
     Rank,
+    RankField,
+    ScoreScale,
+    Duplicates,
     RerankResponse,
+    EmbedInput,
     EmbedRequest,
     EmbedResponse,
+    Embedding,
+    ChunkedEmbeddings,
+    EmbeddingChunk,
+    StreamedEmbedding,
+    Segment,
+    Dimension,
+    TokenAttribution,
+    RawOutput,
+    DetectedLanguage,
+    SimilarityRequest,
+    SimilarityMetric,
+    SimilarityScore,
+    SimilarityResponse,
+    ClassifyBySimilarityRequest,
+    LabelSimilarity,
+    ClassifyBySimilarityResponse,
+    CountTokensRequest,
+    TokenCount,
+    CountTokensResponse,
+    JobRequest,
+    JobStatus,
+    JobResponse,
+    ReloadRequest,
+    LogLevel,
+    HealthDetailResponse,
+    WarmupRequest,
+    WarmupResponse,
+    BenchRequest,
+    BenchResponse,
     ErrorResponse,
     OpenAICompatErrorResponse,
+    EmbedWeaviateRequest,
+    EmbedWeaviateResponse,
+    EmbedWeaviateBatchRequest,
+    EmbedWeaviateBatchResponse,
+    WeaviateErrorResponse,
+    WeaviateErrorMessage,
+    WeaviateVectorizeConfig,
+    WeaviateTask,
+    VectorDtype,
     ErrorType,
     )
     ),
@@ -867,88 +4301,298 @@ pub async fn run(
     )]
     struct ApiDoc;
 
-    // CORS allowed origins
-    // map to go inside the option and then map to parse from String to HeaderValue
-    // Finally, convert to AllowOrigin
-    let allow_origin: Option<AllowOrigin> =
-        env::var("CORS_ALLOW_ORIGIN").ok().map(|cors_allow_origin| {
-            let cors_allow_origin = cors_allow_origin.split(',');
-            AllowOrigin::list(
-                cors_allow_origin.map(|origin| origin.parse::<HeaderValue>().unwrap()),
-            )
-        });
+    let config = ServerConfig::from_env()?;
+    let jobs = JobStore::new(config.job_ttl, config.max_jobs);
+    tokio::spawn(jobs.clone().evict_expired());
+    let shutting_down = ShuttingDown::default();
+    let shutdown_grace_period = config.shutdown_grace_period;
+    let trim_slash = config.trim_trailing_slash;
+    let http_version = config.http_version;
+    let max_connections = config.max_connections;
+    let connection_idle_timeout = config.connection_idle_timeout;
+    let models = if config.lazy_load {
+        tracing::info!("The model backend will be started on the first request");
+        ModelRegistry::lazy(model)
+    } else {
+        let (infer, info) = model.start().await?;
+        ModelRegistry::new(infer, info)
+    };
+    for (i, model_id) in config.additional_model_ids.iter().enumerate() {
+        tracing::info!("Starting additional model {model_id}");
+        let options = options.with_uds_suffix(&i.to_string());
+        let (infer, info) = load_model(model_id.clone(), options)
+            .await
+            .with_context(|| format!("Could not load model {model_id}"))?;
+        models.insert(infer, info);
+    }
+    let info = models.default_info();
+    config.check_reference_set(&info)?;
+    if config.report_device_stats {
+        tokio::spawn(sample_device_stats(models.clone()));
+    }
 
-    let prom_handle = prom_builder
-        .install_recorder()
-        .context("failed to install metrics recorder")?;
+    let prom_handle =
+        prometheus::install_recorder(prom_builder).context("failed to install metrics recorder")?;
 
     // CORS layer
-    let allow_origin = allow_origin.unwrap_or(AllowOrigin::any());
+    let allow_origin = match config.cors_allow_origin.clone() {
+        Some(origins) => AllowOrigin::list(origins),
+        None => AllowOrigin::any(),
+    };
     let cors_layer = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST])
+        .allow_methods([Method::GET, Method::POST, Method::PUT])
         .allow_headers(any())
         .allow_origin(allow_origin);
 
+    let root = match config.root_response {
+        RootResponse::Health => get(health),
+        RootResponse::Info => get(root_info),
+    };
+
     // Create router
+    let cache = middleware::from_fn(http_cache);
+    let auth = middleware::from_fn(authenticate);
+    let load = middleware::from_fn(load_lazy_model);
     let app = Router::new()
-        .merge(SwaggerUi::new("/docs").url("/api-doc/openapi.json", ApiDoc::openapi()))
         // Base routes
-        .route("/embed", post(embed))
+        .route("/embed", post(embed).layer(cache.clone()))
         .route("/predict", post(predict))
         .route("/rerank", post(rerank))
         // OpenAI compat route
-        .route("/embeddings", post(openai_embed))
+        .route("/embeddings", post(openai_embed).layer(cache.clone()))
         // Weaviate compat route
-        .route("/vectors", post(weaviate_embed))
-        .route("/vectors/", post(weaviate_embed)) 
+        .route("/vectors", post(weaviate_embed).layer(cache.clone()))
+        .route("/vectors/", post(weaviate_embed).layer(cache.clone())) 
+        .route(
+            "/vectors/batch",
+            post(weaviate_embed_batch).layer(cache.clone()),
+        )
+        // Similarity route
+        .route("/similarity", post(similarity))
+        // Token counting route
+        .route("/count_tokens", post(count_tokens))
+        // Asynchronous jobs
+        .route("/jobs", post(submit_job))
+        .route("/jobs/:id", get(get_job))
+        // The routes above are inference routes
+        .route_layer(load.clone())
+        .route_layer(auth.clone())
         .route("/.well-known/live", get(live))
         .route("/.well-known/ready", get(ready))
         .route("/meta", get(get_model_info))
         // Base Health route
         .route("/health", get(health))
-        // Inference API health route
-        .route("/", get(health))
+        // Inference API health route, or landing document
+        .route("/", root)
         // AWS Sagemaker health route
         .route("/ping", get(health))
         // Prometheus metrics route
         .route("/metrics", get(metrics));
 
+    // Sampling device stats waits for the running batch
+    let app = if config.report_device_stats {
+        app.route("/health/detail", get(health_detail))
+    } else {
+        app
+    };
+
+    // Only served with a reference set to search
+    let app = if config.reference_set.is_some() {
+        app.route(
+            "/classify_by_similarity",
+            post(classify_by_similarity)
+                .layer(load.clone())
+                .layer(auth.clone()),
+        )
+    } else {
+        app
+    };
+
     // Set default routes
     let app = match &info.model_type {
         ModelType::Classifier(_) => {
-            app.route("/", post(predict))
+            app.route("/", post(predict).layer(load.clone()).layer(auth.clone()))
                 // AWS Sagemaker route
-                .route("/invocations", post(predict))
+                .route(
+                    "/invocations",
+                    post(predict).layer(load.clone()).layer(auth.clone()),
+                )
         }
         ModelType::Reranker(_) => {
-            app.route("/", post(rerank))
+            app.route("/", post(rerank).layer(load.clone()).layer(auth.clone()))
                 // AWS Sagemaker route
-                .route("/invocations", post(rerank))
+                .route(
+                    "/invocations",
+                    post(rerank).layer(load.clone()).layer(auth.clone()),
+                )
         }
         ModelType::Embedding(_) => {
-            app.route("/", post(embed))
-                // AWS Sagemaker route
-                .route("/invocations", post(embed))
+            app.route(
+                "/",
+                post(embed)
+                    .layer(cache.clone())
+                    .layer(load.clone())
+                    .layer(auth.clone()),
+            )
+            // AWS Sagemaker route
+            .route(
+                "/invocations",
+                post(embed)
+                    .layer(cache.clone())
+                    .layer(load.clone())
+                    .layer(auth.clone()),
+            )
+        }
+    };
+
+    let limits = ConcurrencyLimits {
+        endpoints: Arc::new(
+            config
+                .endpoint_concurrency
+                .iter()
+                .map(|(path, limit)| (path.clone(), Arc::new(Semaphore::new(*limit))))
+                .collect(),
+        ),
+        batches: config
+            .max_inflight_batches
+            .map(|limit| Arc::new(Semaphore::new(limit))),
+    };
+    // Paths are matched before the route prefix is added
+    let app = app.layer(middleware::from_fn(shed_load));
+
+    // Admin routes are only served when API keys are configured
+    let app = if config.authenticator.is_some() {
+        let admin = Router::new()
+            .route("/admin/warmup", post(warmup).layer(load.clone()))
+            .route("/admin/bench", post(bench).layer(load.clone()))
+            .route("/admin/reload", post(reload))
+            .route("/admin/log_level", put(set_log_level))
+            .route_layer(middleware::from_fn(require_api_key));
+        app.merge(admin)
+    } else {
+        app
+    };
+
+    // Serve all routes under the route prefix
+    let app = match &config.route_prefix {
+        Some(prefix) => {
+            let app = Router::new().nest(prefix, app);
+            if config.unprefixed_health {
+                // Orchestrator probes usually expect `/health` at the root
+                app.route("/health", get(health))
+            } else {
+                app
+            }
         }
+        None => app,
     };
 
+    let prefix = config.route_prefix.as_deref().unwrap_or_default();
+    let docs_path: Arc<str> = Arc::from(format!("{prefix}/docs"));
+    let root_info = RootInfo {
+        name: "text-embeddings-inference",
+        version: info.version,
+        model_id: String::new(),
+        docs: docs_path.to_string(),
+        endpoints: ApiDoc::openapi()
+            .paths
+            .paths
+            .into_keys()
+            .filter(|path| config.authenticator.is_some() || !path.starts_with("/admin"))
+            .filter(|path| config.report_device_stats || path != "/health/detail")
+            .filter(|path| config.reference_set.is_some() || path != "/classify_by_similarity")
+            .map(|path| format!("{prefix}{path}"))
+            .collect(),
+    };
+    let app = app.merge(
+        SwaggerUi::new(docs_path.to_string())
+            .url(format!("{prefix}/api-doc/openapi.json"), ApiDoc::openapi()),
+    );
+    // Unknown routes get a JSON error instead of an empty body
+    let app = app.fallback(not_found);
+
     let app = app
-        .layer(Extension(infer))
-        .layer(Extension(info))
+        // Run after `select_model` inserted the model info
+        .layer(middleware::from_fn(debug_headers))
+        .layer(middleware::from_fn(trusted_batch_limit))
+        .layer(middleware::from_fn(select_model))
+        .layer(middleware::from_fn(resolve_client_ip))
+        .layer(middleware::from_fn(no_log))
+        .layer(middleware::from_fn(count_truncation))
+        .layer(middleware::from_fn(track_in_flight))
+        .layer(Extension(models))
+        .layer(Extension(options))
+        .layer(Extension(config))
+        .layer(Extension(AdminLocks::default()))
+        .layer(Extension(ProbedDimensions::default()))
+        .layer(Extension(root_info))
+        .layer(Extension(limits))
+        .layer(Extension(jobs))
+        .layer(Extension(shutting_down.clone()))
         .layer(Extension(prom_handle.clone()))
         .layer(OtelAxumLayer::default())
         .layer(cors_layer);
 
+    // Paths must be rewritten before routing, so the router is served as the fallback of a router
+    // applying the rewrite
+    let app = if trim_slash {
+        Router::new()
+            .fallback_service(app)
+            .layer(middleware::map_request_with_state(
+                docs_path,
+                trim_trailing_slash,
+            ))
+    } else {
+        app
+    };
+
     // Run server
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+    let incoming = LimitedIncoming::new(
+        AddrIncoming::bind(&addr)?,
+        max_connections,
+        connection_idle_timeout,
+    );
+    tracing::info!("Ready");
+    axum::Server::builder(incoming)
+        .http1_only(http_version == HttpVersion::Http1)
+        .http2_only(http_version == HttpVersion::Http2)
+        .serve(app.into_make_service_with_connect_info::<Connection>())
         // Wait until all requests are finished to shut down
-        .with_graceful_shutdown(shutdown::shutdown_signal())
+        .with_graceful_shutdown(shutdown::shutdown_signal(
+            shutting_down,
+            shutdown_grace_period,
+        ))
         .await?;
 
     Ok(())
 }
 
+/// Remove the trailing slashes of a request path, so that `/embed/` is routed to `/embed`
+///
+/// Paths under `docs_path` are left as is: the Swagger UI redirects its root to the path with a
+/// trailing slash.
+async fn trim_trailing_slash<B>(
+    State(docs_path): State<Arc<str>>,
+    mut req: Request<B>,
+) -> Request<B> {
+    let path = req.uri().path();
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.len() == path.len() || trimmed.is_empty() || path.starts_with(&*docs_path) {
+        return req;
+    }
+
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{trimmed}?{query}"),
+        None => trimmed.to_string(),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *req.uri_mut() = uri;
+    }
+    req
+}
+
 impl From<&ErrorType> for StatusCode {
     fn from(value: &ErrorType) -> Self {
         match value {
@@ -957,16 +4601,38 @@ impl From<&ErrorType> for StatusCode {
             ErrorType::Overloaded => StatusCode::TOO_MANY_REQUESTS,
             ErrorType::Tokenizer => StatusCode::UNPROCESSABLE_ENTITY,
             ErrorType::Validation => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorType::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorType::NotFound => StatusCode::NOT_FOUND,
+            ErrorType::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorType::InvalidParameter => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+/// Status code and error `type` recognized by OpenAI clients
+fn openai_error_kind(error_type: &ErrorType) -> (StatusCode, &'static str) {
+    match error_type {
+        ErrorType::Validation | ErrorType::Tokenizer | ErrorType::InvalidParameter => {
+            (StatusCode::BAD_REQUEST, "invalid_request_error")
+        }
+        ErrorType::Unauthorized => (StatusCode::UNAUTHORIZED, "authentication_error"),
+        ErrorType::NotFound => (StatusCode::NOT_FOUND, "invalid_request_error"),
+        ErrorType::Overloaded => (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error"),
+        ErrorType::Backend => (StatusCode::INTERNAL_SERVER_ERROR, "server_error"),
+        ErrorType::Unhealthy | ErrorType::Unavailable => {
+            (StatusCode::SERVICE_UNAVAILABLE, "server_error")
         }
     }
 }
 
 impl From<ErrorResponse> for OpenAICompatErrorResponse {
     fn from(value: ErrorResponse) -> Self {
+        let (status, error_type) = openai_error_kind(&value.error_type);
         OpenAICompatErrorResponse {
             message: value.error,
-            code: StatusCode::from(&value.error_type).as_u16(),
-            error_type: value.error_type,
+            code: status.as_u16(),
+            error_type,
+            error_code: value.code,
         }
     }
 }
@@ -978,8 +4644,220 @@ impl From<ErrorResponse> for (StatusCode, Json<ErrorResponse>) {
     }
 }
 
+impl WeaviateErrorResponse {
+    fn new(message: String, protocol: WeaviateProtocol) -> Self {
+        match protocol {
+            WeaviateProtocol::V1 => WeaviateErrorResponse::V1 { error: message },
+            WeaviateProtocol::V2 => WeaviateErrorResponse::V2 {
+                error: vec![WeaviateErrorMessage { message }],
+            },
+        }
+    }
+}
+
+/// Convert an error to the shape of the Weaviate protocol
+///
+/// Overload and availability errors keep their status so that Weaviate can tell them apart from
+/// failed vectorizations
+fn weaviate_error(
+    err: ErrorResponse,
+    protocol: WeaviateProtocol,
+) -> (StatusCode, Json<WeaviateErrorResponse>) {
+    let status = match err.error_type {
+        ErrorType::Backend => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorType::Validation | ErrorType::Tokenizer => StatusCode::UNPROCESSABLE_ENTITY,
+        ref error_type => StatusCode::from(error_type),
+    };
+    (
+        status,
+        Json(WeaviateErrorResponse::new(err.error, protocol)),
+    )
+}
+
 impl From<ErrorResponse> for (StatusCode, Json<OpenAICompatErrorResponse>) {
     fn from(err: ErrorResponse) -> Self {
-        (StatusCode::from(&err.error_type), Json(err.into()))
+        let (status, _) = openai_error_kind(&err.error_type);
+        (status, Json(err.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_ip_trusts_forwarded_for_only_from_trusted_proxies() {
+        let proxies: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let ip = |addr: &str| addr.parse::<IpAddr>().unwrap();
+        let forwarded = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-forwarded-for", value.parse().unwrap());
+            headers
+        };
+
+        // Without trusted proxies the header is ignored
+        let headers = forwarded("1.2.3.4");
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &[]), ip("10.0.0.1"));
+        // A client that is not a trusted proxy cannot spoof its IP
+        assert_eq!(client_ip(ip("5.6.7.8"), &headers, &proxies), ip("5.6.7.8"));
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &proxies), ip("1.2.3.4"));
+        // The chain is walked from the closest hop to the first untrusted address
+        let headers = forwarded("9.9.9.9, 1.2.3.4, 10.0.0.2");
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &proxies), ip("1.2.3.4"));
+        // Hops in several headers are chained in order
+        let mut headers = forwarded("1.2.3.4");
+        headers.append("x-forwarded-for", "10.0.0.2".parse().unwrap());
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &proxies), ip("1.2.3.4"));
+        // An invalid hop stops the walk at the last valid one
+        let headers = forwarded("1.2.3.4, unknown, 10.0.0.2");
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &proxies),
+            ip("10.0.0.2")
+        );
+        // Without the header the peer is the client
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &HeaderMap::new(), &proxies),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn nearest_labels_keeps_the_best_score_of_each_label() {
+        let reference = |label: &str, embedding: Vec<f32>| Reference {
+            label: label.to_string(),
+            embedding,
+        };
+        let references = [
+            reference("first", vec![1.0, 0.0]),
+            reference("second", vec![0.0, 1.0]),
+            reference("first", vec![0.6, 0.8]),
+            reference("third", vec![-1.0, 0.0]),
+        ];
+        let scores = |top_k| {
+            nearest_labels(&[0.0, 1.0], &references, top_k)
+                .into_iter()
+                .map(|label| (label.label, label.score))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            scores(5),
+            vec![
+                ("second".to_string(), 1.0),
+                ("first".to_string(), 0.8),
+                ("third".to_string(), 0.0),
+            ]
+        );
+        assert_eq!(scores(1), vec![("second".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn no_log_is_requested_by_any_value_but_false() {
+        let header = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-no-log", value.parse().unwrap());
+            headers
+        };
+
+        assert!(no_log_requested(&header("1")));
+        assert!(no_log_requested(&header("true")));
+        assert!(no_log_requested(&header("")));
+        assert!(!no_log_requested(&header("0")));
+        assert!(!no_log_requested(&header(" false ")));
+        assert!(!no_log_requested(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn scale_scores_keeps_the_order_of_the_scores() {
+        let scores = vec![2.0, -1.0, 0.0];
+        assert_eq!(scale_scores(scores.clone(), ScoreScale::Raw), scores);
+        assert_eq!(
+            scale_scores(scores.clone(), ScoreScale::Sigmoid),
+            vec![sigmoid(2.0), sigmoid(-1.0), 0.5]
+        );
+        assert_eq!(
+            scale_scores(scores, ScoreScale::Minmax),
+            vec![1.0, 0.0, 1.0 / 3.0]
+        );
+        // Equal scores carry no ranking signal
+        assert_eq!(
+            scale_scores(vec![0.5, 0.5], ScoreScale::Minmax),
+            vec![0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn trim_trailing_slash_keeps_the_query_and_the_docs() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let trimmed = |uri: &str| {
+            let req = Request::builder().uri(uri).body(()).unwrap();
+            let docs_path = State(Arc::from("/docs"));
+            let req = runtime.block_on(trim_trailing_slash(docs_path, req));
+            req.uri().to_string()
+        };
+
+        assert_eq!(trimmed("/embed/"), "/embed");
+        assert_eq!(trimmed("/embed//?truncate=true"), "/embed?truncate=true");
+        assert_eq!(trimmed("/embed"), "/embed");
+        assert_eq!(trimmed("/"), "/");
+        assert_eq!(trimmed("/docs/"), "/docs/");
+    }
+
+    #[test]
+    fn shard_splits_into_contiguous_balanced_parts() {
+        let embedding: Vec<f32> = (0..7).map(|v| v as f32).collect();
+        assert_eq!(
+            shard(&embedding, 3),
+            vec![vec![0.0, 1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]]
+        );
+        assert_eq!(shard(&embedding, 1), vec![embedding.clone()]);
+        // More shards than values leaves the last shards empty
+        assert_eq!(
+            shard(&[1.0, 2.0], 3),
+            vec![vec![1.0], vec![2.0], Vec::new()]
+        );
+    }
+
+    #[test]
+    fn first_occurrences_points_duplicates_to_their_first_index() {
+        let texts: Vec<String> = ["a", "b", "a", "c", "b"].map(String::from).to_vec();
+        assert_eq!(first_occurrences(&texts), vec![0, 1, 0, 3, 1]);
+        assert!(first_occurrences(&[]).is_empty());
+    }
+
+    #[test]
+    fn group_predictions_aggregates_the_labels_of_each_group() {
+        let predictions =
+            [("a", 0.5), ("b", 0.25), ("c", 0.75), ("d", 1.0)].map(|(label, score)| Prediction {
+                score,
+                label: label.to_string(),
+            });
+        let label_groups = HashMap::from([
+            ("a".to_string(), "first".to_string()),
+            ("b".to_string(), "second".to_string()),
+            ("c".to_string(), "first".to_string()),
+        ]);
+        let scores = |aggregation| {
+            group_predictions(&predictions, &label_groups, aggregation)
+                .into_iter()
+                .map(|group| (group.label, group.score))
+                .collect::<Vec<_>>()
+        };
+
+        // Groups are in the order of their first label and `d` has no group
+        assert_eq!(
+            scores(GroupAggregation::Sum),
+            vec![("first".to_string(), 1.25), ("second".to_string(), 0.25)]
+        );
+        assert_eq!(
+            scores(GroupAggregation::Mean),
+            vec![("first".to_string(), 0.625), ("second".to_string(), 0.25)]
+        );
+        assert_eq!(
+            scores(GroupAggregation::Max),
+            vec![("first".to_string(), 0.75), ("second".to_string(), 0.25)]
+        );
     }
 }
@@ -1,13 +1,15 @@
-use crate::ErrorType;
+use crate::{ErrorResponse, Info};
 use serde::de::{SeqAccess, Visitor};
-use serde::{de, Deserialize, Deserializer, Serialize};
+use serde::ser::SerializeMap;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::fmt::Formatter;
 use text_embeddings_core::tokenization::EncodingInput;
 use utoipa::openapi::{RefOr, Schema};
 use utoipa::ToSchema;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum Sequence {
     Single(String),
     Pair(String, String),
@@ -47,6 +49,8 @@ impl<'de> Deserialize<'de> for PredictInput {
         enum Internal {
             Single(String),
             Multiple(Vec<String>),
+            // Kept so that malformed elements can be reported with their index
+            Invalid(de::IgnoredAny),
         }
 
         struct PredictInputVisitor;
@@ -73,66 +77,89 @@ impl<'de> Deserialize<'de> for PredictInput {
             where
                 A: SeqAccess<'de>,
             {
-                let sequence_from_vec = |mut value: Vec<String>| {
-                    // Validate that value is correct
-                    match value.len() {
-                        1 => Ok(Sequence::Single(value.pop().unwrap())),
-                        2 => {
-                            // Second element is last
-                            let second = value.pop().unwrap();
-                            let first = value.pop().unwrap();
-                            Ok(Sequence::Pair(first, second))
-                        }
-                        // Sequence can only be a single string or a pair of strings
-                        _ => Err(de::Error::invalid_length(value.len(), &self)),
-                    }
+                // The first element determines if the input is a single sequence or a batch
+                let first = match seq.next_element::<Internal>()? {
+                    Some(first) => first,
+                    None => return Err(de::Error::custom("`inputs` cannot be an empty list")),
                 };
 
-                // Get first element
-                // This will determine if input is a batch or not
-                let s = match seq
-                    .next_element::<Internal>()?
-                    .ok_or_else(|| de::Error::invalid_length(0, &self))?
-                {
-                    // Input is not a batch
-                    // Return early
-                    Internal::Single(value) => {
-                        // Option get second element
-                        let second = seq.next_element()?;
-
-                        if seq.next_element::<String>()?.is_some() {
-                            // Error as we do not accept > 2 elements
-                            return Err(de::Error::invalid_length(3, &self));
-                        }
-
-                        if let Some(second) = second {
-                            // Second element exists
-                            // This is a pair
-                            return Ok(PredictInput::Single(Sequence::Pair(value, second)));
-                        } else {
-                            // Second element does not exist
-                            return Ok(PredictInput::Single(Sequence::Single(value)));
-                        }
-                    }
-                    // Input is a batch
-                    Internal::Multiple(value) => sequence_from_vec(value),
-                }?;
+                let first = match first {
+                    Internal::Single(first) => return single_or_pair(first, seq),
+                    value => sequence_from_element(0, value)?,
+                };
 
                 let mut batch = Vec::with_capacity(32);
-                // Push first sequence
-                batch.push(s);
-
-                // Iterate on all sequences
-                while let Some(value) = seq.next_element::<Vec<String>>()? {
-                    // Validate sequence
-                    let s = sequence_from_vec(value)?;
-                    // Push to batch
-                    batch.push(s);
+                batch.push(first);
+                while let Some(value) = seq.next_element::<Internal>()? {
+                    batch.push(sequence_from_element(batch.len(), value)?);
                 }
                 Ok(PredictInput::Batch(batch))
             }
         }
 
+        /// Read the rest of a list starting with a string: it must be a single string or a pair
+        fn single_or_pair<'de, A>(first: String, mut seq: A) -> Result<PredictInput, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let second = match seq.next_element::<Internal>()? {
+                None => return Ok(PredictInput::Single(Sequence::Single(first))),
+                Some(Internal::Single(second)) => second,
+                Some(_) => {
+                    return Err(de::Error::custom(
+                        "invalid pair: expected [string, string], the second element is not a \
+                        string",
+                    ))
+                }
+            };
+
+            // Count the extra elements whatever their type to report the length of the list
+            let mut len = 2;
+            while seq.next_element::<de::IgnoredAny>()?.is_some() {
+                len += 1;
+            }
+            if len > 2 {
+                return Err(de::Error::custom(format!(
+                    "invalid pair: expected [string, string], got {len} elements"
+                )));
+            }
+            Ok(PredictInput::Single(Sequence::Pair(first, second)))
+        }
+
+        /// Convert an element of a batch to a sequence
+        fn sequence_from_element<E: de::Error>(
+            index: usize,
+            value: Internal,
+        ) -> Result<Sequence, E> {
+            let value = match value {
+                Internal::Multiple(value) => value,
+                Internal::Single(_) => {
+                    return Err(E::custom(format!(
+                        "invalid batch element {index}: \
+                        expected a list [string] or [string, string], got a string"
+                    )))
+                }
+                Internal::Invalid(_) => {
+                    return Err(E::custom(format!(
+                        "invalid batch element {index}: \
+                        expected a list [string] or [string, string]"
+                    )))
+                }
+            };
+
+            let len = value.len();
+            let mut strings = value.into_iter();
+            match (strings.next(), strings.next(), strings.next()) {
+                (Some(first), None, _) => Ok(Sequence::Single(first)),
+                (Some(first), Some(second), None) => Ok(Sequence::Pair(first, second)),
+                // Sequence can only be a single string or a pair of strings
+                _ => Err(E::custom(format!(
+                    "invalid batch element {index}: \
+                    expected a single string or a pair of strings, got {len} strings"
+                ))),
+            }
+        }
+
         deserializer.deserialize_any(PredictInputVisitor)
     }
 }
@@ -199,9 +226,35 @@ pub(crate) struct PredictRequest {
     #[serde(default)]
     #[schema(default = "false", example = "false")]
     pub truncate: bool,
+    /// Add the special tokens of the model, e.g. `[CLS]` and `[SEP]`, to the inputs
+    #[serde(default = "default_add_special_tokens")]
+    #[schema(default = "true", example = "true")]
+    pub add_special_tokens: bool,
     #[serde(default)]
     #[schema(default = "false", example = "false")]
     pub raw_scores: bool,
+    /// Also return the unmodified classifier output, in model label order
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub return_logits: bool,
+    #[serde(default)]
+    #[schema(default = "list", example = "map")]
+    pub format: PredictFormat,
+    /// Also return the scores of the label groups configured with `LABEL_GROUPS_PATH`, aggregated
+    /// as set by `LABEL_GROUP_AGGREGATION`
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub aggregate_groups: bool,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PredictFormat {
+    /// Predictions sorted by descending score
+    #[default]
+    List,
+    /// Scores keyed by label, in model label order
+    Map,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -212,11 +265,46 @@ pub(crate) struct Prediction {
     pub label: String,
 }
 
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum Predictions {
+    List(Vec<Prediction>),
+    #[schema(value_type = BTreeMap<String, f32>, example = json!({"admiration": 0.5, "amusement": 0.1}))]
+    Map(#[serde(serialize_with = "serialize_label_scores")] Vec<Prediction>),
+    /// Predictions of the labels and of their groups, when `aggregate_groups` is set
+    Grouped {
+        labels: Box<Predictions>,
+        groups: Box<Predictions>,
+    },
+}
+
+/// Serialize predictions as a `{label: score}` object, keeping their order
+fn serialize_label_scores<S>(predictions: &[Prediction], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut map = serializer.serialize_map(Some(predictions.len()))?;
+    for prediction in predictions {
+        map.serialize_entry(&prediction.label, &prediction.score)?;
+    }
+    map.end()
+}
+
 #[derive(Serialize, ToSchema)]
 #[serde(untagged)]
 pub(crate) enum PredictResponse {
-    Single(Vec<Prediction>),
-    Batch(Vec<Vec<Prediction>>),
+    Single(Predictions),
+    Batch(Vec<Predictions>),
+    SingleWithLogits {
+        predictions: Predictions,
+        #[schema(example = json!([-1.2, 3.4]))]
+        logits: Vec<f32>,
+    },
+    BatchWithLogits {
+        predictions: Vec<Predictions>,
+        #[schema(example = json!([[-1.2, 3.4]]))]
+        logits: Vec<Vec<f32>>,
+    },
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -234,6 +322,67 @@ pub(crate) struct RerankRequest {
     #[serde(default)]
     #[schema(default = "false", example = "false")]
     pub return_text: bool,
+    /// Only return these fields in each rank. `text` is only returned if `return_text` is set
+    #[schema(nullable = true, default = "null", example = json!(["index", "score"]))]
+    pub fields: Option<Vec<RankField>>,
+    /// Stream each rank as newline-delimited JSON as soon as it is scored, in completion order
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub stream: bool,
+    /// Scores of another scorer, e.g. BM25, aligned with `texts` and fused with the rerank scores
+    #[schema(nullable = true, default = "null", example = json!([12.5]))]
+    pub prior_scores: Option<Vec<f32>>,
+    /// Weight `w` of the rerank scores in `w * rerank_score + (1 - w) * prior_score`. Both
+    /// scores are min-max normalized over the texts of the request first
+    #[schema(nullable = true, default = "0.5", example = "0.7")]
+    pub fusion_weight: Option<f32>,
+    /// Scale of the returned scores, applied before fusion with `prior_scores`
+    #[serde(default)]
+    #[schema(default = "raw", example = "sigmoid")]
+    pub score_scale: ScoreScale,
+    /// Score each unique text once. Identical texts share their score
+    #[serde(default)]
+    #[schema(default = "false", example = "true")]
+    pub dedupe: bool,
+    /// Ranks returned for identical texts when `dedupe` is set
+    #[serde(default)]
+    #[schema(default = "keep", example = "collapse")]
+    pub duplicates: Duplicates,
+    /// Collapse runs of whitespace and trim the query and the texts before scoring them.
+    /// `return_text` then returns the normalized texts
+    #[serde(default)]
+    #[schema(default = "false", example = "true")]
+    pub normalize_whitespace: bool,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ScoreScale {
+    /// Scores of the model, activated unless `raw_scores` is set
+    #[default]
+    Raw,
+    /// Sigmoid of the logits of the model, in `[0, 1]`. `raw_scores` is ignored
+    Sigmoid,
+    /// Scores min-max scaled to `[0, 1]` over the texts of the request
+    Minmax,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Duplicates {
+    /// One rank per text, at its original index
+    #[default]
+    Keep,
+    /// One rank per unique text, at the index of its first occurrence
+    Collapse,
+}
+
+#[derive(Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RankField {
+    Index,
+    Text,
+    Score,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -247,8 +396,31 @@ pub(crate) struct Rank {
     pub score: f32,
 }
 
+impl Rank {
+    /// Build a JSON object containing only the selected fields
+    pub(crate) fn select(self, fields: &[RankField]) -> serde_json::Value {
+        let mut rank = serde_json::Map::new();
+        if fields.contains(&RankField::Index) {
+            rank.insert("index".to_string(), self.index.into());
+        }
+        if let Some(text) = self.text.filter(|_| fields.contains(&RankField::Text)) {
+            rank.insert("text".to_string(), text.into());
+        }
+        if fields.contains(&RankField::Score) {
+            rank.insert("score".to_string(), self.score.into());
+        }
+        rank.into()
+    }
+}
+
 #[derive(Serialize, ToSchema)]
-pub(crate) struct RerankResponse(pub Vec<Rank>);
+#[serde(untagged)]
+pub(crate) enum RerankResponse {
+    Ranks(Vec<Rank>),
+    /// Ranks restricted to the requested `fields`
+    #[schema(value_type = Vec<Object>)]
+    Selected(Vec<serde_json::Value>),
+}
 
 #[derive(Deserialize, ToSchema)]
 #[serde(untagged)]
@@ -266,14 +438,41 @@ pub(crate) struct OpenAICompatRequest {
     #[allow(dead_code)]
     #[schema(nullable = true, example = "null")]
     pub user: Option<String>,
+    /// Non-standard: write the `data` array incrementally, in the order embeddings complete
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub stream: bool,
+    #[serde(default)]
+    #[schema(default = "float", example = "float")]
+    pub encoding_format: EncodingFormat,
+}
+
+/// Encoding of the embeddings of an OpenAI response
+#[derive(Clone, Copy, Default, Deserialize, Serialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EncodingFormat {
+    /// Arrays of numbers
+    #[default]
+    Float,
+    /// Base64 of the little-endian IEEE 754 `f32` bytes of each embedding, as in the response
+    /// `byte_order`
+    Base64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum OpenAICompatVector {
+    #[schema(example = json!([0.0, 1.0, 2.0]))]
+    Float(Vec<f32>),
+    #[schema(example = "AAAAAAAAgD8AAABA")]
+    Base64(String),
 }
 
 #[derive(Serialize, ToSchema)]
 pub(crate) struct OpenAICompatEmbedding {
     #[schema(example = "embedding")]
     pub object: &'static str,
-    #[schema(example = json!([0.0, 1.0, 2.0]))]
-    pub embedding: Vec<f32>,
+    pub embedding: OpenAICompatVector,
     #[schema(example = "0")]
     pub index: usize,
 }
@@ -294,29 +493,298 @@ pub(crate) struct OpenAICompatResponse {
     #[schema(example = "thenlper/gte-base")]
     pub model: String,
     pub usage: OpenAICompatUsage,
+    /// Echo of the requested encoding, only set for `base64`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true, example = "base64")]
+    pub encoding_format: Option<EncodingFormat>,
+    /// Byte order of the `base64` embeddings, always `little`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true, example = "little")]
+    pub byte_order: Option<&'static str>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum EmbedInput {
+    Positional(Input),
+    /// Inputs keyed by client-supplied ids
+    #[schema(example = json!({"doc1": "What is Deep Learning?"}))]
+    Keyed(BTreeMap<String, String>),
 }
 
 #[derive(Deserialize, ToSchema)]
 pub(crate) struct EmbedRequest {
-    pub inputs: Input,
+    pub inputs: EmbedInput,
     #[serde(default)]
     #[schema(default = "false", example = "false")]
     pub truncate: bool,
     #[serde(default = "default_normalize")]
     #[schema(default = "true", example = "true")]
     pub normalize: bool,
+    /// Add the special tokens of the model, e.g. `[CLS]` and `[SEP]`, to the inputs
+    #[serde(default = "default_add_special_tokens")]
+    #[schema(default = "true", example = "true")]
+    pub add_special_tokens: bool,
+    /// Extract the text content of HTML inputs before tokenization
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub strip_html: bool,
+    /// Return the detected language of each input alongside its embedding
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub detect_language: bool,
+    /// Return the content type of each input, `code`, `table` or `prose`, alongside its
+    /// embedding. The type is guessed from the formatting of the input by a heuristic, not by a
+    /// model
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub classify_content: bool,
+    /// Scale the normalized embeddings to this norm. Implies `normalize`
+    #[schema(nullable = true, default = "null", example = "null")]
+    pub target_norm: Option<f32>,
+    /// Clamp each value of the embeddings to `[min, max]`, after normalization
+    #[schema(value_type = Option<Vec<f32>>, nullable = true, default = "null", example = json!([-1.0, 1.0]))]
+    pub clamp: Option<[f32; 2]>,
+    /// Also return the indices and values of the `top_dims` highest-magnitude dimensions
+    #[schema(nullable = true, default = "null", example = "null")]
+    pub top_dims: Option<usize>,
+    /// Instruction prepended as is to each input. It is always kept whole: only the input is
+    /// truncated
+    #[schema(
+        nullable = true,
+        default = "null",
+        example = "Represent this document for retrieval: "
+    )]
+    pub instruction: Option<String>,
+    /// Name of an instruction of the server registry, exclusive with `instruction`
+    #[schema(nullable = true, default = "null", example = "null")]
+    pub instruction_key: Option<String>,
+    /// Split each input into chunks of at most `chunk_size` tokens and embed every chunk.
+    /// Chunks are shorter when the model input length cannot fit `chunk_size` tokens with the
+    /// instruction and the special tokens. The response groups the chunks by input
+    #[schema(nullable = true, default = "null", example = "null")]
+    pub chunk_size: Option<usize>,
+    /// Number of tokens shared by consecutive chunks
+    #[serde(default)]
+    #[schema(default = "0", example = "0")]
+    pub chunk_overlap: usize,
+    /// Return one embedding per pooling strategy for each input, computed from a single forward
+    /// pass. Takes precedence over `detect_language`, `classify_content`, `top_dims`,
+    /// `return_text`, `return_hash`, `return_confidence` and `return_raw_shape`
+    #[schema(nullable = true, default = "null", example = json!(["mean"]))]
+    pub poolings: Option<Vec<String>>,
+    /// Split each embedding into this number of contiguous sub-vectors, for indexes sharded by
+    /// dimension. The first `dim % shards` sub-vectors have one more dimension than the others.
+    /// Takes precedence over `detect_language`, `classify_content`, `top_dims`, `return_text`,
+    /// `return_hash`, `return_confidence` and `return_raw_shape`
+    #[schema(nullable = true, default = "null", example = "null")]
+    pub shards: Option<usize>,
+    /// Split a single input on this delimiter, or into sentences with `sentences`, and return
+    /// one embedding per segment
+    #[schema(nullable = true, default = "null", example = "sentences")]
+    pub split_on: Option<String>,
+    /// Return each input as sent alongside its embedding
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub return_text: bool,
+    /// Return the SHA-256 of each input as embedded, after `strip_html` and the instruction,
+    /// alongside its embedding
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub return_hash: bool,
+    /// Return a confidence score alongside each embedding: the norm of the embedding pooled by
+    /// the model, before whitening and normalization
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub return_confidence: bool,
+    /// Return the shape and data type of the backend output alongside each embedding.
+    /// Only allowed when the server runs with `DEBUG_MODE`
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub return_raw_shape: bool,
+    /// Also return the token of each input whose removal changes its embedding the most.
+    /// Runs one forward pass per token
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub explain: bool,
+    /// Return `null` with an error for the inputs that failed instead of failing the request.
+    /// Cannot be combined with options changing the shape of the response
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub partial_failure: bool,
+    /// Stream the embeddings as newline-delimited JSON, one line per embedding, in completion
+    /// order
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub stream: bool,
 }
 
 fn default_normalize() -> bool {
-    true
+    crate::DEFAULT_NORMALIZE
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct DetectedLanguage {
+    /// ISO 639-3 code, `und` if the language could not be detected
+    #[schema(example = "eng")]
+    pub code: &'static str,
+    #[schema(example = "0.95")]
+    pub confidence: f32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Embedding {
+    #[schema(nullable = true, default = "null", example = "What is Deep Learning?")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Hex-encoded SHA-256 of the input as embedded
+    #[schema(
+        nullable = true,
+        default = "null",
+        example = "a8df8287eea6566cc627cc0ee2e45b908e5c33ccbc59e2eb9b70023bdf0d80d6"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[schema(example = json!([0.0, 1.0, 2.0]))]
+    pub embedding: Vec<f32>,
+    #[schema(nullable = true, default = "null")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<DetectedLanguage>,
+    /// Content type guessed from the formatting of the input, when `classify_content` is set
+    #[schema(nullable = true, default = "null", example = "prose")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<&'static str>,
+    /// Highest-magnitude dimensions, by decreasing absolute value
+    #[schema(nullable = true, default = "null")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_dims: Option<Vec<Dimension>>,
+    /// Token contributing the most to the embedding, when `explain` is set
+    #[schema(nullable = true, default = "null")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<TokenAttribution>,
+    /// Norm of the embedding pooled by the model, before whitening and normalization, when
+    /// `return_confidence` is set. Inputs the model represents poorly, e.g. out-of-domain or
+    /// nearly empty inputs, tend to have a lower norm than the inputs it was trained on. The scale
+    /// depends on the loaded model: compare with the norms of known-good inputs
+    #[schema(nullable = true, default = "null", example = "12.4")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// Backend output the embedding was taken from, when `return_raw_shape` is set
+    #[schema(nullable = true, default = "null")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<RawOutput>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct RawOutput {
+    /// Shape of the pooled output of the backend batch that computed the embedding,
+    /// `[batch size, dimension]`, before whitening and normalization
+    #[schema(example = json!([8, 384]))]
+    pub shape: Vec<usize>,
+    /// Data type the model runs in. Outputs are converted to `float32`
+    #[schema(example = "float16")]
+    pub dtype: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Dimension {
+    #[schema(example = "42")]
+    pub index: usize,
+    #[schema(example = "-0.31")]
+    pub value: f32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TokenAttribution {
+    /// Position of the token among the tokens of the input, without special tokens
+    #[schema(example = "3")]
+    pub index: usize,
+    #[schema(example = "Learning")]
+    pub token: String,
+    /// Cosine distance between the embeddings of the input with and without the token
+    #[schema(example = "0.12")]
+    pub saliency: f32,
 }
 
 #[derive(Serialize, ToSchema)]
+#[serde(untagged)]
 #[schema(example = json!([[0.0, 1.0, 2.0]]))]
-pub(crate) struct EmbedResponse(pub Vec<Vec<f32>>);
+pub(crate) enum EmbedResponse {
+    Positional(Vec<Vec<f32>>),
+    /// Embeddings keyed by the ids of the request inputs
+    Keyed(BTreeMap<String, Vec<f32>>),
+    /// Embeddings with per-input metadata
+    Detailed(Vec<Embedding>),
+    /// Embeddings with per-input metadata keyed by the ids of the request inputs
+    KeyedDetailed(BTreeMap<String, Embedding>),
+    /// Embeddings of the chunks of each input, when `chunk_size` is set
+    Chunked(Vec<ChunkedEmbeddings>),
+    /// Embeddings by pooling strategy, when `poolings` is set
+    Pooled(Vec<BTreeMap<String, Vec<f32>>>),
+    /// Embeddings by pooling strategy keyed by the ids of the request inputs
+    KeyedPooled(BTreeMap<String, BTreeMap<String, Vec<f32>>>),
+    /// Embeddings of the segments of the input, when `split_on` is set
+    Segmented(Vec<Segment>),
+    /// Embeddings split into contiguous dimension ranges, when `shards` is set
+    Sharded(Vec<Vec<Vec<f32>>>),
+    /// Sharded embeddings keyed by the ids of the request inputs
+    KeyedSharded(BTreeMap<String, Vec<Vec<f32>>>),
+    /// Embeddings and errors aligned with the inputs, when `partial_failure` is set
+    PartialFailure {
+        #[schema(example = json!([[0.0, 1.0, 2.0], null]))]
+        embeddings: Vec<Option<Vec<f32>>>,
+        #[schema(example = json!([null, {"error": "Input validation error: `inputs` cannot be empty", "error_type": "validation", "code": "input_empty"}]))]
+        errors: Vec<Option<ErrorResponse>>,
+    },
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Segment {
+    #[schema(example = "This is a sentence.")]
+    pub text: String,
+    #[schema(example = json!([0.0, 1.0, 2.0]))]
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ChunkedEmbeddings {
+    /// Index of the input the chunks were cut from
+    #[schema(example = "0")]
+    pub index: usize,
+    pub chunks: Vec<EmbeddingChunk>,
+}
+
+/// Embedding streamed by `/embed`
+#[derive(Serialize, ToSchema)]
+pub(crate) struct StreamedEmbedding {
+    /// Index of the input
+    #[schema(example = "0")]
+    pub index: usize,
+    #[schema(example = json!([0.0, 1.0, 2.0]))]
+    pub values: Vec<f32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct EmbeddingChunk {
+    #[schema(example = json!([0.0, 1.0, 2.0]))]
+    pub embedding: Vec<f32>,
+    /// First token of the chunk in the tokens of the input, without special tokens
+    #[schema(example = "0")]
+    pub token_start: usize,
+    /// End of the chunk, exclusive, in the tokens of the input
+    #[schema(example = "256")]
+    pub token_end: usize,
+    /// First character of the chunk in the input
+    #[schema(example = "0")]
+    pub char_start: usize,
+    /// End of the chunk, exclusive, in the characters of the input
+    #[schema(example = "1043")]
+    pub char_end: usize,
+}
 
 #[derive(Deserialize, ToSchema, Debug)]
 pub(crate) struct EmbedWeaviateRequest {
+    #[schema(example = "What is Deep Learning?")]
     pub text: String,
     #[serde(default)]
     #[schema(default = "false", example = "false")]
@@ -324,13 +792,89 @@ pub(crate) struct EmbedWeaviateRequest {
     #[serde(default = "default_normalize")]
     #[schema(default = "true", example = "true")]
     pub normalize: bool,
+    /// Extract the text content of HTML inputs before tokenization
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub strip_html: bool,
+    /// Vectorizer settings, only sent by protocol version 2 clients
+    #[schema(nullable = true, default = "null")]
+    pub config: Option<WeaviateVectorizeConfig>,
+    /// Whether the text is a search query or a passage. Selects the prefix applied to the text
+    #[schema(nullable = true, default = "null", example = "query")]
+    pub task: Option<WeaviateTask>,
+    /// Only return the dimension of the embeddings. `text` is not embedded
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub probe: bool,
+}
+
+#[derive(Clone, Copy, Deserialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WeaviateTask {
+    Query,
+    Passage,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(crate) struct WeaviateVectorizeConfig {
+    /// Pooling is fixed when the model is loaded, this is only checked against it
+    #[schema(nullable = true, example = "masked_mean")]
+    pub pooling_strategy: Option<String>,
 }
 
 #[derive(Serialize, ToSchema, Debug)]
 pub(crate) struct EmbedWeaviateResponse {
-    pub text: String,
-    pub vector: Vec<f32>,
+    /// Not returned for probe requests
+    #[schema(nullable = true, default = "null", example = "What is Deep Learning?")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Not returned for probe requests
+    #[schema(nullable = true, default = "null", example = json!([0.0, 1.0, 2.0]))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+    #[schema(example = "3")]
+    pub dim: usize,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(crate) struct EmbedWeaviateBatchRequest {
+    #[schema(example = json!(["What is Deep Learning?", "Deep Learning is..."]))]
+    pub texts: Vec<String>,
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub truncate: bool,
+    #[serde(default = "default_normalize")]
+    #[schema(default = "true", example = "true")]
+    pub normalize: bool,
+    /// Extract the text content of HTML inputs before tokenization
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub strip_html: bool,
+    /// Whether the texts are search queries or passages. Selects the prefix applied to them
+    #[schema(nullable = true, default = "null", example = "passage")]
+    pub task: Option<WeaviateTask>,
+    /// Precision of the returned vectors. Defaults to `float16` for binary responses and
+    /// `float32` for JSON responses
+    #[schema(nullable = true, default = "null", example = "float16")]
+    pub dtype: Option<VectorDtype>,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum VectorDtype {
+    Float32,
+    /// Values are rounded to half precision. JSON responses still carry them as numbers
+    Float16,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub(crate) struct EmbedWeaviateBatchResponse {
+    /// One vector per text, in request order
+    pub vectors: Vec<Vec<f32>>,
     pub dim: usize,
+    /// Precision of the vectors
+    #[schema(example = "float32")]
+    pub dtype: VectorDtype,
 }
 
 
@@ -338,6 +882,410 @@ pub(crate) struct EmbedWeaviateResponse {
 pub(crate) struct OpenAICompatErrorResponse {
     pub message: String,
     pub code: u16,
+    /// OpenAI error type, e.g. `invalid_request_error` or `rate_limit_error`
     #[serde(rename(serialize = "type"))]
-    pub error_type: ErrorType,
+    #[schema(example = "invalid_request_error")]
+    pub error_type: &'static str,
+    /// Stable machine-readable error code
+    #[schema(example = "batch_too_large")]
+    pub error_code: String,
+}
+
+/// Error shape expected by the Weaviate vectorizer modules
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum WeaviateErrorResponse {
+    V1 { error: String },
+    V2 { error: Vec<WeaviateErrorMessage> },
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct WeaviateErrorMessage {
+    #[schema(example = "Model is overloaded")]
+    pub message: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ReloadRequest {
+    /// Model id or local path. Defaults to `RELOAD_MODEL_PATH`, then to the served model
+    #[schema(nullable = true, default = "null", example = "BAAI/bge-large-en-v1.5")]
+    pub model_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub(crate) struct LogLevel {
+    /// Log filter with the `RUST_LOG` syntax: a level, optionally refined per target
+    #[schema(example = "debug,tokenizers=warn")]
+    pub filter: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct WarmupRequest {
+    /// Number of synthetic inferences to run
+    #[serde(default = "default_warmup_iterations")]
+    #[schema(default = "8", example = "8")]
+    pub iterations: usize,
+}
+
+pub(crate) fn default_warmup_iterations() -> usize {
+    8
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct BenchRequest {
+    /// Number of requests to run
+    #[serde(default = "default_bench_requests")]
+    #[schema(default = "100", example = "100")]
+    pub requests: usize,
+    /// Number of inputs of each request
+    #[serde(default = "default_bench_batch_size")]
+    #[schema(default = "1", example = "1")]
+    pub batch_size: usize,
+    /// Number of words of each input
+    #[serde(default = "default_bench_input_length")]
+    #[schema(default = "128", example = "128")]
+    pub input_length: usize,
+    /// Number of requests in flight
+    #[serde(default = "default_bench_concurrency")]
+    #[schema(default = "1", example = "1")]
+    pub concurrency: usize,
+}
+
+fn default_bench_requests() -> usize {
+    100
+}
+
+fn default_bench_batch_size() -> usize {
+    1
+}
+
+fn default_bench_input_length() -> usize {
+    128
+}
+
+fn default_bench_concurrency() -> usize {
+    1
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct BenchResponse {
+    #[schema(example = "100")]
+    pub requests: usize,
+    #[schema(example = "1")]
+    pub batch_size: usize,
+    #[schema(example = "1250.0")]
+    pub total_ms: f64,
+    /// Requests per second
+    #[schema(example = "80.0")]
+    pub throughput: f64,
+    #[schema(example = "10240.0")]
+    pub tokens_per_sec: f64,
+    #[schema(example = "12.1")]
+    pub p50_ms: f64,
+    #[schema(example = "15.3")]
+    pub p95_ms: f64,
+    #[schema(example = "18.9")]
+    pub p99_ms: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct HealthDetailResponse {
+    #[schema(example = "true")]
+    pub healthy: bool,
+    /// Device memory in use, in bytes. `null` if the backend cannot sample it
+    #[schema(nullable = true, example = "2147483648")]
+    pub memory_used_bytes: Option<u64>,
+    /// Total device memory, in bytes. `null` if the backend cannot sample it
+    #[schema(nullable = true, example = "25769803776")]
+    pub memory_total_bytes: Option<u64>,
+    /// Fraction of time the device was busy. `null` if the backend cannot sample it
+    #[schema(nullable = true, example = "null")]
+    pub utilization: Option<f32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct WarmupResponse {
+    #[schema(example = "8")]
+    pub iterations: usize,
+    #[schema(example = "42.0")]
+    pub total_ms: f64,
+    #[schema(example = "5.25")]
+    pub mean_ms: f64,
+    #[schema(example = "4.1")]
+    pub min_ms: f64,
+    #[schema(example = "9.8")]
+    pub max_ms: f64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct SimilarityRequest {
+    #[schema(example = "What is Deep Learning?")]
+    pub source_sentence: String,
+    #[schema(example = json!(["Deep Learning is ...", "Cheese is ..."]))]
+    pub sentences: Vec<String>,
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub truncate: bool,
+    /// Only return the sentences with a score of at least `threshold`, by decreasing score
+    #[schema(nullable = true, default = "null", example = "0.5")]
+    pub threshold: Option<f32>,
+    #[serde(default)]
+    #[schema(default = "cosine", example = "dot")]
+    pub metric: SimilarityMetric,
+    /// Normalize the embeddings before the dot product. Must be `true` with the `cosine` metric
+    #[serde(default = "default_normalize")]
+    #[schema(default = "true", example = "false")]
+    pub normalize: bool,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SimilarityMetric {
+    /// Cosine similarity, the dot product of the normalized embeddings
+    #[default]
+    Cosine,
+    /// Dot product of the embeddings, normalized unless `normalize` is `false`
+    Dot,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SimilarityScore {
+    #[schema(example = "0")]
+    pub index: usize,
+    #[schema(example = "0.82")]
+    pub score: f32,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum SimilarityResponse {
+    /// Similarity of each sentence with the source sentence, in input order
+    #[schema(example = json!([0.82, 0.12]))]
+    Scores(Vec<f32>),
+    /// Sentences above the threshold, by decreasing score
+    Matches(Vec<SimilarityScore>),
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ClassifyBySimilarityRequest {
+    pub inputs: Input,
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub truncate: bool,
+    /// Number of labels returned per input, by decreasing score
+    #[serde(default = "default_top_k")]
+    #[schema(default = "1", example = "3")]
+    pub top_k: usize,
+}
+
+fn default_top_k() -> usize {
+    1
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LabelSimilarity {
+    #[schema(example = "sports")]
+    pub label: String,
+    /// Cosine similarity with the closest reference embedding of the label
+    #[schema(example = "0.82")]
+    pub score: f32,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum ClassifyBySimilarityResponse {
+    Single(Vec<LabelSimilarity>),
+    Batch(Vec<Vec<LabelSimilarity>>),
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct CountTokensRequest {
+    pub inputs: Input,
+    #[serde(default = "default_add_special_tokens")]
+    #[schema(default = "true", example = "true")]
+    pub add_special_tokens: bool,
+}
+
+fn default_add_special_tokens() -> bool {
+    true
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TokenCount {
+    #[schema(example = "7")]
+    pub tokens: usize,
+    /// Whether the input is longer than the maximum input length of the model
+    #[schema(example = "false")]
+    pub truncated: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct CountTokensResponse {
+    pub counts: Vec<TokenCount>,
+    #[schema(example = "7")]
+    pub total: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct JobRequest {
+    #[schema(example = json!(["What is Deep Learning?", "Deep Learning is ..."]))]
+    pub inputs: Vec<String>,
+    #[serde(default)]
+    #[schema(default = "false", example = "false")]
+    pub truncate: bool,
+    #[serde(default = "default_normalize")]
+    #[schema(default = "true", example = "true")]
+    pub normalize: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct JobResponse {
+    #[schema(example = "0b6c4b9e-3d38-4c3b-9a0e-8b8f0d1f5d2a")]
+    pub id: String,
+    pub status: JobStatus,
+    #[schema(example = "2")]
+    pub total: usize,
+    #[schema(example = "2")]
+    pub processed: usize,
+    /// Only set once the job is completed
+    #[schema(nullable = true, default = "null", example = json!([[0.0, 1.0, 2.0]]))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeddings: Option<Vec<Vec<f32>>>,
+    #[schema(nullable = true, default = "null", example = "null")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum MetaResponse {
+    Info(Info),
+    /// Shape expected by Weaviate's t2v-transformers module
+    Weaviate {
+        model: Info,
+    },
+}
+
+/// Landing document of `GET /` when `ROOT_RESPONSE` is `info`
+#[derive(Clone, Serialize, Debug)]
+pub(crate) struct RootInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// Id of the model serving requests that do not select one
+    pub model_id: String,
+    /// Path of the Swagger UI
+    pub docs: String,
+    /// Paths of the documented routes, including the route prefix
+    pub endpoints: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// Deterministic xorshift generator, so that failures can be reproduced
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    /// Arbitrary JSON value, biased towards the strings and lists accepted by `/predict`
+    fn arbitrary_json(rng: &mut Rng, depth: usize) -> Value {
+        let kind = if depth == 0 {
+            rng.below(5)
+        } else {
+            rng.below(7)
+        };
+        match kind {
+            0 | 1 => json!("a".repeat(rng.below(4) as usize)),
+            2 => json!(rng.below(100)),
+            3 => Value::Null,
+            4 => json!(rng.below(2) == 0),
+            5 => Value::Array(
+                (0..rng.below(4))
+                    .map(|_| arbitrary_json(rng, depth - 1))
+                    .collect(),
+            ),
+            _ => json!({"key": arbitrary_json(rng, depth - 1)}),
+        }
+    }
+
+    /// Strings of a sequence
+    fn sequence(sequence: &Sequence) -> Vec<&str> {
+        match sequence {
+            Sequence::Single(text) => vec![text],
+            Sequence::Pair(first, second) => vec![first, second],
+        }
+    }
+
+    /// Strings of a list of one or two strings
+    fn strings(value: &Value) -> Option<Vec<&str>> {
+        let list = value.as_array()?;
+        if !(1..=2).contains(&list.len()) {
+            return None;
+        }
+        list.iter().map(Value::as_str).collect()
+    }
+
+    #[test]
+    fn malformed_predict_inputs_are_reported() {
+        let malformed = [
+            (json!([]), "cannot be an empty list"),
+            (json!(["a", "b", "c"]), "got 3 elements"),
+            (json!(["a", ["b"]]), "second element is not a string"),
+            (json!(["a", "b", 1]), "got 3 elements"),
+            (json!([[]]), "invalid batch element 0"),
+            (json!([["a"], []]), "invalid batch element 1"),
+            (json!([[["a"]]]), "invalid batch element 0"),
+            (json!([["a"], "b"]), "got a string"),
+            (json!([["a", 1]]), "invalid batch element 0"),
+        ];
+        for (inputs, message) in malformed {
+            let error = serde_json::from_value::<PredictInput>(inputs.clone()).unwrap_err();
+            assert!(error.to_string().contains(message), "{inputs}: {error}");
+        }
+    }
+
+    #[test]
+    fn arbitrary_predict_inputs_are_parsed_or_rejected() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10_000 {
+            let inputs = arbitrary_json(&mut rng, 4);
+            let parsed = serde_json::from_value::<PredictInput>(inputs.clone()).ok();
+
+            // A string or a list of one or two strings is a single input, a list of such lists
+            // is a batch, and anything else is rejected
+            let expected = match (inputs.as_str(), strings(&inputs), inputs.as_array()) {
+                (Some(text), _, _) => Some(vec![vec![text]]),
+                (_, Some(sequence), _) => Some(vec![sequence]),
+                (_, _, Some(batch)) if !batch.is_empty() => batch.iter().map(strings).collect(),
+                _ => None,
+            };
+            let parsed = parsed.as_ref().map(|parsed| match parsed {
+                PredictInput::Single(single) => vec![sequence(single)],
+                PredictInput::Batch(batch) => batch.iter().map(sequence).collect(),
+            });
+            assert_eq!(parsed, expected, "{inputs}");
+        }
+    }
 }
@@ -0,0 +1,265 @@
+/// Checks of the numeric parameters of requests
+use crate::http::types::{
+    Duplicates, EmbedInput, EmbedRequest, Input, RerankRequest, ScoreScale, SimilarityMetric,
+    SimilarityRequest,
+};
+use crate::{ErrorResponse, ErrorType};
+
+/// Requests whose numeric parameters must be checked before they are used
+pub(crate) trait Validate {
+    fn validate(&self) -> Result<(), ErrorResponse>;
+}
+
+impl Validate for EmbedRequest {
+    fn validate(&self) -> Result<(), ErrorResponse> {
+        if let Some(target_norm) = self.target_norm {
+            positive("target_norm", target_norm)?;
+        }
+        if let Some([min, max]) = self.clamp {
+            if !min.is_finite() || !max.is_finite() || min > max {
+                return Err(invalid(
+                    "clamp",
+                    format!("must be a finite range [min, max], got [{min}, {max}]"),
+                ));
+            }
+        }
+        if let Some(top_dims) = self.top_dims {
+            non_zero("top_dims", top_dims)?;
+        }
+        if let Some(shards) = self.shards {
+            non_zero("shards", shards)?;
+            if self.chunk_size.is_some()
+                || self.split_on.is_some()
+                || self.poolings.is_some()
+                || self.partial_failure
+            {
+                return Err(invalid(
+                    "shards",
+                    "cannot be combined with `chunk_size`, `split_on`, `poolings` or \
+                    `partial_failure`"
+                        .to_string(),
+                ));
+            }
+        }
+        if let Some(chunk_size) = self.chunk_size {
+            non_zero("chunk_size", chunk_size)?;
+            if self.chunk_overlap >= chunk_size {
+                return Err(invalid(
+                    "chunk_overlap",
+                    format!(
+                        "must be lower than `chunk_size` ({chunk_size}), got {}",
+                        self.chunk_overlap
+                    ),
+                ));
+            }
+        }
+        if let Some(split_on) = &self.split_on {
+            if split_on.is_empty() {
+                return Err(invalid("split_on", "must not be empty".to_string()));
+            }
+            if !matches!(self.inputs, EmbedInput::Positional(Input::Single(_))) {
+                return Err(invalid(
+                    "split_on",
+                    "only applies to a single input".to_string(),
+                ));
+            }
+            if self.chunk_size.is_some() {
+                return Err(invalid(
+                    "split_on",
+                    "cannot be combined with `chunk_size`".to_string(),
+                ));
+            }
+        }
+        if self.explain
+            && (self.chunk_size.is_some()
+                || self.split_on.is_some()
+                || self.poolings.is_some()
+                || self.partial_failure)
+        {
+            return Err(invalid(
+                "explain",
+                "cannot be combined with `chunk_size`, `split_on`, `poolings` or `partial_failure`"
+                    .to_string(),
+            ));
+        }
+        if self.stream {
+            if !matches!(self.inputs, EmbedInput::Positional(_)) {
+                return Err(invalid(
+                    "stream",
+                    "only applies to positional inputs".to_string(),
+                ));
+            }
+            if self.chunk_size.is_some()
+                || self.split_on.is_some()
+                || self.poolings.is_some()
+                || self.shards.is_some()
+                || self.top_dims.is_some()
+                || self.detect_language
+                || self.classify_content
+                || self.return_text
+                || self.return_hash
+                || self.return_confidence
+                || self.return_raw_shape
+                || self.explain
+                || self.partial_failure
+            {
+                return Err(invalid(
+                    "stream",
+                    "cannot be combined with options changing the shape of the response"
+                        .to_string(),
+                ));
+            }
+        }
+        if self.partial_failure {
+            if !matches!(self.inputs, EmbedInput::Positional(_)) {
+                return Err(invalid(
+                    "partial_failure",
+                    "only applies to positional inputs".to_string(),
+                ));
+            }
+            if self.chunk_size.is_some() || self.split_on.is_some() {
+                return Err(invalid(
+                    "partial_failure",
+                    "cannot be combined with `chunk_size` or `split_on`".to_string(),
+                ));
+            }
+            // The embeddings are returned as is, aligned with the errors
+            if self.poolings.is_some()
+                || self.shards.is_some()
+                || self.top_dims.is_some()
+                || self.detect_language
+                || self.classify_content
+                || self.return_text
+                || self.return_hash
+                || self.return_confidence
+                || self.return_raw_shape
+                || self.explain
+            {
+                return Err(invalid(
+                    "partial_failure",
+                    "cannot be combined with options changing the shape of the response"
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Validate for RerankRequest {
+    fn validate(&self) -> Result<(), ErrorResponse> {
+        if self.score_scale == ScoreScale::Minmax && self.stream {
+            // Scaling needs all the scores
+            return Err(invalid(
+                "score_scale",
+                "`minmax` cannot be combined with `stream`".to_string(),
+            ));
+        }
+        if self.dedupe && self.stream {
+            return Err(invalid(
+                "dedupe",
+                "cannot be combined with `stream`".to_string(),
+            ));
+        }
+        if self.duplicates == Duplicates::Collapse && !self.dedupe {
+            return Err(invalid(
+                "duplicates",
+                "`collapse` requires `dedupe`".to_string(),
+            ));
+        }
+        if let Some(fusion_weight) = self.fusion_weight {
+            if !(0.0..=1.0).contains(&fusion_weight) {
+                return Err(invalid(
+                    "fusion_weight",
+                    format!("must be between 0 and 1, got {fusion_weight}"),
+                ));
+            }
+        }
+        let prior_scores = match &self.prior_scores {
+            Some(prior_scores) => prior_scores,
+            None if self.fusion_weight.is_some() => {
+                return Err(invalid(
+                    "fusion_weight",
+                    "requires `prior_scores`".to_string(),
+                ))
+            }
+            None => return Ok(()),
+        };
+        if prior_scores.len() != self.texts.len() {
+            return Err(invalid(
+                "prior_scores",
+                format!(
+                    "must have one score per text ({}), got {}",
+                    self.texts.len(),
+                    prior_scores.len()
+                ),
+            ));
+        }
+        for prior_score in prior_scores {
+            finite("prior_scores", *prior_score)?;
+        }
+        if self.stream {
+            // Normalization needs all the scores
+            return Err(invalid(
+                "prior_scores",
+                "cannot be combined with `stream`".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for SimilarityRequest {
+    fn validate(&self) -> Result<(), ErrorResponse> {
+        if let Some(threshold) = self.threshold {
+            finite("threshold", threshold)?;
+        }
+        if self.metric == SimilarityMetric::Cosine && !self.normalize {
+            // Raw dot products are only returned for the `dot` metric
+            return Err(invalid(
+                "normalize",
+                "cannot be `false` with the `cosine` metric".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn positive(field: &str, value: f32) -> Result<(), ErrorResponse> {
+    if !value.is_finite() || value <= 0.0 {
+        return Err(invalid(
+            field,
+            format!("must be a positive number, got {value}"),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn finite(field: &str, value: f32) -> Result<(), ErrorResponse> {
+    if !value.is_finite() {
+        return Err(invalid(
+            field,
+            format!("must be a finite number, got {value}"),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn non_zero(field: &str, value: usize) -> Result<(), ErrorResponse> {
+    if value == 0 {
+        return Err(invalid(field, "must be at least 1, got 0".to_string()));
+    }
+    Ok(())
+}
+
+/// 422 error naming the field and the constraint it breaks
+pub(crate) fn invalid(field: &str, constraint: String) -> ErrorResponse {
+    let message = format!("`{field}` {constraint}");
+    tracing::error!("{message}");
+    metrics::increment_counter!("te_request_failure", "err" => "validation");
+    ErrorResponse {
+        error: message,
+        error_type: ErrorType::InvalidParameter,
+        code: format!("invalid_{field}"),
+    }
+}
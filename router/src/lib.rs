@@ -13,38 +13,58 @@ use ::http::HeaderMap;
 use anyhow::{anyhow, Context, Result};
 use hf_hub::api::tokio::ApiBuilder;
 use hf_hub::{Repo, RepoType};
+use safetensors::SafeTensors;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
 use std::fs;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use text_embeddings_backend::DType;
-use text_embeddings_core::download::{download_artifacts, download_pool_config};
+use text_embeddings_backend::{BackendError, DType};
+use text_embeddings_core::dense::{Dense, DenseActivation};
+use text_embeddings_core::download::{download_artifacts, download_dense, download_pool_config};
 use text_embeddings_core::infer::Infer;
 use text_embeddings_core::queue::Queue;
 use text_embeddings_core::tokenization::Tokenization;
+use text_embeddings_core::whitening::Whitening;
 use text_embeddings_core::TextEmbeddingsError;
 use tokenizers::decoders::metaspace::PrependScheme;
 use tokenizers::pre_tokenizers::sequence::Sequence;
 use tokenizers::{PreTokenizerWrapper, Tokenizer};
+use tokio::sync::Semaphore;
 use tracing::Span;
 
 pub use logging::init_logging;
 
+/// Whether embeddings are normalized when a request does not set `normalize`
+pub(crate) const DEFAULT_NORMALIZE: bool = true;
+
 /// Create entrypoint
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
     model_id: String,
     revision: Option<String>,
     tokenization_workers: Option<usize>,
+    tokenization_concurrency: Option<usize>,
     dtype: Option<DType>,
     pooling: Option<text_embeddings_backend::Pool>,
+    pair_separator: Option<String>,
     max_concurrent_requests: usize,
     max_batch_tokens: usize,
     max_batch_requests: Option<usize>,
+    min_backend_batch: Option<usize>,
     max_client_batch_size: usize,
+    normalize_epsilon: f32,
+    coalesce_ttl: Duration,
+    dense_projection: bool,
+    whitening_matrix_path: Option<String>,
+    max_query_tokens: Option<usize>,
+    canonicalize_sign: bool,
+    output_round_bits: Option<u32>,
+    backend_connect_timeout: Option<Duration>,
+    backend_read_timeout: Option<Duration>,
     hf_api_token: Option<String>,
     hostname: Option<String>,
     port: u16,
@@ -52,6 +72,251 @@ pub async fn run(
     huggingface_hub_cache: Option<String>,
     otlp_endpoint: Option<String>,
 ) -> Result<()> {
+    if tokenization_concurrency == Some(0) {
+        return Err(anyhow!("`--tokenization-concurrency` must be at least 1"));
+    }
+    let options = ModelOptions {
+        revision,
+        tokenization_workers,
+        tokenization_permits: tokenization_concurrency
+            .map(|concurrency| Arc::new(Semaphore::new(concurrency))),
+        dtype,
+        pooling,
+        pair_separator,
+        max_concurrent_requests,
+        max_batch_tokens,
+        max_batch_requests,
+        min_backend_batch,
+        max_client_batch_size,
+        normalize_epsilon,
+        coalesce_ttl,
+        dense_projection,
+        whitening_matrix_path,
+        max_query_tokens,
+        canonicalize_sign,
+        output_round_bits,
+        backend_connect_timeout,
+        backend_read_timeout,
+        hf_api_token,
+        uds_path,
+        huggingface_hub_cache,
+        otlp_endpoint,
+    };
+    // Fail on invalid settings before spending time loading the model
+    #[cfg(feature = "http")]
+    http::config::ServerConfig::from_env()?;
+
+    let model = prepare_model(model_id, options.clone()).await?;
+
+    let addr = match hostname.unwrap_or("0.0.0.0".to_string()).parse() {
+        Ok(ip) => SocketAddr::new(ip, port),
+        Err(_) => {
+            tracing::warn!("Invalid hostname, defaulting to 0.0.0.0");
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port)
+        }
+    };
+
+    let prom_builder = prometheus::prometheus_builer(model.info.max_input_length)?;
+
+    #[cfg(all(feature = "grpc", feature = "http"))]
+    compile_error!("Features `http` and `grpc` cannot be enabled at the same time.");
+
+    #[cfg(not(any(feature = "http", feature = "grpc")))]
+    compile_error!("Either feature `http` or `grpc` must be enabled.");
+
+    #[cfg(feature = "http")]
+    {
+        // The backend may only be started on the first request. `Ready` is logged once the
+        // server is listening
+        let server =
+            tokio::spawn(
+                async move { http::server::run(model, options, addr, prom_builder).await },
+            );
+        server.await??;
+    }
+
+    #[cfg(feature = "grpc")]
+    {
+        let (infer, info) = model.start().await?;
+        let server =
+            tokio::spawn(async move { grpc::server::run(infer, info, addr, prom_builder).await });
+        tracing::info!("Ready");
+        server.await??;
+    }
+
+    Ok(())
+}
+
+/// Socket of the Python backend when `--uds-path` is not set
+const DEFAULT_UDS_PATH: &str = "/tmp/text-embeddings-inference-server";
+
+/// Settings used to load a model
+#[derive(Clone, Debug)]
+pub struct ModelOptions {
+    pub revision: Option<String>,
+    pub tokenization_workers: Option<usize>,
+    /// Limit of the inputs tokenized at the same time, shared by all the models of the server
+    pub tokenization_permits: Option<Arc<Semaphore>>,
+    pub dtype: Option<DType>,
+    pub pooling: Option<text_embeddings_backend::Pool>,
+    pub pair_separator: Option<String>,
+    pub max_concurrent_requests: usize,
+    pub max_batch_tokens: usize,
+    pub max_batch_requests: Option<usize>,
+    /// Minimum number of inputs of the backend batches, padded with dummy inputs
+    pub min_backend_batch: Option<usize>,
+    pub max_client_batch_size: usize,
+    pub normalize_epsilon: f32,
+    pub coalesce_ttl: Duration,
+    /// Apply the `2_Dense` projection head of sentence-transformers models
+    pub dense_projection: bool,
+    pub whitening_matrix_path: Option<String>,
+    pub max_query_tokens: Option<usize>,
+    pub canonicalize_sign: bool,
+    pub output_round_bits: Option<u32>,
+    pub backend_connect_timeout: Option<Duration>,
+    pub backend_read_timeout: Option<Duration>,
+    pub hf_api_token: Option<String>,
+    pub uds_path: Option<String>,
+    pub huggingface_hub_cache: Option<String>,
+    pub otlp_endpoint: Option<String>,
+}
+
+impl ModelOptions {
+    /// Options of a backend running next to the one started with these options, listening on
+    /// its own socket
+    pub(crate) fn with_uds_suffix(&self, suffix: &str) -> Self {
+        let uds_path = self.uds_path.as_deref().unwrap_or(DEFAULT_UDS_PATH);
+        Self {
+            uds_path: Some(format!("{uds_path}-{suffix}")),
+            ..self.clone()
+        }
+    }
+}
+
+/// Download a model and start its backend, tokenization and batching tasks
+pub(crate) async fn load_model(model_id: String, options: ModelOptions) -> Result<(Infer, Info)> {
+    prepare_model(model_id, options).await?.start().await
+}
+
+/// Model downloaded and ready to be started
+///
+/// Everything but the backend is loaded, so the model info is already known.
+#[derive(Clone, Debug)]
+pub struct PreparedModel {
+    pub info: Info,
+    model_root: PathBuf,
+    backend_model_type: text_embeddings_backend::ModelType,
+    dtype: DType,
+    tokenization: Tokenization,
+    dense: Option<Dense>,
+    whitening: Option<Whitening>,
+    min_backend_batch: Option<usize>,
+    normalize_epsilon: f32,
+    coalesce_ttl: Duration,
+    canonicalize_sign: bool,
+    output_round_bits: Option<u32>,
+    uds_path: String,
+    otlp_endpoint: Option<String>,
+    backend_connect_timeout: Option<Duration>,
+    backend_read_timeout: Option<Duration>,
+}
+
+impl PreparedModel {
+    /// Start the backend and the batching tasks of the model
+    pub(crate) async fn start(&self) -> Result<(Infer, Info)> {
+        tracing::info!("Starting model backend");
+        let backend = text_embeddings_backend::Backend::new(
+            self.model_root.clone(),
+            self.dtype.clone(),
+            self.backend_model_type.clone(),
+            self.uds_path.clone(),
+            self.otlp_endpoint.clone(),
+            self.backend_connect_timeout,
+            self.backend_read_timeout,
+        )
+        .context("Could not create backend")?;
+        backend
+            .health()
+            .await
+            .context("Model backend is not healthy")?;
+
+        let mut info = self.info.clone();
+        info.max_batch_requests = backend
+            .max_batch_size
+            .map(|s| {
+                tracing::warn!("Backend does not support a batch size > {s}");
+                tracing::warn!("forcing `max_batch_requests={s}`");
+                s
+            })
+            .or(info.max_batch_requests);
+
+        // Queue logic
+        let queue = Queue::new(
+            backend.padded_model,
+            info.max_batch_tokens,
+            info.max_batch_requests,
+            self.min_backend_batch,
+            info.max_concurrent_requests,
+        );
+
+        // Create infer task
+        let infer = Infer::new(
+            self.tokenization.clone(),
+            queue,
+            info.max_concurrent_requests,
+            backend,
+            self.normalize_epsilon,
+            self.coalesce_ttl,
+            self.dense.clone(),
+            self.whitening.clone(),
+            self.canonicalize_sign,
+            self.output_round_bits,
+        );
+
+        Ok((infer, info))
+    }
+}
+
+/// Download a model and load everything but its backend
+pub(crate) async fn prepare_model(
+    model_id: String,
+    options: ModelOptions,
+) -> Result<PreparedModel> {
+    let ModelOptions {
+        revision,
+        tokenization_workers,
+        tokenization_permits,
+        dtype,
+        pooling,
+        pair_separator,
+        max_concurrent_requests,
+        max_batch_tokens,
+        max_batch_requests,
+        min_backend_batch,
+        max_client_batch_size,
+        normalize_epsilon,
+        coalesce_ttl,
+        dense_projection,
+        whitening_matrix_path,
+        max_query_tokens,
+        canonicalize_sign,
+        output_round_bits,
+        backend_connect_timeout,
+        backend_read_timeout,
+        hf_api_token,
+        uds_path,
+        huggingface_hub_cache,
+        otlp_endpoint,
+    } = options;
+
+    // A zero or NaN epsilon would turn zero vectors into NaN embeddings
+    if normalize_epsilon.is_nan() || normalize_epsilon <= 0.0 {
+        return Err(anyhow!(
+            "`--normalize-epsilon` must be positive, got {normalize_epsilon}"
+        ));
+    }
+
     let model_id_path = Path::new(&model_id);
     let model_root = if model_id_path.exists() && model_id_path.is_dir() {
         // Using a local model
@@ -77,6 +342,12 @@ pub async fn run(
             // If a pooling config exist, download it
             let _ = download_pool_config(&api_repo).await;
         }
+        if dense_projection {
+            download_dense(&api_repo).await.context(
+                "Could not download the dense projection head (`2_Dense`). Only \
+                 `2_Dense/model.safetensors` weights are supported",
+            )?;
+        }
 
         // Download model from the Hub
         download_artifacts(&api_repo)
@@ -157,11 +428,51 @@ pub async fn run(
         }
     };
 
+    // Load the dense projection head of embedding models
+    let dense = match (dense_projection, &backend_model_type) {
+        (false, _) => None,
+        (true, text_embeddings_backend::ModelType::Classifier) => {
+            tracing::warn!(
+                "`--dense-projection` arg is set but model is a classifier. Ignoring `--dense-projection` arg."
+            );
+            None
+        }
+        (true, _) => Some(load_dense(&model_root, config.hidden_size)?),
+    };
+    // Dimension of the embeddings the whitening transform applies to
+    let embedding_dim = dense
+        .as_ref()
+        .map(|dense| dense.output_dim())
+        .or(config.hidden_size);
+
+    // Load the whitening transform of embedding models
+    let whitening = match (&whitening_matrix_path, &backend_model_type) {
+        (None, _) => None,
+        (Some(_), text_embeddings_backend::ModelType::Classifier) => {
+            tracing::warn!(
+                "`--whitening-matrix-path` arg is set but model is a classifier. Ignoring `--whitening-matrix-path` arg."
+            );
+            None
+        }
+        (Some(path), _) => Some(load_whitening(path, embedding_dim)?),
+    };
+    // Dimension of the embeddings returned to clients
+    let embedding_dim = match &backend_model_type {
+        text_embeddings_backend::ModelType::Classifier => None,
+        text_embeddings_backend::ModelType::Embedding(_) => whitening
+            .as_ref()
+            .map(Whitening::output_dim)
+            .or(embedding_dim),
+    };
+
     // Load tokenizer
     let tokenizer_path = model_root.join("tokenizer.json");
-    let mut tokenizer = Tokenizer::from_file(tokenizer_path).expect(
-        "tokenizer.json not found. text-embeddings-inference only supports fast tokenizers",
-    );
+    // Models can be loaded while serving, so this must not panic
+    let mut tokenizer = Tokenizer::from_file(tokenizer_path).map_err(|err| {
+        anyhow!(
+            "tokenizer.json not found. text-embeddings-inference only supports fast tokenizers: {err}"
+        )
+    })?;
     // See https://github.com/huggingface/tokenizers/pull/1357
     if let Some(pre_tokenizer) = tokenizer.get_pre_tokenizer() {
         if let PreTokenizerWrapper::Metaspace(m) = pre_tokenizer {
@@ -214,13 +525,22 @@ pub async fn run(
     let max_input_length = config.max_position_embeddings - position_offset;
 
     let tokenization_workers = tokenization_workers.unwrap_or_else(num_cpus::get_physical);
+    if let Some(bits) = output_round_bits {
+        if !(1..=23).contains(&bits) {
+            return Err(anyhow!(
+                "`--output-round-bits` must be between 1 and 23, got {bits}"
+            ));
+        }
+    }
 
     // Tokenization logic
     let tokenization = Tokenization::new(
         tokenization_workers,
+        tokenization_permits,
         tokenizer,
         max_input_length,
         position_offset,
+        pair_separator,
     );
 
     // Get dtype
@@ -235,40 +555,10 @@ pub async fn run(
         }
     });
 
-    // Create backend
-    tracing::info!("Starting model backend");
-    let backend = text_embeddings_backend::Backend::new(
-        model_root,
-        dtype.clone(),
-        backend_model_type,
-        uds_path.unwrap_or("/tmp/text-embeddings-inference-server".to_string()),
-        otlp_endpoint.clone(),
-    )
-    .context("Could not create backend")?;
-    backend
-        .health()
-        .await
-        .context("Model backend is not healthy")?;
-
-    let max_batch_requests = backend
-        .max_batch_size
-        .map(|s| {
-            tracing::warn!("Backend does not support a batch size > {s}");
-            tracing::warn!("forcing `max_batch_requests={s}`");
-            s
-        })
-        .or(max_batch_requests);
-
-    // Queue logic
-    let queue = Queue::new(
-        backend.padded_model,
-        max_batch_tokens,
-        max_batch_requests,
-        max_concurrent_requests,
-    );
-
-    // Create infer task
-    let infer = Infer::new(tokenization, queue, max_concurrent_requests, backend);
+    let default_pooling = match &model_type {
+        ModelType::Embedding(model) => Some(model.pooling.clone()),
+        ModelType::Classifier(_) | ModelType::Reranker(_) => None,
+    };
 
     // Endpoint info
     let info = Info {
@@ -276,50 +566,39 @@ pub async fn run(
         model_sha: revision,
         model_dtype: dtype.to_string(),
         model_type,
+        default_pooling,
+        default_normalize: DEFAULT_NORMALIZE,
+        embedding_dim,
         max_concurrent_requests,
         max_input_length,
         max_batch_tokens,
         tokenization_workers,
         max_batch_requests,
         max_client_batch_size,
+        max_query_tokens,
         version: env!("CARGO_PKG_VERSION"),
         sha: option_env!("VERGEN_GIT_SHA"),
         docker_label: option_env!("DOCKER_LABEL"),
     };
 
-    let addr = match hostname.unwrap_or("0.0.0.0".to_string()).parse() {
-        Ok(ip) => SocketAddr::new(ip, port),
-        Err(_) => {
-            tracing::warn!("Invalid hostname, defaulting to 0.0.0.0");
-            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port)
-        }
-    };
-
-    let prom_builder = prometheus::prometheus_builer(info.max_input_length)?;
-
-    #[cfg(all(feature = "grpc", feature = "http"))]
-    compile_error!("Features `http` and `grpc` cannot be enabled at the same time.");
-
-    #[cfg(not(any(feature = "http", feature = "grpc")))]
-    compile_error!("Either feature `http` or `grpc` must be enabled.");
-
-    #[cfg(feature = "http")]
-    {
-        let server =
-            tokio::spawn(async move { http::server::run(infer, info, addr, prom_builder).await });
-        tracing::info!("Ready");
-        server.await??;
-    }
-
-    #[cfg(feature = "grpc")]
-    {
-        let server =
-            tokio::spawn(async move { grpc::server::run(infer, info, addr, prom_builder).await });
-        tracing::info!("Ready");
-        server.await??;
-    }
-
-    Ok(())
+    Ok(PreparedModel {
+        info,
+        model_root,
+        backend_model_type,
+        dtype,
+        tokenization,
+        dense,
+        whitening,
+        min_backend_batch,
+        normalize_epsilon,
+        coalesce_ttl,
+        canonicalize_sign,
+        output_round_bits,
+        uds_path: uds_path.unwrap_or(DEFAULT_UDS_PATH.to_string()),
+        otlp_endpoint,
+        backend_connect_timeout,
+        backend_read_timeout,
+    })
 }
 
 #[derive(Debug, Deserialize)]
@@ -329,10 +608,138 @@ pub struct ModelConfig {
     #[serde(alias = "n_positions")]
     pub max_position_embeddings: usize,
     pub pad_token_id: usize,
+    pub hidden_size: Option<usize>,
     pub id2label: Option<HashMap<String, String>>,
     pub label2id: Option<HashMap<String, usize>>,
 }
 
+/// Transform loaded from `--whitening-matrix-path`
+#[derive(Debug, Deserialize)]
+struct WhiteningConfig {
+    matrix: Vec<Vec<f32>>,
+    mean: Option<Vec<f32>>,
+}
+
+/// Load the whitening transform at `path` and check that it applies to the model embeddings
+fn load_whitening(path: &str, hidden_size: Option<usize>) -> Result<Whitening> {
+    let config = fs::read_to_string(path)
+        .with_context(|| format!("Could not read whitening matrix `{path}`"))?;
+    let config: WhiteningConfig = serde_json::from_str(&config)
+        .with_context(|| format!("Failed to parse whitening matrix `{path}`"))?;
+    let whitening = Whitening::new(config.matrix, config.mean)
+        .map_err(|err| anyhow!("Invalid whitening matrix `{path}`: {err}"))?;
+
+    let hidden_size = hidden_size
+        .context("`config.json` does not contain `hidden_size` to check the whitening matrix")?;
+    if whitening.input_dim() != hidden_size {
+        return Err(anyhow!(
+            "Whitening matrix `{path}` applies to embeddings of dimension {}, but the model \
+            embeddings have dimension {hidden_size}",
+            whitening.input_dim()
+        ));
+    }
+    tracing::info!(
+        "Whitening embeddings from dimension {hidden_size} to {}",
+        whitening.output_dim()
+    );
+    Ok(whitening)
+}
+
+/// Configuration of a sentence-transformers dense layer, `2_Dense/config.json`
+#[derive(Debug, Deserialize)]
+struct DenseConfig {
+    in_features: usize,
+    out_features: usize,
+    #[serde(default = "default_dense_bias")]
+    bias: bool,
+    /// Python path of the activation, e.g. `torch.nn.modules.activation.Tanh`
+    activation_function: String,
+}
+
+fn default_dense_bias() -> bool {
+    true
+}
+
+/// Load the dense projection head in `2_Dense` and check that it applies to the model embeddings
+fn load_dense(model_root: &Path, hidden_size: Option<usize>) -> Result<Dense> {
+    let dense_root = model_root.join("2_Dense");
+    let config = fs::read_to_string(dense_root.join("config.json"))
+        .context("`--dense-projection` is set but `2_Dense/config.json` was not found")?;
+    let config: DenseConfig =
+        serde_json::from_str(&config).context("Failed to parse `2_Dense/config.json`")?;
+    let activation = match config.activation_function.rsplit('.').next() {
+        Some("Identity") => DenseActivation::Identity,
+        Some("Tanh") => DenseActivation::Tanh,
+        _ => {
+            return Err(anyhow!(
+                "Dense activation `{}` is not supported",
+                config.activation_function
+            ))
+        }
+    };
+
+    let weights = fs::read(dense_root.join("model.safetensors")).context(
+        "`--dense-projection` is set but `2_Dense/model.safetensors` was not found. \
+         `2_Dense/pytorch_model.bin` weights are not supported",
+    )?;
+    let weights = SafeTensors::deserialize(&weights)
+        .context("Failed to parse `2_Dense/model.safetensors`")?;
+    let shape = [config.out_features, config.in_features];
+    let weight = read_f32_tensor(&weights, "linear.weight", &shape)?;
+    let bias = if config.bias {
+        Some(read_f32_tensor(&weights, "linear.bias", &shape[..1])?)
+    } else {
+        None
+    };
+    let dense = Dense::new(
+        weight,
+        config.out_features,
+        config.in_features,
+        bias,
+        activation,
+    )
+    .map_err(|err| anyhow!("Invalid dense projection head: {err}"))?;
+
+    let hidden_size = hidden_size
+        .context("`config.json` does not contain `hidden_size` to check the dense projection")?;
+    if dense.input_dim() != hidden_size {
+        return Err(anyhow!(
+            "Dense projection head applies to embeddings of dimension {}, but the model \
+            embeddings have dimension {hidden_size}",
+            dense.input_dim()
+        ));
+    }
+    tracing::info!(
+        "Projecting embeddings from dimension {hidden_size} to {}",
+        dense.output_dim()
+    );
+    Ok(dense)
+}
+
+/// Read a `float32` tensor of `weights` and check its shape
+fn read_f32_tensor(weights: &SafeTensors, name: &str, shape: &[usize]) -> Result<Vec<f32>> {
+    let tensor = weights
+        .tensor(name)
+        .with_context(|| format!("`2_Dense/model.safetensors` does not contain `{name}`"))?;
+    if tensor.dtype() != safetensors::Dtype::F32 {
+        return Err(anyhow!(
+            "`{name}` has data type {:?}, expected F32",
+            tensor.dtype()
+        ));
+    }
+    if tensor.shape() != shape {
+        return Err(anyhow!(
+            "`{name}` has shape {:?}, expected {shape:?}",
+            tensor.shape()
+        ));
+    }
+    Ok(tensor
+        .data()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct PoolConfig {
     pooling_mode_cls_token: bool,
@@ -380,6 +787,18 @@ pub struct Info {
     #[cfg_attr(feature = "http", schema(example = "float16"))]
     pub model_dtype: String,
     pub model_type: ModelType,
+    /// Pooling applied to embeddings, `null` for classifier models
+    #[cfg_attr(feature = "http", schema(nullable = true, example = "cls"))]
+    pub default_pooling: Option<String>,
+    /// Whether embeddings are normalized when a request does not set `normalize`
+    #[cfg_attr(feature = "http", schema(example = "true"))]
+    pub default_normalize: bool,
+    /// Dimension of the embeddings, `null` for classifier models or if it is unknown
+    #[cfg_attr(
+        feature = "http",
+        schema(nullable = true, example = "384", default = "null")
+    )]
+    pub embedding_dim: Option<usize>,
     /// Router Parameters
     #[cfg_attr(feature = "http", schema(example = "128"))]
     pub max_concurrent_requests: usize,
@@ -394,6 +813,12 @@ pub struct Info {
     pub max_batch_requests: Option<usize>,
     #[cfg_attr(feature = "http", schema(example = "32"))]
     pub max_client_batch_size: usize,
+    /// Maximum number of tokens of the query of a rerank request
+    #[cfg_attr(
+        feature = "http",
+        schema(nullable = true, example = "null", default = "null")
+    )]
+    pub max_query_tokens: Option<usize>,
     #[cfg_attr(feature = "http", schema(example = "4"))]
     pub tokenization_workers: usize,
     /// Router Info
@@ -413,6 +838,10 @@ pub enum ErrorType {
     Overloaded,
     Validation,
     Tokenizer,
+    Unauthorized,
+    NotFound,
+    Unavailable,
+    InvalidParameter,
 }
 
 #[derive(Serialize)]
@@ -420,23 +849,63 @@ pub enum ErrorType {
 pub struct ErrorResponse {
     pub error: String,
     pub error_type: ErrorType,
+    /// Stable machine-readable error code
+    #[cfg_attr(feature = "http", schema(example = "batch_too_large"))]
+    pub code: String,
 }
 
 impl From<TextEmbeddingsError> for ErrorResponse {
     fn from(err: TextEmbeddingsError) -> Self {
-        let error_type = match err {
-            TextEmbeddingsError::Tokenizer(_) => ErrorType::Tokenizer,
-            TextEmbeddingsError::Validation(_) => ErrorType::Validation,
-            TextEmbeddingsError::Overloaded(_) => ErrorType::Overloaded,
-            TextEmbeddingsError::Backend(_) => ErrorType::Backend,
+        let (error_type, code) = match err {
+            TextEmbeddingsError::Tokenizer(_) => (ErrorType::Tokenizer, "tokenizer_error"),
+            TextEmbeddingsError::Validation(_) => (ErrorType::Validation, "validation_error"),
+            TextEmbeddingsError::EmptyInput => (ErrorType::Validation, "input_empty"),
+            TextEmbeddingsError::InputTooLong(_, _) => (ErrorType::Validation, "token_overflow"),
+            TextEmbeddingsError::Overloaded(_) => (ErrorType::Overloaded, "overloaded"),
+            // The request can be retried once the backend responds again
+            TextEmbeddingsError::Backend(BackendError::Timeout(_)) => {
+                (ErrorType::Unavailable, "backend_timeout")
+            }
+            TextEmbeddingsError::Backend(_) => (ErrorType::Backend, "backend_error"),
         };
         Self {
             error: err.to_string(),
             error_type,
+            code: code.to_string(),
         }
     }
 }
 
+/// Reject a rerank query longer than `max_query_tokens`. The query is paired with every text, so
+/// it is checked once before fanning out
+async fn check_query_tokens(infer: &Infer, info: &Info, query: &str) -> Result<(), ErrorResponse> {
+    let Some(max_query_tokens) = info.max_query_tokens else {
+        return Ok(());
+    };
+    let query_tokens = infer
+        .count_tokens(query.to_string(), false)
+        .await
+        .map_err(ErrorResponse::from)?;
+    if query_tokens > max_query_tokens {
+        let message = format!(
+            "query has {query_tokens} tokens > maximum allowed query tokens {max_query_tokens}"
+        );
+        tracing::error!("{message}");
+        metrics::increment_counter!("te_request_failure", "err" => "query_too_long");
+        return Err(ErrorResponse {
+            error: message,
+            error_type: ErrorType::InvalidParameter,
+            code: "query_too_long".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Mean of `count` durations summing to `total_nanos`, zero for an empty batch
+fn mean_duration(total_nanos: u64, count: u64) -> Duration {
+    Duration::from_nanos(total_nanos.checked_div(count).unwrap_or(0))
+}
+
 struct ResponseMetadata {
     compute_chars: usize,
     compute_tokens: usize,
@@ -490,6 +959,7 @@ impl ResponseMetadata {
             "te_request_inference_duration",
             self.inference_time.as_secs_f64()
         );
+        metrics::histogram!("te_request_tokens", self.compute_tokens as f64);
     }
 }
 
@@ -551,3 +1021,68 @@ impl From<ResponseMetadata> for HeaderMap {
         headers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_model_rejects_invalid_normalize_epsilon() {
+        let options = ModelOptions {
+            revision: None,
+            tokenization_workers: None,
+            tokenization_permits: None,
+            dtype: None,
+            pooling: None,
+            pair_separator: None,
+            max_concurrent_requests: 4,
+            max_batch_tokens: 1024,
+            max_batch_requests: None,
+            min_backend_batch: None,
+            max_client_batch_size: 32,
+            normalize_epsilon: 0.0,
+            coalesce_ttl: Duration::ZERO,
+            dense_projection: false,
+            whitening_matrix_path: None,
+            max_query_tokens: None,
+            canonicalize_sign: false,
+            output_round_bits: None,
+            backend_connect_timeout: None,
+            backend_read_timeout: None,
+            hf_api_token: None,
+            uds_path: None,
+            huggingface_hub_cache: None,
+            otlp_endpoint: None,
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        // Rejected before the model is looked up
+        for normalize_epsilon in [0.0, -1e-12, f32::NAN] {
+            let options = ModelOptions {
+                normalize_epsilon,
+                ..options.clone()
+            };
+            let err = runtime
+                .block_on(prepare_model("missing/model".to_string(), options))
+                .unwrap_err();
+            assert!(err.to_string().contains("--normalize-epsilon"), "{err}");
+        }
+    }
+
+    #[test]
+    fn backend_timeouts_can_be_retried() {
+        let err = ErrorResponse::from(TextEmbeddingsError::Backend(BackendError::Timeout(
+            "deadline".to_string(),
+        )));
+        assert!(matches!(err.error_type, ErrorType::Unavailable));
+        assert_eq!(err.code, "backend_timeout");
+
+        let err = ErrorResponse::from(TextEmbeddingsError::Backend(BackendError::Inference(
+            "error".to_string(),
+        )));
+        assert!(matches!(err.error_type, ErrorType::Backend));
+        assert_eq!(err.code, "backend_error");
+    }
+}
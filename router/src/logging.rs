@@ -3,9 +3,13 @@ use opentelemetry::sdk::trace::Sampler;
 use opentelemetry::sdk::{trace, Resource};
 use opentelemetry::{global, KeyValue};
 use opentelemetry_otlp::WithExportConfig;
+use std::sync::OnceLock;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::{EnvFilter, Layer};
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Handle to replace the filter of the global subscriber, set by `init_logging`
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 
 /// Init logging using env variables LOG_LEVEL and LOG_FORMAT:
 ///     - otlp_endpoint is an optional URL to an Open Telemetry collector
@@ -56,6 +60,9 @@ pub fn init_logging(otlp_endpoint: Option<&String>, json_output: bool) -> bool {
     // Filter events with LOG_LEVEL
     let env_filter =
         EnvFilter::try_from_env("LOG_LEVEL").unwrap_or_else(|_| EnvFilter::new("info"));
+    // The filter can be replaced at runtime with `set_log_filter`
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
+    let _ = FILTER_HANDLE.set(filter_handle);
 
     tracing_subscriber::registry()
         .with(env_filter)
@@ -63,3 +70,24 @@ pub fn init_logging(otlp_endpoint: Option<&String>, json_output: bool) -> bool {
         .init();
     global_tracer
 }
+
+/// Replace the log filter with an env-filter directive string, e.g. `debug,tokenizers=warn`
+///
+/// Fails if the directives are invalid or if logging was not initialized with `init_logging`.
+pub(crate) fn set_log_filter(directives: &str) -> Result<(), LogFilterError> {
+    let filter =
+        EnvFilter::try_new(directives).map_err(|err| LogFilterError::Invalid(err.to_string()))?;
+    FILTER_HANDLE
+        .get()
+        .ok_or(LogFilterError::Unavailable)?
+        .reload(filter)
+        .map_err(|_| LogFilterError::Unavailable)
+}
+
+#[derive(Debug)]
+pub(crate) enum LogFilterError {
+    /// The directives could not be parsed
+    Invalid(String),
+    /// The global subscriber was not created by `init_logging` or was dropped
+    Unavailable,
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 use opentelemetry::global;
+use std::time::Duration;
 use text_embeddings_backend::DType;
 use veil::Redact;
 
@@ -28,6 +29,14 @@ struct Args {
     #[clap(long, env)]
     tokenization_workers: Option<usize>,
 
+    /// Optionally limit the number of inputs tokenized at the same time by all the models of the
+    /// server, so that tokenizing large batches cannot starve the other tasks of the server, e.g.
+    /// health checks. Unlike `tokenization-workers`, which applies to each model, the limit is
+    /// shared by the additional and reloaded models.
+    /// Default to `tokenization-workers` per model.
+    #[clap(long, env)]
+    tokenization_concurrency: Option<usize>,
+
     /// The dtype to be forced upon the model.
     #[clap(long, env, value_enum)]
     dtype: Option<DType>,
@@ -41,6 +50,13 @@ struct Args {
     #[clap(long, env, value_enum)]
     pooling: Option<text_embeddings_backend::Pool>,
 
+    /// Optionally join sequence pairs with this separator and encode them as a single sequence.
+    ///
+    /// By default, pairs are encoded with the tokenizer pair template. Only set this for models
+    /// that expect a custom join.
+    #[clap(long, env)]
+    pair_separator: Option<String>,
+
     /// The maximum amount of concurrent requests for this particular deployment.
     /// Having a low limit will refuse clients requests instead of having them
     /// wait for too long and is usually good to handle backpressure correctly.
@@ -65,10 +81,64 @@ struct Args {
     #[clap(long, env)]
     max_batch_requests: Option<usize>,
 
+    /// Minimum number of inputs of the batches sent to the backend, for backends that are faster
+    /// on larger batches. Smaller batches are padded with single-token inputs whose results are
+    /// discarded
+    #[clap(long, env)]
+    min_backend_batch: Option<usize>,
+
     /// Control the maximum number of inputs that a client can send in a single request
     #[clap(default_value = "32", long, env)]
     max_client_batch_size: usize,
 
+    /// Lower bound of the norm used to normalize embeddings. Near-zero vectors are scaled by
+    /// `1 / normalize_epsilon` instead of exploding, and zero vectors stay zero.
+    #[clap(default_value = "1e-12", long, env)]
+    normalize_epsilon: f32,
+
+    /// How long in milliseconds an embedding is shared with the requests for the same input
+    /// after it is computed. Identical requests running at the same time always share one
+    /// backend call.
+    #[clap(default_value = "0", long, env)]
+    coalesce_ttl_ms: u64,
+
+    /// Apply the dense projection head of sentence-transformers models (`2_Dense`) to pooled
+    /// embeddings, as sentence-transformers does. Its weights must be in
+    /// `2_Dense/model.safetensors`: heads only shipped as `2_Dense/pytorch_model.bin`, as for
+    /// LaBSE or sentence-t5, are not supported and must be converted to safetensors first
+    #[clap(long, env)]
+    dense_projection: bool,
+
+    /// Path of a JSON file `{"matrix": [[...]], "mean": [...]}` holding a whitening or PCA
+    /// transform. Embeddings are replaced by `matrix · (embedding - mean)` before normalization.
+    #[clap(long, env)]
+    whitening_matrix_path: Option<String>,
+
+    /// Maximum number of tokens of the query of a rerank request. Longer queries are rejected
+    /// instead of being paired with every text
+    #[clap(long, env)]
+    max_query_tokens: Option<usize>,
+
+    /// Flip the sign of each embedding so that its largest-magnitude component is positive.
+    /// Makes embeddings whose sign is arbitrary reproducible across model reloads
+    #[clap(long, env)]
+    canonicalize_sign: bool,
+
+    /// Round each embedding to this number of mantissa bits (1 to 23) before it is returned.
+    /// Embeddings computed on different hardware then hash identically, at the cost of a
+    /// relative error of up to `2^-(bits + 1)`
+    #[clap(long, env)]
+    output_round_bits: Option<u32>,
+
+    /// Timeout in milliseconds of each connection attempt to the backend process
+    #[clap(long, env)]
+    backend_connect_timeout_ms: Option<u64>,
+
+    /// Timeout in milliseconds of each call to the backend process. Calls timing out return a
+    /// 503 error that can be retried, independently of the end-to-end request timeout
+    #[clap(long, env)]
+    backend_read_timeout_ms: Option<u64>,
+
     /// Your HuggingFace hub token
     #[clap(long, env)]
     #[redact(partial)]
@@ -115,12 +185,24 @@ async fn main() -> Result<()> {
         args.model_id,
         args.revision,
         args.tokenization_workers,
+        args.tokenization_concurrency,
         args.dtype,
         args.pooling,
+        args.pair_separator,
         args.max_concurrent_requests,
         args.max_batch_tokens,
         args.max_batch_requests,
+        args.min_backend_batch,
         args.max_client_batch_size,
+        args.normalize_epsilon,
+        Duration::from_millis(args.coalesce_ttl_ms),
+        args.dense_projection,
+        args.whitening_matrix_path,
+        args.max_query_tokens,
+        args.canonicalize_sign,
+        args.output_round_bits,
+        args.backend_connect_timeout_ms.map(Duration::from_millis),
+        args.backend_read_timeout_ms.map(Duration::from_millis),
         args.hf_api_token,
         Some(args.hostname),
         args.port,
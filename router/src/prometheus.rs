@@ -1,4 +1,86 @@
+#[cfg(feature = "http")]
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Label, Recorder, SharedString, Unit};
 use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder};
+#[cfg(feature = "http")]
+use metrics_exporter_prometheus::{PrometheusHandle, PrometheusRecorder};
+#[cfg(feature = "http")]
+use std::future::Future;
+
+#[cfg(feature = "http")]
+/// Metrics labeled with the tenant of the request when `TENANT_METRICS` is set
+const TENANT_METRICS: [&str; 5] = [
+    "te_request_count",
+    "te_request_success",
+    "te_request_failure",
+    "te_request_duration",
+    "te_request_tokens",
+];
+
+#[cfg(feature = "http")]
+tokio::task_local! {
+    /// Tenant label of the current request
+    static TENANT: String;
+}
+
+#[cfg(feature = "http")]
+/// Label the request metrics recorded by `future` with `tenant`
+pub(crate) async fn with_tenant<F: Future>(tenant: String, future: F) -> F::Output {
+    TENANT.scope(tenant, future).await
+}
+
+#[cfg(feature = "http")]
+/// Recorder adding the tenant of the current request to the labels of the `TENANT_METRICS`
+struct TenantRecorder(PrometheusRecorder);
+
+#[cfg(feature = "http")]
+impl TenantRecorder {
+    fn key(&self, key: &Key) -> Key {
+        if !TENANT_METRICS.contains(&key.name()) {
+            return key.clone();
+        }
+        match TENANT.try_with(|tenant| Label::new("tenant", tenant.clone())) {
+            Ok(label) => key.with_extra_labels(vec![label]),
+            Err(_) => key.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Recorder for TenantRecorder {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.0.describe_counter(key, unit, description)
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.0.describe_gauge(key, unit, description)
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.0.describe_histogram(key, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key) -> Counter {
+        self.0.register_counter(&self.key(key))
+    }
+
+    fn register_gauge(&self, key: &Key) -> Gauge {
+        self.0.register_gauge(&self.key(key))
+    }
+
+    fn register_histogram(&self, key: &Key) -> Histogram {
+        self.0.register_histogram(&self.key(key))
+    }
+}
+
+#[cfg(feature = "http")]
+/// Install the Prometheus recorder as the global recorder, labeling the request metrics of
+/// tenants scoped with [`with_tenant`]
+pub(crate) fn install_recorder(builder: PrometheusBuilder) -> Result<PrometheusHandle, BuildError> {
+    let recorder = builder.build_recorder();
+    let handle = recorder.handle();
+    metrics::set_boxed_recorder(Box::new(TenantRecorder(recorder)))?;
+    Ok(handle)
+}
 
 pub(crate) fn prometheus_builer(max_input_length: usize) -> Result<PrometheusBuilder, BuildError> {
     // Duration buckets
@@ -24,14 +106,22 @@ pub(crate) fn prometheus_builer(max_input_length: usize) -> Result<PrometheusBui
     let batch_size_matcher = Matcher::Full(String::from("te_batch_next_size"));
     let batch_size_buckets: Vec<f64> = (0..13).map(|x| 2.0_f64.powi(x)).collect();
 
+    // Client batch size buckets
+    let client_batch_size_matcher = Matcher::Full(String::from("te_batch_size"));
+
     // Batch tokens buckets
     let batch_tokens_matcher = Matcher::Full(String::from("te_batch_next_tokens"));
     let batch_tokens_buckets: Vec<f64> = (0..21).map(|x| 2.0_f64.powi(x)).collect();
 
+    // Request tokens buckets
+    let request_tokens_matcher = Matcher::Full(String::from("te_request_tokens"));
+
     // Prometheus handler
     PrometheusBuilder::new()
         .set_buckets_for_metric(duration_matcher, &duration_buckets)?
         .set_buckets_for_metric(input_length_matcher, &input_length_buckets)?
         .set_buckets_for_metric(batch_size_matcher, &batch_size_buckets)?
-        .set_buckets_for_metric(batch_tokens_matcher, &batch_tokens_buckets)
+        .set_buckets_for_metric(client_batch_size_matcher, &batch_size_buckets)?
+        .set_buckets_for_metric(batch_tokens_matcher, &batch_tokens_buckets)?
+        .set_buckets_for_metric(request_tokens_matcher, &batch_tokens_buckets)
 }
@@ -1,7 +1,24 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 
+/// Set once a shutdown signal is received, while the requests in flight are drained
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ShuttingDown(Arc<AtomicBool>);
+
+impl ShuttingDown {
+    pub(crate) fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 /// Shutdown signal handler
-pub(crate) async fn shutdown_signal() {
+///
+/// `shutting_down` is set as soon as the signal is received. The server keeps accepting requests
+/// for `grace_period` after it so that load balancers notice it is not ready anymore before it
+/// stops listening.
+pub(crate) async fn shutdown_signal(shutting_down: ShuttingDown, grace_period: Duration) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -23,7 +40,14 @@ pub(crate) async fn shutdown_signal() {
         _ = ctrl_c => {},
         _ = terminate => {},
     }
+    shutting_down.0.store(true, Ordering::Relaxed);
 
-    tracing::info!("signal received, starting graceful shutdown");
+    if grace_period.is_zero() {
+        tracing::info!("signal received, starting graceful shutdown");
+    } else {
+        tracing::info!("signal received, reporting not ready for {grace_period:?}");
+        tokio::time::sleep(grace_period).await;
+        tracing::info!("starting graceful shutdown");
+    }
     opentelemetry::global::shutdown_tracer_provider();
 }
@@ -44,17 +44,67 @@ async fn check_health(port: u16, timeout: Duration) -> Result<()> {
 }
 
 pub async fn start_server(model_id: String, revision: Option<String>, dtype: DType) -> Result<()> {
+    start_server_with_tokenization(model_id, revision, dtype, 1, None).await
+}
+
+pub async fn start_server_with_tokenization(
+    model_id: String,
+    revision: Option<String>,
+    dtype: DType,
+    tokenization_workers: usize,
+    tokenization_concurrency: Option<usize>,
+) -> Result<()> {
+    start(
+        model_id,
+        revision,
+        dtype,
+        tokenization_workers,
+        tokenization_concurrency,
+        None,
+    )
+    .await
+}
+
+pub async fn start_server_with_max_query_tokens(
+    model_id: String,
+    revision: Option<String>,
+    dtype: DType,
+    max_query_tokens: usize,
+) -> Result<()> {
+    start(model_id, revision, dtype, 1, None, Some(max_query_tokens)).await
+}
+
+async fn start(
+    model_id: String,
+    revision: Option<String>,
+    dtype: DType,
+    tokenization_workers: usize,
+    tokenization_concurrency: Option<usize>,
+    max_query_tokens: Option<usize>,
+) -> Result<()> {
     let server_task = tokio::spawn({
         run(
             model_id,
             revision,
-            Some(1),
+            Some(tokenization_workers),
+            tokenization_concurrency,
             Some(dtype),
             None,
+            None,
             4,
             1024,
             None,
+            None,
             32,
+            1e-12,
+            Duration::ZERO,
+            false,
+            None,
+            max_query_tokens,
+            false,
+            None,
+            None,
+            None,
             None,
             None,
             8090,
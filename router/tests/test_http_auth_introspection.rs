@@ -0,0 +1,47 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_auth_introspection_outage() -> Result<()> {
+    // Nothing listens on this port
+    std::env::set_var("AUTH_INTROSPECTION_URL", "http://127.0.0.1:1/introspect");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+
+    // Routes that do not use the principal are not authenticated
+    let res = client
+        .get("http://0.0.0.0:8090/health")
+        .bearer_auth("key")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .bearer_auth("key")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 503);
+
+    // Requests without a key do not call the endpoint
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_auth_keys_file() -> Result<()> {
+    let keys_file = std::env::temp_dir().join("test_http_auth_keys_file.json");
+    std::fs::write(
+        &keys_file,
+        json!({
+            "tenant-key": {"tenant": "acme", "scopes": ["embed"], "max_batch_size": 2},
+            "admin-key": {"tenant": "ops", "scopes": ["admin"]},
+        })
+        .to_string(),
+    )?;
+    std::env::set_var("API_KEYS_FILE", &keys_file);
+    std::env::set_var("REQUIRE_AUTH", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let embed = |key: Option<&str>, inputs: Vec<&str>| {
+        let mut request = client
+            .post("http://0.0.0.0:8090/embed")
+            .json(&json!({"inputs": inputs}));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        request.send()
+    };
+
+    assert_eq!(embed(None, vec!["test"]).await?.status(), 401);
+    assert_eq!(
+        embed(Some("unknown-key"), vec!["test"]).await?.status(),
+        401
+    );
+    assert_eq!(embed(Some("tenant-key"), vec!["test"]).await?.status(), 200);
+
+    // The batch limit of the tenant replaces `max_client_batch_size`
+    let res = embed(Some("tenant-key"), vec!["test"; 3]).await?;
+    assert_eq!(res.status(), 413);
+
+    // Health routes stay public
+    let res = client.get("http://0.0.0.0:8090/health").send().await?;
+    assert_eq!(res.status(), 200);
+
+    // Admin routes need the `admin` scope
+    let res = client
+        .put("http://0.0.0.0:8090/admin/log_level")
+        .bearer_auth("tenant-key")
+        .json(&json!({"filter": "debug"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 401);
+    let res = client
+        .put("http://0.0.0.0:8090/admin/log_level")
+        .bearer_auth("admin-key")
+        .json(&json!({"filter": "tokenizers=loud"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
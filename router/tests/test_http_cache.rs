@@ -0,0 +1,59 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_cache() -> Result<()> {
+    std::env::set_var("ENABLE_HTTP_CACHE", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "normalize": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let etag = res.headers()["etag"].clone();
+    assert!(res.headers()["cache-control"]
+        .to_str()?
+        .starts_with("public"));
+
+    // Key order does not change the tag
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("If-None-Match", etag.clone())
+        .json(&json!({"normalize": true, "inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["etag"], etag);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("If-None-Match", etag)
+        .json(&json!({"inputs": "another test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // A wildcard does not skip the handler of these POST routes
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("If-None-Match", "*")
+        .json(&json!({"inputs": "test", "normalize": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
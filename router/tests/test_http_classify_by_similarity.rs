@@ -0,0 +1,76 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_classify_by_similarity() -> Result<()> {
+    // Basis vectors, so that the scores are components of the input embedding
+    let basis = |i: usize, sign: f32| {
+        let mut embedding = vec![0.0; 384];
+        embedding[i] = sign;
+        embedding
+    };
+    let path = std::env::temp_dir().join("te_test_reference_set.json");
+    std::fs::write(
+        &path,
+        json!([
+            {"label": "first", "embedding": basis(0, 1.0)},
+            {"label": "first", "embedding": basis(1, 1.0)},
+            {"label": "opposite", "embedding": basis(0, -1.0)},
+        ])
+        .to_string(),
+    )?;
+    std::env::set_var("REFERENCE_SET_PATH", &path);
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let embeddings: Vec<Vec<f32>> = res.json().await?;
+    let embedding = &embeddings[0];
+
+    let res = client
+        .post("http://0.0.0.0:8090/classify_by_similarity")
+        .json(&json!({"inputs": ["test"], "top_k": 5}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    let labels = body[0].as_array().unwrap();
+    // One score per label, by decreasing score
+    assert_eq!(labels.len(), 2);
+    let score = |label: &str| {
+        labels.iter().find(|l| l["label"] == label).unwrap()["score"]
+            .as_f64()
+            .unwrap() as f32
+    };
+    assert!((score("first") - embedding[0].max(embedding[1])).abs() < 1e-5);
+    assert!((score("opposite") + embedding[0]).abs() < 1e-5);
+    assert!(labels[0]["score"].as_f64() >= labels[1]["score"].as_f64());
+
+    // A single input returns its closest label
+    let res = client
+        .post("http://0.0.0.0:8090/classify_by_similarity")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["label"], labels[0]["label"]);
+
+    Ok(())
+}
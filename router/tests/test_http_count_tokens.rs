@@ -0,0 +1,43 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_count_tokens() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": vec!["test", "test test"],
+        "add_special_tokens": false,
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/count_tokens")
+        .json(&request)
+        .send()
+        .await?;
+
+    let response = res.json::<serde_json::Value>().await?;
+    assert_eq!(
+        response,
+        json!({
+            "counts": [
+                {"tokens": 1, "truncated": false},
+                {"tokens": 2, "truncated": false},
+            ],
+            "total": 3,
+        })
+    );
+
+    Ok(())
+}
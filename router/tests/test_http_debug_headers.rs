@@ -0,0 +1,41 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_debug_headers() -> Result<()> {
+    std::env::set_var("DEBUG_HEADERS", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["What is Deep Learning?", "test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    assert!(res.headers().contains_key("x-backend-device"));
+    let composition = res.headers()["x-batch-composition"].to_str()?;
+    let batch_sizes: Vec<usize> = composition
+        .split(',')
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(batch_sizes.len(), 2);
+    assert!(batch_sizes.iter().all(|size| (1..=2).contains(size)));
+
+    // Routes without inference only get the device
+    let res = client.get("http://0.0.0.0:8090/health").send().await?;
+    assert!(!res.headers().contains_key("x-batch-composition"));
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_chunked() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": ["one two three four five six seven", "short", "a b c d"],
+        "chunk_size": 4,
+        "chunk_overlap": 1
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let groups: Value = res.json().await?;
+    let groups = groups.as_array().unwrap();
+    assert_eq!(groups.len(), 3);
+
+    // 7 tokens in windows of 4 with an overlap of 1: [0, 4) and [3, 7)
+    let chunks = groups[0]["chunks"].as_array().unwrap();
+    assert_eq!(groups[0]["index"], 0);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0]["token_start"], 0);
+    assert_eq!(chunks[0]["token_end"], 4);
+    assert_eq!(chunks[0]["char_start"], 0);
+    assert_eq!(chunks[0]["char_end"], 18);
+    assert_eq!(chunks[1]["token_start"], 3);
+    assert_eq!(chunks[1]["token_end"], 7);
+    assert_eq!(chunks[1]["embedding"].as_array().unwrap().len(), 384);
+
+    assert_eq!(groups[1]["chunks"].as_array().unwrap().len(), 1);
+
+    // Windows leave room for the 2 special tokens in the 512 tokens of the model, so that no
+    // chunk is truncated
+    let request = json!({
+        "inputs": "word ".repeat(600),
+        "chunk_size": 1000,
+    });
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let groups: Value = res.json().await?;
+    let chunks = groups[0]["chunks"].as_array().unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0]["token_end"], 510);
+    assert_eq!(chunks[1]["token_start"], 510);
+    assert_eq!(chunks[1]["token_end"], 600);
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_embeddings_clamp() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": vec!["test", "another test"],
+        "clamp": [-0.01, 0.01],
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+
+    let embeddings = res.json::<Vec<Vec<f32>>>().await?;
+    assert_eq!(embeddings.len(), 2);
+    for embedding in embeddings {
+        assert!(embedding.iter().all(|v| (-0.01..=0.01).contains(v)));
+    }
+
+    let request = json!({
+        "inputs": "test",
+        "clamp": [1.0, -1.0],
+    });
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
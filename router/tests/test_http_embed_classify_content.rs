@@ -0,0 +1,38 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_classify_content() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let res = reqwest::Client::new()
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({
+            "inputs": [
+                "What is Deep Learning?",
+                "fn main() {\n    println!(\"test\");\n}",
+                "| name | size |\n|---|---|\n| a | 1 |"
+            ],
+            "classify_content": true
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let embeddings: Vec<Value> = res.json().await?;
+    assert_eq!(embeddings[0]["content_type"], "prose");
+    assert_eq!(embeddings[1]["content_type"], "code");
+    assert_eq!(embeddings[2]["content_type"], "table");
+    assert_eq!(embeddings[0]["embedding"].as_array().unwrap().len(), 384);
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_explain() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["the quick brown fox", "deep learning"], "explain": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Value> = res.json().await?;
+    assert_eq!(body.len(), 2);
+    let attribution = &body[0]["attribution"];
+    let index = attribution["index"].as_u64().unwrap();
+    assert!(index < 4);
+    assert!(["the", "quick", "brown", "fox"].contains(&attribution["token"].as_str().unwrap()));
+    assert!(attribution["saliency"].as_f64().unwrap() > 0.0);
+    assert!(body[1]["attribution"].is_object());
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "first", "explain": true, "chunk_size": 4}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_explain");
+
+    Ok(())
+}
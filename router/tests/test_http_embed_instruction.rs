@@ -0,0 +1,52 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_instruction() -> Result<()> {
+    let path = std::env::temp_dir().join("te_test_instructions.json");
+    std::fs::write(
+        &path,
+        json!({"retrieval": "Represent this document for retrieval: "}).to_string(),
+    )?;
+    std::env::set_var("INSTRUCTIONS_PATH", &path);
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    let tokens = res.headers()["x-compute-tokens"].to_str()?.parse::<usize>()?;
+
+    // Instruction tokens count toward usage
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "instruction_key": "retrieval"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let instructed_tokens = res.headers()["x-compute-tokens"].to_str()?.parse::<usize>()?;
+    assert!(instructed_tokens > tokens);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "instruction_key": "unknown"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "unknown_instruction");
+
+    Ok(())
+}
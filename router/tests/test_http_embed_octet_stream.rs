@@ -0,0 +1,59 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_embed_octet_stream() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let request = json!({"inputs": ["test", "another test"]});
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let expected: Vec<Vec<f32>> = res.json().await?;
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("accept", "application/octet-stream")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-shape"], "[2, 384]");
+    assert_eq!(res.headers()["x-dtype"], "float32");
+    let bytes = res.bytes().await?;
+    assert_eq!(bytes.len(), 2 * 384 * 4);
+    let decoded: Vec<f32> = bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(decoded, expected.concat());
+
+    // Keyed inputs keep their JSON response
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("accept", "application/octet-stream")
+        .json(&json!({"inputs": {"first": "test"}}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("x-shape").is_none());
+    let body: serde_json::Value = res.json().await?;
+    assert!(body["first"].is_array());
+
+    Ok(())
+}
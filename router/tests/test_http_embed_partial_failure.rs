@@ -0,0 +1,52 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_partial_failure() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "", "other"], "partial_failure": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    let embeddings = body["embeddings"].as_array().unwrap();
+    let errors = body["errors"].as_array().unwrap();
+    assert_eq!(embeddings.len(), 3);
+    assert_eq!(errors.len(), 3);
+    assert_eq!(embeddings[0].as_array().unwrap().len(), 384);
+    assert!(embeddings[1].is_null());
+    assert_eq!(errors[1]["code"], "input_empty");
+    assert!(errors[2].is_null());
+
+    // Without the flag the whole request fails
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "", "other"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 413);
+
+    // Options changing the shape of the response are rejected instead of being dropped
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test"], "partial_failure": true, "return_text": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
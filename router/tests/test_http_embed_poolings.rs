@@ -0,0 +1,62 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_poolings() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "other"], "poolings": ["mean"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    let embeddings = body.as_array().unwrap();
+    assert_eq!(embeddings.len(), 2);
+    assert_eq!(embeddings[0]["mean"].as_array().unwrap().len(), 384);
+
+    // The model uses mean pooling: the backend cannot also produce CLS pooling
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "poolings": ["mean", "cls"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 424);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "pooling_unavailable");
+
+    // Known strategies that the model does not use are not reported as unknown
+    for pooling in ["splade", "last_token"] {
+        let res = client
+            .post("http://0.0.0.0:8090/embed")
+            .json(&json!({"inputs": "test", "poolings": [pooling]}))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 424);
+        let body: Value = res.json().await?;
+        assert_eq!(body["code"], "pooling_unavailable");
+    }
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "poolings": ["max"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_poolings");
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_raw_shape() -> Result<()> {
+    std::env::set_var("DEBUG_MODE", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "return_raw_shape": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Value> = res.json().await?;
+    assert_eq!(
+        body[0]["raw_output"],
+        json!({"shape": [1, 384], "dtype": "float32"})
+    );
+
+    // The normal output is unchanged
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    let body: Value = res.json().await?;
+    assert!(body[0].is_array());
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_return_confidence() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "test"], "return_confidence": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Value> = res.json().await?;
+    // The confidence is the norm before normalization
+    let confidence = body[0]["confidence"].as_f64().unwrap();
+    assert!(confidence > 0.0);
+    assert_eq!(body[1]["confidence"].as_f64().unwrap(), confidence);
+    let norm: f64 = body[0]["embedding"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_f64().unwrap().powi(2))
+        .sum::<f64>()
+        .sqrt();
+    assert!((norm - 1.0).abs() < 1e-3);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test"]}))
+        .send()
+        .await?;
+    let body: Value = res.json().await?;
+    // Embeddings are returned as arrays when no detail is requested
+    assert!(body[0].is_array());
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_return_hash() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "<p>test</p>"], "strip_html": true, "return_hash": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Value> = res.json().await?;
+    // Inputs are hashed as embedded
+    let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    assert_eq!(body[0]["hash"], hash);
+    assert_eq!(body[1]["hash"], hash);
+    assert_eq!(body[0]["embedding"].as_array().unwrap().len(), 384);
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_return_text() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(
+            &json!({"inputs": ["first", "<p>second</p>"], "strip_html": true, "return_text": true}),
+        )
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Value> = res.json().await?;
+    assert_eq!(body.len(), 2);
+    assert_eq!(body[0]["text"], "first");
+    // Inputs are echoed as sent
+    assert_eq!(body[1]["text"], "<p>second</p>");
+    assert_eq!(body[0]["embedding"].as_array().unwrap().len(), 384);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "first"}))
+        .send()
+        .await?;
+    let body: Vec<Vec<f32>> = res.json().await?;
+    assert_eq!(body.len(), 1);
+
+    Ok(())
+}
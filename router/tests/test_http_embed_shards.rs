@@ -0,0 +1,67 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_shards() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test"], "shards": 5}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Vec<Vec<f32>>> = res.json().await?;
+    // 384 dimensions are split into 4 shards of 77 and one of 76
+    let lengths: Vec<usize> = body[0].iter().map(Vec::len).collect();
+    assert_eq!(lengths, vec![77, 77, 77, 77, 76]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test"]}))
+        .send()
+        .await?;
+    let embeddings: Vec<Vec<f32>> = res.json().await?;
+    assert_eq!(body[0].concat(), embeddings[0]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": {"a": "test"}, "shards": 2}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["a"].as_array().unwrap().len(), 2);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "shards": 1000}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_shards");
+
+    // An empty batch has no embedding to split
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": [], "shards": 2}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Vec<Vec<f32>>> = res.json().await?;
+    assert!(body.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_special_tokens() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let with_special_tokens = res.headers()["x-compute-tokens"]
+        .to_str()?
+        .parse::<usize>()?;
+    let embedding_with: Vec<Vec<f32>> = res.json().await?;
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "add_special_tokens": false}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let without_special_tokens = res.headers()["x-compute-tokens"]
+        .to_str()?
+        .parse::<usize>()?;
+    let embedding_without: Vec<Vec<f32>> = res.json().await?;
+
+    // `[CLS]` and `[SEP]`
+    assert_eq!(with_special_tokens, without_special_tokens + 2);
+    assert_ne!(embedding_with, embedding_without);
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_split() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({
+            "inputs": "This is a sentence. Is this another one? Yes!",
+            "split_on": "sentences",
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    let segments: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|segment| segment["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        segments,
+        vec!["This is a sentence.", "Is this another one?", "Yes!"]
+    );
+    assert_eq!(body[0]["embedding"].as_array().unwrap().len(), 384);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "first|second", "split_on": "|"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body[1]["text"], "second");
+
+    // Splitting must not exceed the maximum batch size
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": vec!["test"; 33].join("|"), "split_on": "|"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 413);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["a. b."], "split_on": "sentences"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_stream() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "other"], "stream": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "application/x-ndjson");
+
+    let body = res.text().await?;
+    let mut indices = body
+        .lines()
+        .map(|line| {
+            let embedding: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(embedding["values"].as_array().unwrap().len(), 384);
+            embedding["index"].as_u64().unwrap()
+        })
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    assert_eq!(indices, vec![0, 1]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test", "stream": true, "return_text": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_stream");
+
+    Ok(())
+}
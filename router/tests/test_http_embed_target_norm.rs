@@ -0,0 +1,39 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_embeddings_target_norm() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": vec!["test", "a longer test input", "another one"],
+        "normalize": false,
+        "target_norm": 10.0,
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+
+    let embeddings = res.json::<Vec<Vec<f32>>>().await?;
+    assert_eq!(embeddings.len(), 3);
+    for embedding in embeddings {
+        let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 10.0).abs() < 1e-3, "norm {norm} != 10.0");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_embeddings_top_dims() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": vec!["test", "another test"],
+        "top_dims": 3,
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+
+    let response = res.json::<serde_json::Value>().await?;
+    let response = response.as_array().unwrap();
+    assert_eq!(response.len(), 2);
+    for embedding in response {
+        let values = embedding["embedding"].as_array().unwrap();
+        let top_dims = embedding["top_dims"].as_array().unwrap();
+        assert_eq!(top_dims.len(), 3);
+
+        let max = values
+            .iter()
+            .map(|v| v.as_f64().unwrap().abs())
+            .fold(0.0, f64::max);
+        let first = &top_dims[0];
+        assert_eq!(first["value"].as_f64().unwrap().abs(), max);
+        let index = first["index"].as_u64().unwrap() as usize;
+        assert_eq!(values[index], first["value"]);
+        assert!(embedding.get("language").is_none());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_embed_validation() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    for (request, code) in [
+        (json!({"inputs": "test", "top_dims": 0}), "invalid_top_dims"),
+        (json!({"inputs": "test", "target_norm": -1.0}), "invalid_target_norm"),
+        (json!({"inputs": "test", "target_norm": 0.0}), "invalid_target_norm"),
+    ] {
+        let res = client
+            .post("http://0.0.0.0:8090/embed")
+            .json(&request)
+            .send()
+            .await?;
+        assert_eq!(res.status(), 422);
+        let body: Value = res.json().await?;
+        assert_eq!(body["code"], code);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_empty_batch() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": []}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-compute-tokens"], "0");
+    assert_eq!(res.headers()["x-inference-time"], "0");
+    let body: Value = res.json().await?;
+    assert_eq!(body, json!([]));
+
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&json!({"input": []}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["data"], json!([]));
+
+    Ok(())
+}
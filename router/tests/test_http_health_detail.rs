@@ -0,0 +1,28 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::Value;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_health_detail() -> Result<()> {
+    std::env::set_var("REPORT_DEVICE_STATS", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let res = reqwest::get("http://0.0.0.0:8090/health/detail").await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["healthy"], true);
+    // The CPU backend does not sample device stats
+    assert!(body["memory_used_bytes"].is_null());
+    assert!(body["utilization"].is_null());
+
+    Ok(())
+}
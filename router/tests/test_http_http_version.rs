@@ -0,0 +1,36 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_http_version() -> Result<()> {
+    // HTTP/1 only, so that the health check of `start_server` still succeeds
+    std::env::set_var("HTTP_VERSION", "http1");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let res = reqwest::Client::new()
+        .get("http://0.0.0.0:8090/health")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.version(), reqwest::Version::HTTP_11);
+
+    // HTTP/2 connections are refused
+    let res = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()?
+        .get("http://0.0.0.0:8090/health")
+        .send()
+        .await;
+    assert!(res.is_err());
+
+    Ok(())
+}
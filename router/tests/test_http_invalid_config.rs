@@ -0,0 +1,26 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_invalid_config() -> Result<()> {
+    std::env::set_var("CORS_ALLOW_ORIGIN", "http://localhost:3000,bad\norigin");
+    std::env::set_var("REQUEST_TIMEOUT_SECS", "-1");
+    let err = start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await
+    .unwrap_err();
+
+    // All invalid settings are reported together
+    let message = format!("{err:#}");
+    assert!(message.contains("CORS_ALLOW_ORIGIN"));
+    assert!(message.contains("REQUEST_TIMEOUT_SECS"));
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_lazy_load() -> Result<()> {
+    std::env::set_var("LAZY_LOAD", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get("http://0.0.0.0:8090/.well-known/ready")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 503);
+
+    // Only the inference routes start the model
+    let res = client
+        .post("http://0.0.0.0:8090/unknown")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+    let res = client
+        .get("http://0.0.0.0:8090/.well-known/ready")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 503);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .get("http://0.0.0.0:8090/.well-known/ready")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
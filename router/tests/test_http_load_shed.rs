@@ -0,0 +1,39 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_load_shed() -> Result<()> {
+    // A cap of zero rejects every request on the endpoint
+    std::env::set_var("ENDPOINT_MAX_CONCURRENCY", "/embed=0");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 503);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "load_shed");
+
+    // Other endpoints are not capped
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&json!({"input": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_log_level() -> Result<()> {
+    std::env::set_var("API_KEY", "admin-key");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .put("http://0.0.0.0:8090/admin/log_level")
+        .json(&json!({"filter": "debug"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .put("http://0.0.0.0:8090/admin/log_level")
+        .bearer_auth("admin-key")
+        .json(&json!({"filter": "tokenizers=loud"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    // The test server does not initialize logging
+    let res = client
+        .put("http://0.0.0.0:8090/admin/log_level")
+        .bearer_auth("admin-key")
+        .json(&json!({"filter": "debug,tokenizers=warn"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 503);
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use std::time::Duration;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_max_connections() -> Result<()> {
+    std::env::set_var("MAX_CONNECTIONS", "1");
+    std::env::set_var("CONNECTION_IDLE_TIMEOUT_SECS", "1");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+    // Let the server close the connection of the health check
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Keeps its connection open between requests
+    let client = reqwest::Client::new();
+    let res = client.get("http://0.0.0.0:8090/health").send().await?;
+    assert_eq!(res.status(), 200);
+
+    // A second connection is closed as soon as it is accepted
+    let res = reqwest::Client::new()
+        .get("http://0.0.0.0:8090/health")
+        .send()
+        .await;
+    assert!(res.is_err());
+
+    // The open connection keeps being served
+    let res = client.get("http://0.0.0.0:8090/health").send().await?;
+    assert_eq!(res.status(), 200);
+
+    // Closing it frees its slot
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+    let res = client.get("http://0.0.0.0:8090/health").send().await?;
+    assert_eq!(res.status(), 200);
+
+    // An idle keep-alive connection is closed by the server, freeing its slot even though the
+    // client keeps it
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let res = reqwest::Client::new()
+        .get("http://0.0.0.0:8090/health")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    drop(client);
+
+    Ok(())
+}
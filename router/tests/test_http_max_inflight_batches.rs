@@ -0,0 +1,89 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_max_inflight_batches() -> Result<()> {
+    std::env::set_var("MAX_INFLIGHT_BATCHES", "1");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    // Batches sent at the same time are rejected while the first one is in flight
+    let client = reqwest::Client::new();
+    let inputs = vec!["test ".repeat(200); 32];
+    let batches: Vec<_> = (0..8)
+        .map(|_| {
+            let request = client
+                .post("http://0.0.0.0:8090/embed")
+                .json(&json!({ "inputs": inputs }))
+                .send();
+            tokio::spawn(request)
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for batch in batches {
+        let res = batch.await??;
+        let status = res.status();
+        if status == 429 {
+            let body: Value = res.json().await?;
+            assert_eq!(body["code"], "too_many_batches");
+        }
+        statuses.push(status);
+    }
+    assert!(statuses.iter().any(|status| *status == 200));
+    assert!(statuses.iter().any(|status| *status == 429));
+
+    // The slot is released once the batches are done
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // Streamed batches hold their slot until the stream ends
+    let streams: Vec<_> = (0..8)
+        .map(|_| {
+            let request = client
+                .post("http://0.0.0.0:8090/embeddings")
+                .json(&json!({ "input": inputs, "stream": true }))
+                .send();
+            tokio::spawn(async move {
+                let res = request.await?;
+                let status = res.status();
+                res.bytes().await?;
+                Ok::<_, reqwest::Error>(status)
+            })
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for stream in streams {
+        statuses.push(stream.await??);
+    }
+    assert!(statuses.iter().any(|status| *status == 200));
+    assert!(statuses.iter().any(|status| *status == 429));
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&json!({ "input": ["test", "test"], "stream": true }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // Single requests are not capped
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
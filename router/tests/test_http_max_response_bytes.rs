@@ -0,0 +1,57 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_max_response_bytes() -> Result<()> {
+    // Two embeddings of 384 values fit, three do not
+    std::env::set_var("MAX_RESPONSE_BYTES", (384 * 12 * 2).to_string());
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let info: Value = client
+        .get("http://0.0.0.0:8090/meta")
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(info["embedding_dim"], 384);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    for (path, body) in [
+        ("embed", json!({"inputs": ["test", "test", "test"]})),
+        ("embeddings", json!({"input": ["test", "test", "test"]})),
+    ] {
+        let res = client
+            .post(format!("http://0.0.0.0:8090/{path}"))
+            .json(&body)
+            .send()
+            .await?;
+        assert_eq!(res.status(), 413);
+    }
+
+    // Weaviate reports validation errors as 422
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .json(&json!({"texts": ["test", "test", "test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_model_header() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": "test"
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("X-Model-Id", "sentence-transformers/all-MiniLM-L6-v2")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .header("X-Model-Id", "unknown/model")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    let error = res.json::<serde_json::Value>().await?;
+    assert_eq!(error["code"], "model_not_found");
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .contains("sentence-transformers/all-MiniLM-L6-v2"));
+
+    Ok(())
+}
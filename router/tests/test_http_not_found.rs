@@ -0,0 +1,37 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::Value;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_not_found() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client.get("http://0.0.0.0:8090/unknown").send().await?;
+    assert_eq!(res.status(), 404);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "route_not_found");
+    assert_eq!(body["error_type"], "NotFound");
+    assert!(body["error"].as_str().unwrap().contains("/embed"));
+
+    // OpenAI clients get an OpenAI error
+    let res = client
+        .post("http://0.0.0.0:8090/v1/embeddings")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+    let body: Value = res.json().await?;
+    assert_eq!(body["type"], "invalid_request_error");
+    assert_eq!(body["error_code"], "route_not_found");
+
+    Ok(())
+}
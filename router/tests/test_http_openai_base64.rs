@@ -0,0 +1,54 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_openai_base64() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let input = json!(["test", "another test"]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&json!({"input": input}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let floats: Value = res.json().await?;
+    assert!(floats.get("encoding_format").is_none());
+    assert!(floats.get("byte_order").is_none());
+
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&json!({"input": input, "encoding_format": "base64"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let encoded: Value = res.json().await?;
+    assert_eq!(encoded["encoding_format"], "base64");
+    assert_eq!(encoded["byte_order"], "little");
+
+    for i in 0..2 {
+        let expected: Vec<f32> = serde_json::from_value(floats["data"][i]["embedding"].clone())?;
+        let bytes = BASE64_STANDARD.decode(encoded["data"][i]["embedding"].as_str().unwrap())?;
+        assert_eq!(bytes.len(), expected.len() * 4);
+        let decoded: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(decoded, expected);
+    }
+
+    Ok(())
+}
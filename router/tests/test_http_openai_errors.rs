@@ -0,0 +1,37 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_openai_errors() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    // The test server accepts at most 32 inputs per request
+    let request = json!({
+        "input": vec!["test"; 33],
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let error = res.json::<serde_json::Value>().await?;
+    assert_eq!(error["type"], "invalid_request_error");
+    assert_eq!(error["code"], 400);
+    assert_eq!(error["error_code"], "batch_too_large");
+
+    Ok(())
+}
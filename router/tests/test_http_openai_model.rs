@@ -0,0 +1,74 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_openai_model() -> Result<()> {
+    std::env::set_var("PUBLIC_MODEL_NAME", "minilm");
+    std::env::set_var("ADDITIONAL_MODEL_IDS", "BAAI/bge-small-en-v1.5");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let mut embeddings = Vec::new();
+    for (request, model) in [
+        (json!({"input": "test", "model": "minilm"}), "minilm"),
+        (json!({"input": "test"}), "minilm"),
+        (
+            json!({"input": "test", "model": "sentence-transformers/all-MiniLM-L6-v2"}),
+            "sentence-transformers/all-MiniLM-L6-v2",
+        ),
+        (
+            json!({"input": "test", "model": "BAAI/bge-small-en-v1.5"}),
+            "BAAI/bge-small-en-v1.5",
+        ),
+    ] {
+        let res = client
+            .post("http://0.0.0.0:8090/embeddings")
+            .json(&request)
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let body: Value = res.json().await?;
+        assert_eq!(body["model"], model);
+        embeddings.push(body["data"][0]["embedding"].clone());
+    }
+    // The public name and the id select the default model
+    assert_eq!(embeddings[0], embeddings[1]);
+    assert_eq!(embeddings[0], embeddings[2]);
+    // The body field selects the second model
+    assert_ne!(embeddings[0], embeddings[3]);
+
+    // The body field takes precedence over the header
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .header("X-Model-Id", "sentence-transformers/all-MiniLM-L6-v2")
+        .json(&json!({"input": "test", "model": "BAAI/bge-small-en-v1.5"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["data"][0]["embedding"], embeddings[3]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&json!({"input": "test", "model": "text-embedding-ada-002"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+    let error: Value = res.json().await?;
+    assert_eq!(error["error_code"], "model_not_found");
+    let message = error["message"].as_str().unwrap();
+    assert!(message.contains("BAAI/bge-small-en-v1.5"));
+    assert!(message.contains("sentence-transformers/all-MiniLM-L6-v2"));
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_openai_stream() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "input": vec!["test", "another test", "a third test"],
+        "stream": true,
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embeddings")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // The streamed body is a complete OpenAI response
+    let response = res.json::<serde_json::Value>().await?;
+    assert_eq!(response["object"], "list");
+    let data = response["data"].as_array().unwrap();
+    assert_eq!(data.len(), 3);
+    let mut indices: Vec<u64> = data.iter().map(|e| e["index"].as_u64().unwrap()).collect();
+    indices.sort_unstable();
+    assert_eq!(indices, vec![0, 1, 2]);
+    assert!(response["usage"]["total_tokens"].as_u64().unwrap() > 0);
+
+    Ok(())
+}
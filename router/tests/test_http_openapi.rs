@@ -0,0 +1,49 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_openapi_documents_all_routes() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let res = reqwest::get("http://0.0.0.0:8090/api-doc/openapi.json").await?;
+    assert_eq!(res.status(), 200);
+    let doc = res.json::<serde_json::Value>().await?;
+
+    for path in [
+        "/vectors",
+        "/vectors/batch",
+        "/similarity",
+        "/count_tokens",
+        "/.well-known/live",
+        "/.well-known/ready",
+    ] {
+        assert!(doc["paths"][path].is_object(), "{path} is not documented");
+    }
+    assert_eq!(
+        doc["paths"]["/vectors"]["post"]["requestBody"]["content"]["application/json"]["schema"]
+            ["$ref"],
+        "#/components/schemas/EmbedWeaviateRequest"
+    );
+    for schema in [
+        "EmbedWeaviateRequest",
+        "EmbedWeaviateResponse",
+        "EmbedWeaviateBatchRequest",
+        "EmbedWeaviateBatchResponse",
+    ] {
+        assert!(
+            doc["components"]["schemas"][schema].is_object(),
+            "{schema} is not documented"
+        );
+    }
+
+    Ok(())
+}
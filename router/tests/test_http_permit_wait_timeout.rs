@@ -0,0 +1,39 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_permit_wait_timeout() -> Result<()> {
+    // Inputs do not wait for a permit to be released
+    std::env::set_var("PERMIT_WAIT_TIMEOUT_MS", "0");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // The test server has 4 permits
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": vec!["test"; 8]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 429);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "overloaded");
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_predict_groups() -> Result<()> {
+    let path = std::env::temp_dir().join("te_test_label_groups.json");
+    std::fs::write(
+        &path,
+        json!({"joy": "positive", "love": "positive", "anger": "negative"}).to_string(),
+    )?;
+    std::env::set_var("LABEL_GROUPS_PATH", &path);
+    std::env::set_var("LABEL_GROUP_AGGREGATION", "sum");
+    start_server(
+        "SamLowe/roberta-base-go_emotions".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let res = reqwest::Client::new()
+        .post("http://0.0.0.0:8090/predict")
+        .json(&json!({"inputs": "I like you.", "format": "map", "aggregate_groups": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    let labels = body["labels"].as_object().unwrap();
+    assert_eq!(labels.len(), 28);
+    let groups = body["groups"].as_object().unwrap();
+    assert_eq!(groups.len(), 2);
+
+    let score = |label: &str| labels[label].as_f64().unwrap();
+    let positive = groups["positive"].as_f64().unwrap();
+    assert!((positive - (score("joy") + score("love"))).abs() < 1e-5);
+    assert!((groups["negative"].as_f64().unwrap() - score("anger")).abs() < 1e-5);
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_predict_invalid_batch_element() -> Result<()> {
+    start_server(
+        "SamLowe/roberta-base-go_emotions".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "inputs": vec![vec!["test"], vec!["a", "b", "c"]]
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/predict")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    let error = res.text().await?;
+    assert!(error.contains("invalid batch element 1"), "{error}");
+
+    Ok(())
+}
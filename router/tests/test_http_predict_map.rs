@@ -0,0 +1,56 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_predict_map() -> Result<()> {
+    start_server(
+        "SamLowe/roberta-base-go_emotions".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/predict")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    let list: Vec<Value> = res.json().await?;
+
+    let res = client
+        .post("http://0.0.0.0:8090/predict")
+        .json(&json!({"inputs": "test", "format": "map"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let map: Map<String, Value> = res.json().await?;
+    assert_eq!(map.len(), list.len());
+    for prediction in &list {
+        let label = prediction["label"].as_str().unwrap();
+        assert_eq!(map[label], prediction["score"]);
+    }
+
+    let res = client
+        .post("http://0.0.0.0:8090/predict")
+        .json(&json!({"inputs": [["test"], ["test"]], "format": "map"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let batch: Vec<Map<String, Value>> = res.json().await?;
+    assert_eq!(batch, vec![map.clone(), map]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/predict")
+        .json(&json!({"inputs": "test", "format": "table"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
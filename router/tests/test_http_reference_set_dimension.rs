@@ -0,0 +1,31 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_reference_set_dimension() -> Result<()> {
+    // The model embeddings have dimension 384
+    let path = std::env::temp_dir().join("te_test_reference_set_dimension.json");
+    std::fs::write(
+        &path,
+        json!([{"label": "first", "embedding": [1.0, 0.0, 0.0]}]).to_string(),
+    )?;
+    std::env::set_var("REFERENCE_SET_PATH", &path);
+    let err = start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await
+    .unwrap_err();
+
+    let message = format!("{err:#}");
+    assert!(message.contains("dimension 3"), "{message}");
+    assert!(message.contains("dimension 384"), "{message}");
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_reload() -> Result<()> {
+    std::env::set_var("API_KEY", "admin-key");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let embed = || async {
+        let res = client
+            .post("http://0.0.0.0:8090/embed")
+            .bearer_auth("admin-key")
+            .json(&json!({"inputs": "test"}))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        res.json::<Value>().await
+    };
+    let before = embed().await?;
+
+    let res = client
+        .post("http://0.0.0.0:8090/admin/reload")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 401);
+
+    // Reloads the served model when the body does not name one
+    let res = client
+        .post("http://0.0.0.0:8090/admin/reload")
+        .bearer_auth("admin-key")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let info: Value = res.json().await?;
+    assert_eq!(info["model_id"], "sentence-transformers/all-MiniLM-L6-v2");
+    // The new backend serves the next requests
+    assert_eq!(embed().await?, before);
+
+    // A model of another type is refused and the served model keeps serving
+    let res = client
+        .post("http://0.0.0.0:8090/admin/reload")
+        .bearer_auth("admin-key")
+        .json(&json!({"model_id": "SamLowe/roberta-base-go_emotions"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 424);
+    let error: Value = res.json().await?;
+    assert_eq!(error["code"], "model_type_mismatch");
+    assert_eq!(embed().await?, before);
+
+    Ok(())
+}
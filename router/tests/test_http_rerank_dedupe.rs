@@ -0,0 +1,62 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_rerank_dedupe() -> Result<()> {
+    start_server(
+        "BAAI/bge-reranker-base".to_string(),
+        Some("refs/pr/5".to_string()),
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+
+    // Duplicates keep their index and share the score of their first occurrence
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test", "other", "test"], "dedupe": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ranks: Vec<Value> = res.json().await?;
+    assert_eq!(ranks.len(), 3);
+    let score =
+        |index: u64| ranks.iter().find(|rank| rank["index"] == index).unwrap()["score"].clone();
+    assert_eq!(score(0), score(2));
+
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({
+            "query": "test",
+            "texts": ["test", "other", "test"],
+            "dedupe": true,
+            "duplicates": "collapse"
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ranks: Vec<Value> = res.json().await?;
+    let mut indices: Vec<u64> = ranks
+        .iter()
+        .map(|rank| rank["index"].as_u64().unwrap())
+        .collect();
+    indices.sort_unstable();
+    assert_eq!(indices, vec![0, 1]);
+
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test"], "duplicates": "collapse"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_duplicates");
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_rerank_fusion() -> Result<()> {
+    start_server(
+        "BAAI/bge-reranker-base".to_string(),
+        Some("refs/pr/5".to_string()),
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+
+    // Only the prior scores count with a zero weight
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({
+            "query": "test",
+            "texts": ["test", "other"],
+            "prior_scores": [1.0, 3.0],
+            "fusion_weight": 0.0
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ranks: Vec<Value> = res.json().await?;
+    assert_eq!(ranks[0]["index"], 1);
+    assert_eq!(ranks[0]["score"], 1.0);
+    assert_eq!(ranks[1]["score"], 0.0);
+
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test", "other"], "prior_scores": [1.0]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_prior_scores");
+
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({
+            "query": "test",
+            "texts": ["test"],
+            "prior_scores": [1.0],
+            "fusion_weight": 1.5
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
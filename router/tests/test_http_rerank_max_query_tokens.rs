@@ -0,0 +1,46 @@
+mod common;
+
+use crate::common::start_server_with_max_query_tokens;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_rerank_max_query_tokens() -> Result<()> {
+    start_server_with_max_query_tokens(
+        "BAAI/bge-reranker-base".to_string(),
+        Some("refs/pr/5".to_string()),
+        DType::Float32,
+        4,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test", "other"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // The limit applies to the query alone, not to the texts it is paired with
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["a much longer text than the query"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "a query longer than four tokens", "texts": ["test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "query_too_long");
+    assert_eq!(body["error_type"], "invalid_parameter");
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_rerank_normalize_whitespace() -> Result<()> {
+    start_server(
+        "BAAI/bge-reranker-base".to_string(),
+        Some("refs/pr/5".to_string()),
+        DType::Float32,
+    )
+    .await?;
+
+    // Reformatted texts get the score of the text they were formatted from
+    let res = reqwest::Client::new()
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({
+            "query": "  What is   Deep Learning? ",
+            "texts": ["Deep Learning is a subset of ML", "Deep  Learning\n is a\tsubset of ML  "],
+            "normalize_whitespace": true,
+            "return_text": true
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ranks: Vec<Value> = res.json().await?;
+    assert_eq!(ranks[0]["score"], ranks[1]["score"]);
+    assert_eq!(ranks[1]["text"], "Deep Learning is a subset of ML");
+
+    Ok(())
+}
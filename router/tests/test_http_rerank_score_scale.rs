@@ -0,0 +1,53 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_rerank_score_scale() -> Result<()> {
+    start_server(
+        "BAAI/bge-reranker-base".to_string(),
+        Some("refs/pr/5".to_string()),
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test", "other"], "score_scale": "minmax"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ranks: Vec<Value> = res.json().await?;
+    assert_eq!(ranks[0]["score"], 1.0);
+    assert_eq!(ranks[1]["score"], 0.0);
+
+    // The sigmoid of the logits is the default activation of single-label rerankers
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test"], "score_scale": "sigmoid"}))
+        .send()
+        .await?;
+    let sigmoid: Vec<Value> = res.json().await?;
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test"]}))
+        .send()
+        .await?;
+    let raw: Vec<Value> = res.json().await?;
+    let difference = sigmoid[0]["score"].as_f64().unwrap() - raw[0]["score"].as_f64().unwrap();
+    assert!(difference.abs() < 1e-4);
+
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&json!({"query": "test", "texts": ["test"], "score_scale": "minmax", "stream": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 422);
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_rerank_stream() -> Result<()> {
+    start_server(
+        "BAAI/bge-reranker-base".to_string(),
+        Some("refs/pr/5".to_string()),
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({
+        "query": "test",
+        "texts": vec!["test", "other", "test"],
+        "stream": true
+    });
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/rerank")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "application/x-ndjson");
+
+    let body = res.text().await?;
+    let mut indices = body
+        .lines()
+        .map(|line| {
+            let rank: Value = serde_json::from_str(line).unwrap();
+            assert!(rank["score"].is_number());
+            rank["index"].as_u64().unwrap()
+        })
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    assert_eq!(indices, vec![0, 1, 2]);
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_root_info() -> Result<()> {
+    std::env::set_var("ROOT_RESPONSE", "info");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client.get("http://0.0.0.0:8090/").send().await?;
+    assert_eq!(res.status(), 200);
+    let root = res.json::<Value>().await?;
+    assert_eq!(root["name"], "text-embeddings-inference");
+    assert_eq!(root["model_id"], "sentence-transformers/all-MiniLM-L6-v2");
+    assert_eq!(root["docs"], "/docs");
+    let endpoints = root["endpoints"].as_array().unwrap();
+    assert!(endpoints.contains(&json!("/embed")));
+    assert!(endpoints.contains(&json!("/vectors")));
+    // Admin routes are not served without API keys
+    assert!(!endpoints.contains(&json!("/admin/reload")));
+
+    // `POST /` is still the default route of the model
+    let res = client
+        .post("http://0.0.0.0:8090/")
+        .json(&json!({"inputs": "test"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let embeddings = res.json::<Vec<Vec<f32>>>().await?;
+    assert_eq!(embeddings.len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_similarity_threshold() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let sentences = vec!["What is Deep Learning?", "Cheese is made from milk"];
+    let client = reqwest::Client::new();
+
+    let res = client
+        .post("http://0.0.0.0:8090/similarity")
+        .json(&json!({
+            "source_sentence": "What is Deep Learning?",
+            "sentences": sentences,
+        }))
+        .send()
+        .await?;
+    let scores = res.json::<Vec<f32>>().await?;
+    assert_eq!(scores.len(), 2);
+    assert!((scores[0] - 1.0).abs() < 1e-3);
+    assert!(scores[1] < 0.5);
+
+    let res = client
+        .post("http://0.0.0.0:8090/similarity")
+        .json(&json!({
+            "source_sentence": "What is Deep Learning?",
+            "sentences": sentences,
+            "threshold": 0.5,
+        }))
+        .send()
+        .await?;
+    let matches = res.json::<serde_json::Value>().await?;
+    let matches = matches.as_array().unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0]["index"], 0);
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_similarity_normalize() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let source = "What is Deep Learning?";
+    let sentences = vec![
+        "Deep Learning is a subfield of AI",
+        "Cheese is made from milk",
+    ];
+    let client = reqwest::Client::new();
+
+    let similarity = |body: Value| {
+        let client = client.clone();
+        async move {
+            client
+                .post("http://0.0.0.0:8090/similarity")
+                .json(&body)
+                .send()
+                .await
+        }
+    };
+
+    let cosine = similarity(json!({"source_sentence": source, "sentences": sentences}))
+        .await?
+        .json::<Vec<f32>>()
+        .await?;
+
+    // `dot` on normalized embeddings is the cosine similarity
+    let dot = similarity(json!({
+        "source_sentence": source,
+        "sentences": sentences,
+        "metric": "dot",
+    }))
+    .await?
+    .json::<Vec<f32>>()
+    .await?;
+    for (a, b) in cosine.iter().zip(&dot) {
+        assert!((a - b).abs() < 1e-5);
+    }
+
+    // Raw dot products match the unnormalized embeddings
+    let raw = similarity(json!({
+        "source_sentence": source,
+        "sentences": sentences,
+        "metric": "dot",
+        "normalize": false,
+    }))
+    .await?
+    .json::<Vec<f32>>()
+    .await?;
+    let embeddings = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({
+            "inputs": [source, sentences[0], sentences[1]],
+            "normalize": false,
+        }))
+        .send()
+        .await?
+        .json::<Vec<Vec<f32>>>()
+        .await?;
+    for (score, embedding) in raw.iter().zip(&embeddings[1..]) {
+        let expected: f32 = embeddings[0]
+            .iter()
+            .zip(embedding)
+            .map(|(a, b)| a * b)
+            .sum();
+        assert!((score - expected).abs() < 1e-3 * expected.abs().max(1.0));
+    }
+    assert!((raw[0] - cosine[0]).abs() > 1e-3);
+
+    // The cosine similarity is always normalized
+    let res = similarity(json!({
+        "source_sentence": source,
+        "sentences": sentences,
+        "normalize": false,
+    }))
+    .await?;
+    assert_eq!(res.status(), 422);
+    let error = res.json::<Value>().await?;
+    assert_eq!(error["code"], "invalid_normalize");
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_tenant_metrics() -> Result<()> {
+    let keys_file = std::env::temp_dir().join("test_http_tenant_metrics.json");
+    std::fs::write(
+        &keys_file,
+        json!({
+            "acme-key": {"tenant": "acme"},
+            "beta-key": {"tenant": "beta"},
+        })
+        .to_string(),
+    )?;
+    std::env::set_var("API_KEYS_FILE", &keys_file);
+    std::env::set_var("TENANT_METRICS", "acme");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    for key in ["acme-key", "beta-key"] {
+        let res = client
+            .post("http://0.0.0.0:8090/embed")
+            .bearer_auth(key)
+            .json(&json!({"inputs": "test"}))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    let metrics = client
+        .get("http://0.0.0.0:8090/metrics")
+        .send()
+        .await?
+        .text()
+        .await?;
+    assert!(metrics.contains(r#"te_request_success{method="single",tenant="acme"} 1"#));
+    // Tenants missing from the allowlist are not exposed
+    assert!(metrics.contains(r#"te_request_success{method="single",tenant="other"} 1"#));
+    assert!(!metrics.contains("beta"));
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use crate::common::start_server_with_tokenization;
+use anyhow::Result;
+use serde_json::json;
+use std::time::Duration;
+use text_embeddings_backend::DType;
+use tokio::time::Instant;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_tokenization_load() -> Result<()> {
+    // Fewer inputs are tokenized at the same time than there are workers
+    start_server_with_tokenization(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+        4,
+        Some(2),
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let inputs = vec!["test ".repeat(400); 32];
+    let load: Vec<_> = (0..4)
+        .map(|_| {
+            let request = client
+                .post("http://0.0.0.0:8090/embed")
+                .json(&json!({ "inputs": inputs }))
+                .send();
+            tokio::spawn(request)
+        })
+        .collect();
+
+    // Health checks are answered while the batches are tokenized
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for _ in 0..5 {
+        let start = Instant::now();
+        let res = client.get("http://0.0.0.0:8090/health").send().await?;
+        assert_eq!(res.status(), 200);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    for request in load {
+        assert_eq!(request.await??.status(), 200);
+    }
+
+    Ok(())
+}
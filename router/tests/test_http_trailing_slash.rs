@@ -0,0 +1,39 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_trailing_slash() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+
+    // The body of the request is routed as is
+    let res = client
+        .post("http://0.0.0.0:8090/embed/")
+        .json(&json!({"inputs": ["test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let embeddings = res.json::<Vec<Vec<f32>>>().await?;
+    assert_eq!(embeddings.len(), 1);
+
+    for path in ["/health/", "/metrics/", "/meta/?x=1", "/docs/", "/docs"] {
+        let res = client
+            .get(format!("http://0.0.0.0:8090{path}"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200, "{path}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_truncation_headers() -> Result<()> {
+    std::env::set_var("REPORT_TRUNCATION", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": "test ".repeat(600), "truncate": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-truncated"], "true");
+    let input_tokens = res.headers()["x-input-tokens"].to_str()?.parse::<usize>()?;
+    let used_tokens = res.headers()["x-used-tokens"].to_str()?.parse::<usize>()?;
+    assert_eq!(input_tokens, 602);
+    assert_eq!(used_tokens, 512);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&json!({"inputs": ["test", "other"], "truncate": true}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-truncated"], "false");
+    assert_eq!(
+        res.headers()["x-input-tokens"],
+        res.headers()["x-used-tokens"]
+    );
+
+    Ok(())
+}
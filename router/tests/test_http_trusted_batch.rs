@@ -0,0 +1,58 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::json;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_trusted_batch() -> Result<()> {
+    std::env::set_var("TRUSTED_API_KEYS", "trusted-key");
+    std::env::set_var("TRUSTED_MAX_BATCH", "64");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    // Above the default `max_client_batch_size` of 32
+    let request = json!({"inputs": vec!["test"; 40]});
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 413);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .bearer_auth("other-key")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 413);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .bearer_auth("trusted-key")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Vec<Vec<f32>> = res.json().await?;
+    assert_eq!(body.len(), 40);
+
+    let res = client
+        .post("http://0.0.0.0:8090/embed")
+        .bearer_auth("trusted-key")
+        .json(&json!({"inputs": vec!["test"; 65]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 413);
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_weaviate_batch() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let request = json!({"texts": ["test", "another test"]});
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["dim"], 384);
+    assert_eq!(body["vectors"].as_array().unwrap().len(), 2);
+
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .header("Accept", "application/octet-stream")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-shape"], "[2, 384]");
+    assert_eq!(res.headers()["x-dtype"], "float16");
+    let bytes = res.bytes().await?;
+    assert_eq!(bytes.len(), 2 * 384 * 2);
+
+    Ok(())
+}
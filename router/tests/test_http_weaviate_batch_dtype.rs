@@ -0,0 +1,49 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_weaviate_batch_dtype() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .header("Accept", "application/octet-stream")
+        .json(&json!({"texts": ["test", "another test"], "dtype": "float32"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-dtype"], "float32");
+    let bytes = res.bytes().await?;
+    assert_eq!(bytes.len(), 2 * 384 * 4);
+
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .json(&json!({"texts": ["test"]}))
+        .send()
+        .await?;
+    let body: Value = res.json().await?;
+    assert_eq!(body["dtype"], "float32");
+
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .json(&json!({"texts": ["test"], "dtype": "float16"}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["dtype"], "float16");
+    assert_eq!(body["vectors"][0].as_array().unwrap().len(), 384);
+
+    Ok(())
+}
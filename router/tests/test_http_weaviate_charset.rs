@@ -0,0 +1,46 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::Value;
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_weaviate_charset() -> Result<()> {
+    std::env::set_var("TRANSCODE_CHARSET", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    // `café` in Latin-1
+    let body = b"{\"text\": \"caf\xe9\"}".to_vec();
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/vectors")
+        .header("Content-Type", "application/json; charset=ISO-8859-1")
+        .body(body.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let response: Value = res.json().await?;
+    assert_eq!(response["text"], "café");
+
+    // Without a charset the body must be UTF-8
+    let res = client
+        .post("http://0.0.0.0:8090/vectors")
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    let response: Value = res.json().await?;
+    let message = response["error"][0]["message"].as_str().unwrap();
+    assert!(message.contains("not valid UTF-8"));
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_weaviate_probe() -> Result<()> {
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    for _ in 0..2 {
+        // The second probe is answered from cache
+        let res = client
+            .post("http://0.0.0.0:8090/vectors")
+            .json(&json!({"text": "", "probe": true}))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let response: Value = res.json().await?;
+        assert_eq!(response, json!({"dim": 384}));
+    }
+
+    let res = client
+        .post("http://0.0.0.0:8090/vectors")
+        .json(&json!({"text": "test"}))
+        .send()
+        .await?;
+    let response: Value = res.json().await?;
+    assert_eq!(response["dim"], 384);
+    assert_eq!(response["vector"].as_array().unwrap().len(), 384);
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use crate::common::start_server;
+use anyhow::Result;
+use serde_json::{json, Value};
+use text_embeddings_backend::DType;
+
+#[tokio::test]
+#[cfg(feature = "http")]
+async fn test_http_weaviate_stream_parse() -> Result<()> {
+    std::env::set_var("STREAM_PARSE", "true");
+    start_server(
+        "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        None,
+        DType::Float32,
+    )
+    .await?;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://0.0.0.0:8090/vectors/batch")
+        .json(&json!({"texts": ["test", "another test"]}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body: Value = res.json().await?;
+    assert_eq!(body["vectors"].as_array().unwrap().len(), 2);
+
+    let res = client
+        .post("http://0.0.0.0:8090/vectors")
+        .header("Content-Type", "application/json")
+        .body(r#"{"text": "test""#)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    let body: Value = res.json().await?;
+    assert!(body["error"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("EOF"));
+
+    // Streamed bodies are still limited in size
+    let res = client
+        .post("http://0.0.0.0:8090/vectors")
+        .json(&json!({"text": "a".repeat(3 * 1024 * 1024)}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 413);
+
+    Ok(())
+}